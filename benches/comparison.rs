@@ -15,8 +15,8 @@ pub struct SbbfWrapper {
 impl SbbfWrapper {
     pub fn new(entries: usize, fpr: f64) -> Self {
         let ln2 = std::f64::consts::LN_2;
-        let bits_per_key = ((-1.0 * fpr.ln()) / (ln2 * ln2)).ceil() as usize;
-        let num_buckets = (entries * bits_per_key + 255) / 256;
+        let bits_per_key = (-fpr.ln() / (ln2 * ln2)).ceil() as usize;
+        let num_buckets = (entries * bits_per_key).div_ceil(256);
         let buf_size = num_buckets * BUCKET_SIZE;
         
         let layout = Layout::from_size_align(buf_size, ALIGNMENT).unwrap();
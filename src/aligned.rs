@@ -0,0 +1,127 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::CacheLineBlock;
+
+/// Alignment of the first block of every `AlignedBlocks`, one cache line.
+pub(crate) const BLOCK_ARRAY_ALIGNMENT: usize = 64;
+
+/// Block storage whose first block starts on a cache line boundary.
+///
+/// An ordinary `Vec<CacheLineBlock>` is only aligned to the block type's 32 bytes. When its
+/// allocation is not also 64-byte aligned, one spare block is added and the blocks are shifted
+/// up by one, so they start 32 bytes later, on the next cache line. The spare costs 32 bytes
+/// and is kept in front of the blocks, so the array is exactly `storage[start..]`.
+pub(crate) struct AlignedBlocks {
+    storage: Vec<CacheLineBlock>,
+    start: usize,
+}
+
+impl AlignedBlocks {
+    /// Takes ownership of the blocks, moving them at most once to align them.
+    pub(crate) fn new(mut storage: Vec<CacheLineBlock>) -> Self {
+        let len = storage.len();
+        let aligned =
+            |v: &Vec<CacheLineBlock>| (v.as_ptr() as usize).is_multiple_of(BLOCK_ARRAY_ALIGNMENT);
+        if aligned(&storage) {
+            return Self { storage, start: 0 };
+        }
+        // Growing by one may reallocate, and the new allocation may already be aligned.
+        storage.push(CacheLineBlock::default());
+        if aligned(&storage) {
+            storage.truncate(len);
+            return Self { storage, start: 0 };
+        }
+        storage.copy_within(0..len, 1);
+        storage[0] = CacheLineBlock::default();
+        Self { storage, start: 1 }
+    }
+}
+
+impl Deref for AlignedBlocks {
+    type Target = [CacheLineBlock];
+
+    #[inline(always)]
+    fn deref(&self) -> &[CacheLineBlock] {
+        &self.storage[self.start..]
+    }
+}
+
+impl DerefMut for AlignedBlocks {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [CacheLineBlock] {
+        &mut self.storage[self.start..]
+    }
+}
+
+impl Clone for AlignedBlocks {
+    fn clone(&self) -> Self {
+        // Room for the spare block, so aligning never reallocates.
+        let mut storage = Vec::with_capacity(self.len() + 1);
+        storage.extend_from_slice(self);
+        Self::new(storage)
+    }
+}
+
+impl fmt::Debug for AlignedBlocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for AlignedBlocks {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for AlignedBlocks {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for AlignedBlocks {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for AlignedBlocks {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Wipes the blocks in place, keeping their count so the storage stays usable.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for AlignedBlocks {
+    fn zeroize(&mut self) {
+        zeroize::Zeroize::zeroize(self.storage.as_mut_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_start_on_a_cache_line() {
+        for len in [0, 1, 2, 7, 1000] {
+            for _ in 0..8 {
+                let mut storage = vec![CacheLineBlock::default(); len];
+                storage
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, b)| b.words[0] = i as u32);
+                let blocks = AlignedBlocks::new(storage);
+                assert_eq!(blocks.len(), len);
+                assert!((0..len).all(|i| blocks[i].words[0] == i as u32));
+                if len > 0 {
+                    assert_eq!(blocks.as_ptr() as usize % BLOCK_ARRAY_ALIGNMENT, 0);
+                    let clone = blocks.clone();
+                    assert_eq!(clone.as_ptr() as usize % BLOCK_ARRAY_ALIGNMENT, 0);
+                    assert_eq!(clone, blocks);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::hash::Hash;
+
+use crate::{
+    BlockMapping, BlockedBloomFilter, CacheLineBlock, DecodeError, FilterHeader, HashAlgorithm,
+};
+
+/// A filter queried in place from bytes produced by `to_bytes`.
+///
+/// Construction validates the header and payload length but copies nothing, so a memory-mapped
+/// or cached buffer can answer queries without rebuilding the block vector. The payload needs
+/// no particular alignment.
+#[derive(Clone, Copy, Debug)]
+pub struct ArchivedFilter<'a> {
+    bytes: &'a [u8],
+    num_blocks: u32,
+    seed: u64,
+    mapping: BlockMapping,
+    hash: HashAlgorithm,
+}
+
+impl<'a> ArchivedFilter<'a> {
+    /// Validates `bytes` as an encoded filter and wraps them without copying.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        let header = FilterHeader::parse(bytes)?;
+        let payload = &bytes[FilterHeader::LEN..];
+        if payload.len() != header.payload_len() {
+            return Err(DecodeError::LengthMismatch {
+                expected: header.payload_len(),
+                actual: payload.len(),
+            });
+        }
+        Ok(Self {
+            bytes,
+            num_blocks: header.num_blocks,
+            seed: header.seed,
+            mapping: header.mapping,
+            hash: header.hash,
+        })
+    }
+
+    /// Returns the number of blocks.
+    pub fn num_blocks(&self) -> u32 {
+        self.num_blocks
+    }
+
+    /// Returns the seed used to hash keys.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the algorithm used to hash keys.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
+
+    /// Returns the encoded filter, header included.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the encoded block payload.
+    pub(crate) fn payload(&self) -> &'a [u8] {
+        &self.bytes[FilterHeader::LEN..]
+    }
+
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let block_idx = self.mapping.block_index(h, self.num_blocks);
+        let start = block_idx * size_of::<CacheLineBlock>();
+        let block = &self.payload()[start..start + size_of::<CacheLineBlock>()];
+        let check = block
+            .chunks_exact(4)
+            .zip(BlockedBloomFilter::SALT.iter())
+            .fold(0u32, |acc, (w, &salt)| {
+                let w = u32::from_le_bytes(w.try_into().unwrap());
+                let idx = (h as u32).wrapping_mul(salt) >> 27;
+                acc | ((1 << idx) & !w)
+            });
+        check == 0
+    }
+
+    /// Sets the bits for a hash in `bytes`, a mutable copy of this filter's encoding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is shorter than the encoding.
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn insert_hash_into(&self, bytes: &mut [u8], h: u64) {
+        let block_idx = self.mapping.block_index(h, self.num_blocks);
+        let start = FilterHeader::LEN + block_idx * size_of::<CacheLineBlock>();
+        let block = &mut bytes[start..start + size_of::<CacheLineBlock>()];
+        for (w, &salt) in block
+            .chunks_exact_mut(4)
+            .zip(BlockedBloomFilter::SALT.iter())
+        {
+            let idx = (h as u32).wrapping_mul(salt) >> 27;
+            let word = u32::from_le_bytes((&*w).try_into().unwrap()) | (1 << idx);
+            w.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Copies the blocks into an owned filter.
+    pub fn to_filter(&self) -> BlockedBloomFilter {
+        BlockedBloomFilter::from_blocks(
+            crate::binary::blocks_from_le_bytes(self.payload()),
+            self.seed,
+        )
+        .with_mapping(self.mapping)
+        .with_hash(self.hash)
+    }
+}
+
+/// Serializes as the `to_bytes` encoding.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ArchivedFilter<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.bytes)
+    }
+}
+
+/// Borrows the `to_bytes` encoding from the input, for formats that can lend out byte slices.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ArchivedFilter<'de> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = ArchivedFilter<'de>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("borrowed bytes of an encoded filter")
+            }
+
+            fn visit_borrowed_bytes<E: serde::de::Error>(
+                self,
+                bytes: &'de [u8],
+            ) -> Result<Self::Value, E> {
+                ArchivedFilter::from_bytes(bytes).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archived_queries_match_owned() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 9);
+        for i in 0..1000u64 {
+            bf.insert_key(&i);
+        }
+        let bytes = bf.to_bytes();
+        // Query from an unaligned offset to check the payload needs no alignment.
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&bytes);
+        let archived = ArchivedFilter::from_bytes(&shifted[1..]).unwrap();
+
+        for i in 0..5000u64 {
+            assert_eq!(archived.may_match_key(&i), bf.may_match_key(&i));
+        }
+        assert_eq!(archived.to_filter().params(), bf.params());
+        assert!(ArchivedFilter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_borrows_input() {
+        use serde::Deserialize;
+        use serde::de::value::{BorrowedBytesDeserializer, Error};
+
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key("Leopold Bloom");
+        let bytes = bf.to_bytes();
+
+        let archived =
+            ArchivedFilter::deserialize(BorrowedBytesDeserializer::<Error>::new(&bytes)).unwrap();
+        assert!(std::ptr::eq(archived.as_bytes(), &bytes[..]));
+        assert!(archived.may_match_key("Leopold Bloom"));
+    }
+}
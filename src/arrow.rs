@@ -0,0 +1,218 @@
+use std::hash::Hash;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Int8Type, Int16Type, Int32Type, Int64Type, UInt8Type, UInt16Type, UInt32Type, UInt64Type,
+};
+use arrow_array::{Array, ArrayRef, BooleanArray};
+use arrow_buffer::{BooleanBuffer, Buffer};
+use arrow_schema::{ArrowError, DataType};
+
+use crate::binary::blocks_from_le_bytes;
+use crate::{BlockedBloomFilter, FilterParams};
+
+impl BlockedBloomFilter {
+    /// Inserts every non-null value of a string, binary or integer array.
+    ///
+    /// Values are hashed like the equivalent `insert_key` call (`&str`, `&[u8]` or the native
+    /// integer), so they can be queried through either API.
+    pub fn insert_array(&mut self, array: &ArrayRef) -> Result<(), ArrowError> {
+        let hashes = self.hash_array(array)?;
+        match array.logical_nulls() {
+            Some(nulls) => nulls
+                .valid_indices()
+                .for_each(|i| self.insert_hash(hashes[i])),
+            None => hashes.into_iter().for_each(|h| self.insert_hash(h)),
+        }
+        Ok(())
+    }
+
+    /// Probes every value of a string, binary or integer array.
+    ///
+    /// Null inputs produce null outputs.
+    pub fn probe_array(&self, array: &ArrayRef) -> Result<BooleanArray, ArrowError> {
+        let hashes = self.hash_array(array)?;
+        let values = BooleanBuffer::collect_bool(hashes.len(), |i| self.may_match_hash(hashes[i]));
+        Ok(BooleanArray::new(values, array.logical_nulls()))
+    }
+
+    /// Probes every value of an array and writes the indices of possible matches to `selection`.
+    ///
+    /// Null slots are never selected. See `select_hashes` for the output format.
+    pub fn select_array(
+        &self,
+        array: &ArrayRef,
+        selection: &mut Vec<u32>,
+    ) -> Result<usize, ArrowError> {
+        let hashes = self.hash_array(array)?;
+        self.select_hashes(&hashes, selection);
+        if let Some(nulls) = array.logical_nulls() {
+            selection.retain(|&i| nulls.is_valid(i as usize));
+        }
+        Ok(selection.len())
+    }
+
+    /// Builds a runtime filter from the build-side key columns of a hash join.
+    ///
+    /// The filter is sized for the non-null values across all `arrays`, which are usually the
+    /// batches of the join key column. Probe-side batches can then be pruned with `probe_array`
+    /// or `select_array` before they reach the join.
+    pub fn from_build_side(arrays: &[ArrayRef], fpr: f64) -> Result<Self, ArrowError> {
+        let entries = arrays
+            .iter()
+            .map(|a| a.len() - a.logical_null_count())
+            .sum();
+        let mut filter = Self::new(entries, fpr);
+        for array in arrays {
+            filter.insert_array(array)?;
+        }
+        Ok(filter)
+    }
+
+    /// Returns the block payload as an Arrow `Buffer` together with the parameters needed to
+    /// rebuild the filter.
+    ///
+    /// The buffer holds the block words in little-endian order, 32 bytes per block, and takes
+    /// ownership of the encoded bytes without another copy. It can be sent as-is in Arrow IPC
+    /// or Flight messages, e.g. as the values of a `FixedSizeBinary(32)` array, with the
+    /// parameters carried alongside as metadata.
+    pub fn to_arrow_buffer(&self) -> (Buffer, FilterParams) {
+        let mut payload = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut payload);
+        (Buffer::from_vec(payload), self.params())
+    }
+
+    /// Rebuilds a filter from a buffer and parameters produced by `to_arrow_buffer`.
+    pub fn from_arrow_buffer(buffer: &Buffer, params: FilterParams) -> Result<Self, ArrowError> {
+        let invalid = |e: &dyn std::fmt::Display| ArrowError::InvalidArgumentError(e.to_string());
+        let empty = Self::with_params(params).map_err(|e| invalid(&e))?;
+        let expected = empty.header().payload_len();
+        if buffer.len() != expected {
+            return Err(invalid(&format!(
+                "expected {expected} bytes of filter data, got {}",
+                buffer.len()
+            )));
+        }
+        Ok(
+            Self::from_blocks(blocks_from_le_bytes(buffer.as_slice()), params.seed)
+                .with_mapping(params.mapping)
+                .with_hash(params.hash),
+        )
+    }
+
+    /// Hashes every slot of the array in one pass, including null slots.
+    fn hash_array(&self, array: &dyn Array) -> Result<Vec<u64>, ArrowError> {
+        macro_rules! hash_each_value {
+            ($array:expr) => {{
+                let array = $array;
+                self.hash_values((0..array.len()).map(|i| array.value(i)))
+            }};
+        }
+
+        let hashes = match array.data_type() {
+            DataType::Utf8 => hash_each_value!(array.as_string::<i32>()),
+            DataType::LargeUtf8 => hash_each_value!(array.as_string::<i64>()),
+            DataType::Utf8View => hash_each_value!(array.as_string_view()),
+            DataType::Binary => hash_each_value!(array.as_binary::<i32>()),
+            DataType::LargeBinary => hash_each_value!(array.as_binary::<i64>()),
+            DataType::BinaryView => hash_each_value!(array.as_binary_view()),
+            DataType::Int8 => self.hash_values(array.as_primitive::<Int8Type>().values()),
+            DataType::Int16 => self.hash_values(array.as_primitive::<Int16Type>().values()),
+            DataType::Int32 => self.hash_values(array.as_primitive::<Int32Type>().values()),
+            DataType::Int64 => self.hash_values(array.as_primitive::<Int64Type>().values()),
+            DataType::UInt8 => self.hash_values(array.as_primitive::<UInt8Type>().values()),
+            DataType::UInt16 => self.hash_values(array.as_primitive::<UInt16Type>().values()),
+            DataType::UInt32 => self.hash_values(array.as_primitive::<UInt32Type>().values()),
+            DataType::UInt64 => self.hash_values(array.as_primitive::<UInt64Type>().values()),
+            other => {
+                return Err(ArrowError::NotYetImplemented(format!(
+                    "bloom filter hashing for {other} arrays"
+                )));
+            }
+        };
+        Ok(hashes)
+    }
+
+    fn hash_values<'a, T, I>(&self, values: I) -> Vec<u64>
+    where
+        T: Hash + ?Sized + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        values.into_iter().map(|v| self.hash_key(v)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockMapping;
+    use arrow_array::{Float64Array, Int64Array, StringArray};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_insert_and_probe_array() {
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+
+        let strings: ArrayRef = Arc::new(StringArray::from(vec![Some("Leopold"), None]));
+        let ints: ArrayRef = Arc::new(Int64Array::from(vec![1904, 1922]));
+        bf.insert_array(&strings).unwrap();
+        bf.insert_array(&ints).unwrap();
+
+        assert!(bf.may_match_key("Leopold"));
+        assert!(bf.may_match_key(&1904i64));
+
+        let probe: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("Leopold"),
+            Some("Molly"),
+            None,
+        ]));
+        let result = bf.probe_array(&probe).unwrap();
+        assert_eq!(
+            result,
+            BooleanArray::from(vec![Some(true), Some(false), None])
+        );
+        let mut selection = Vec::new();
+        assert_eq!(bf.select_array(&probe, &mut selection).unwrap(), 1);
+        assert_eq!(selection, [0]);
+
+        let floats: ArrayRef = Arc::new(Float64Array::from(vec![1.0]));
+        assert!(bf.insert_array(&floats).is_err());
+    }
+
+    #[test]
+    fn test_from_build_side() {
+        let build: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(vec![Some(1), None, Some(2)])),
+            Arc::new(Int64Array::from(vec![3])),
+        ];
+        let bf = BlockedBloomFilter::from_build_side(&build, 0.01).unwrap();
+        assert_eq!(bf.num_blocks, BlockedBloomFilter::new(3, 0.01).num_blocks);
+
+        let probe: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        let result = bf.probe_array(&probe).unwrap();
+        assert_eq!(result.true_count(), 3);
+    }
+
+    #[test]
+    fn test_arrow_buffer_round_trip() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 3);
+        bf.insert_key("Leopold Bloom");
+
+        let (buffer, params) = bf.to_arrow_buffer();
+        assert_eq!(buffer.len(), bf.header().payload_len());
+        let decoded = BlockedBloomFilter::from_arrow_buffer(&buffer, params).unwrap();
+        assert_eq!(decoded.blocks[..], bf.blocks[..]);
+        assert!(decoded.may_match_key("Leopold Bloom"));
+
+        let mut mixed = BlockedBloomFilter::new_with_mapping(1000, 0.01, 3, BlockMapping::Mixed);
+        mixed.insert_key("Molly Bloom");
+        let (buffer, params) = mixed.to_arrow_buffer();
+        let decoded = BlockedBloomFilter::from_arrow_buffer(&buffer, params).unwrap();
+        assert_eq!(decoded.params(), params);
+        assert!(decoded.may_match_key("Molly Bloom"));
+
+        let (buffer, params) = bf.to_arrow_buffer();
+        let truncated = buffer.slice_with_length(0, buffer.len() - 32);
+        assert!(BlockedBloomFilter::from_arrow_buffer(&truncated, params).is_err());
+    }
+}
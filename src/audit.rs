@@ -0,0 +1,182 @@
+use crate::{BlockedBloomFilter, FilterDiagnostics};
+
+/// One sampled query: the probed hash and the filter's answer.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuerySample {
+    /// The probed hash.
+    pub hash: u64,
+    /// Whether the filter answered "may match".
+    pub matched: bool,
+}
+
+/// Keeps a uniform random sample of the queries made through it, for auditing the false
+/// positive rate offline.
+///
+/// Reservoir sampling holds at most `capacity` samples however many queries are recorded,
+/// each query equally likely to be kept. Pass it to `may_match_hash_sampled`; queries made
+/// without it are not seen. Export with `BlockedBloomFilter::export_query_audit`.
+#[derive(Clone, Debug)]
+pub struct QuerySampler {
+    capacity: usize,
+    seen: u64,
+    samples: Vec<QuerySample>,
+    rng: u64,
+}
+
+impl QuerySampler {
+    /// Creates a sampler keeping up to `capacity` samples, choosing them with a generator
+    /// seeded by `seed`.
+    pub fn new(capacity: usize, seed: u64) -> Self {
+        Self {
+            capacity,
+            seen: 0,
+            samples: Vec::with_capacity(capacity),
+            rng: seed,
+        }
+    }
+
+    /// SplitMix64: cheap and good enough to pick reservoir slots.
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Records a query, keeping it with probability `capacity / seen`.
+    pub fn record(&mut self, hash: u64, matched: bool) {
+        self.seen += 1;
+        let sample = QuerySample { hash, matched };
+        if self.samples.len() < self.capacity {
+            self.samples.push(sample);
+            return;
+        }
+        let slot = ((self.next_random() as u128 * self.seen as u128) >> 64) as usize;
+        if let Some(kept) = self.samples.get_mut(slot) {
+            *kept = sample;
+        }
+    }
+
+    /// Returns the number of queries recorded.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Returns the samples kept so far, in no particular order.
+    pub fn samples(&self) -> &[QuerySample] {
+        &self.samples
+    }
+
+    /// Drops the samples and counts, keeping the capacity.
+    pub fn clear(&mut self) {
+        self.seen = 0;
+        self.samples.clear();
+    }
+}
+
+/// A filter's diagnostics together with sampled queries, exported for an offline audit.
+///
+/// Unlike `FilterDiagnostics` alone this carries query hashes, so treat it like the keys
+/// themselves.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryAudit {
+    /// The filter's key-free diagnostics at export time.
+    pub diagnostics: FilterDiagnostics,
+    /// Queries the sampler saw.
+    pub queries_seen: u64,
+    /// The sampled queries.
+    pub samples: Vec<QuerySample>,
+}
+
+/// The false positive rate measured by checking sampled queries against ground truth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FprAudit {
+    /// Sampled queries for hashes that are not in the set.
+    pub negatives: u64,
+    /// Of those, how many the filter matched.
+    pub false_positives: u64,
+    /// `false_positives / negatives`, or 0 without negatives.
+    pub observed_fpr: f64,
+    /// The rate the filter's diagnostics predicted.
+    pub estimated_fpr: f64,
+}
+
+impl QueryAudit {
+    /// Checks the samples against ground truth, `is_member` telling whether a hash's key is
+    /// really in the set. Samples of members are skipped.
+    pub fn audit(&self, mut is_member: impl FnMut(u64) -> bool) -> FprAudit {
+        let (negatives, false_positives) = self
+            .samples
+            .iter()
+            .filter(|sample| !is_member(sample.hash))
+            .fold((0, 0), |(n, fp), sample| {
+                (n + 1, fp + sample.matched as u64)
+            });
+        FprAudit {
+            negatives,
+            false_positives,
+            observed_fpr: if negatives == 0 {
+                0.0
+            } else {
+                false_positives as f64 / negatives as f64
+            },
+            estimated_fpr: self.diagnostics.estimated_fpr,
+        }
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Checks if the filter might contain the hash and records the query in `sampler`.
+    #[inline]
+    pub fn may_match_hash_sampled(&self, h: u64, sampler: &mut QuerySampler) -> bool {
+        let matched = self.may_match_hash(h);
+        sampler.record(h, matched);
+        matched
+    }
+
+    /// Exports the diagnostics with the sampler's queries for an offline audit.
+    ///
+    /// The opt-in counterpart of `export_diagnostics`: the result holds query hashes.
+    pub fn export_query_audit(&self, sampler: &QuerySampler) -> QueryAudit {
+        QueryAudit {
+            diagnostics: self.export_diagnostics(),
+            queries_seen: sampler.seen(),
+            samples: sampler.samples().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_measures_fpr() {
+        let mut bf = BlockedBloomFilter::new(10_000, 0.01);
+        (0..10_000u64).for_each(|i| bf.insert_key(&i));
+        let mut sampler = QuerySampler::new(20_000, 1);
+        let mut truth = std::collections::HashSet::new();
+        for i in (0..200_000u64).step_by(2) {
+            let h = bf.hash_key(&i);
+            if i < 10_000 {
+                truth.insert(h);
+            }
+            bf.may_match_hash_sampled(h, &mut sampler);
+        }
+        assert_eq!(sampler.seen(), 100_000);
+        assert_eq!(sampler.samples().len(), 20_000);
+
+        let audit = bf
+            .export_query_audit(&sampler)
+            .audit(|h| truth.contains(&h));
+        // About 5% of the sampled queries were members.
+        assert!((18_700..19_300).contains(&audit.negatives), "{audit:?}");
+        assert!(
+            (audit.observed_fpr - audit.estimated_fpr).abs() < 0.004,
+            "{audit:?}"
+        );
+    }
+}
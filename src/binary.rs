@@ -0,0 +1,355 @@
+use std::fmt;
+
+use crate::{BlockMapping, BlockedBloomFilter, CacheLineBlock, ErrorKind, HashAlgorithm};
+
+const MAGIC: [u8; 4] = *b"BLMS";
+const FORMAT_VERSION: u32 = 2;
+/// Oldest layout version that can still be read (and upgraded).
+const MIN_FORMAT_VERSION: u32 = 1;
+
+/// How to treat filters written with an older layout version.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompatPolicy {
+    /// Fail with `DecodeError::OlderVersion` unless the layout is the current one.
+    RejectOlder,
+    /// Convert readable older layouts to the current one.
+    #[default]
+    AcceptAndUpgrade,
+}
+
+/// Fixed-size header that precedes the block payload in the binary format.
+///
+/// All fields are little-endian: magic (4 bytes), version (4), block count (4), hashing (4)
+/// and seed (8). The payload is the block words, also little-endian.
+///
+/// The hashing word holds the block mapping in its low 16 bits (0 is `Direct`, 1 is `Mixed`)
+/// and the key hash algorithm in the next 8 (0 is `Xxh64`, 1 is `Xxh3`); the rest is zero.
+/// The seed is the algorithm's whole key. The word was reserved and ignored in version 1, so
+/// it came with version 2: version 1 readers reject every newer file rather than misreading
+/// one that uses another mapping or algorithm. Version 1 files are still read; their word
+/// must be zero, `Direct` and `Xxh64`, the only combination that existed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FilterHeader {
+    pub version: u32,
+    pub num_blocks: u32,
+    pub mapping: BlockMapping,
+    pub hash: HashAlgorithm,
+    pub seed: u64,
+}
+
+impl FilterHeader {
+    /// Length of the encoded header in bytes.
+    pub const LEN: usize = 24;
+
+    /// Parses and validates a header from the start of `bytes`.
+    ///
+    /// Any readable layout version is accepted; see `check_version` for applying a policy.
+    pub fn parse(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < Self::LEN {
+            return Err(DecodeError::LengthMismatch {
+                expected: Self::LEN,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let hashing = u32_at(12);
+        if hashing >> 24 != 0 {
+            return Err(DecodeError::UnsupportedMapping(hashing));
+        }
+        let header = Self {
+            version: u32_at(4),
+            num_blocks: u32_at(8),
+            mapping: BlockMapping::from_u32(hashing & 0xffff)
+                .ok_or(DecodeError::UnsupportedMapping(hashing & 0xffff))?,
+            hash: HashAlgorithm::from_u32(hashing >> 16)
+                .ok_or(DecodeError::UnsupportedHash(hashing >> 16))?,
+            seed: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        };
+        if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&header.version) {
+            return Err(DecodeError::UnsupportedVersion {
+                found: header.version,
+                current: FORMAT_VERSION,
+            });
+        }
+        if header.version == 1 && hashing != 0 {
+            return Err(DecodeError::UnsupportedMapping(hashing));
+        }
+        if header.num_blocks == 0 {
+            return Err(DecodeError::NoBlocks);
+        }
+        Ok(header)
+    }
+
+    /// Checks the header's layout version against `policy`.
+    pub fn check_version(&self, policy: CompatPolicy) -> Result<(), DecodeError> {
+        if self.version < FORMAT_VERSION && policy == CompatPolicy::RejectOlder {
+            return Err(DecodeError::OlderVersion {
+                found: self.version,
+                current: FORMAT_VERSION,
+            });
+        }
+        Ok(())
+    }
+
+    /// Length of the block payload that follows the header, in bytes.
+    pub fn payload_len(&self) -> usize {
+        self.num_blocks as usize * size_of::<CacheLineBlock>()
+    }
+
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&self.num_blocks.to_le_bytes());
+        let hashing = self.mapping.to_u32() | self.hash.to_u32() << 16;
+        out.extend_from_slice(&hashing.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+    }
+}
+
+/// Errors produced when decoding the binary format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input does not start with the format's magic bytes.
+    BadMagic,
+    /// The header names a layout version this build cannot read.
+    UnsupportedVersion { found: u32, current: u32 },
+    /// The layout version is older than the current one and the policy rejects it.
+    OlderVersion { found: u32, current: u32 },
+    /// The header describes a filter without blocks.
+    NoBlocks,
+    /// The input length does not match the length implied by the header.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The header names a block mapping this build does not implement.
+    UnsupportedMapping(u32),
+    /// The header names a key hash algorithm this build does not implement.
+    UnsupportedHash(u32),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a bloomsday filter (bad magic bytes)"),
+            Self::UnsupportedVersion { found, current } => write!(
+                f,
+                "unsupported filter layout version {found} (this build reads \
+                 {MIN_FORMAT_VERSION} through {current})"
+            ),
+            Self::OlderVersion { found, current } => write!(
+                f,
+                "filter layout version {found} is older than the current version {current}"
+            ),
+            Self::NoBlocks => write!(f, "filter header has zero blocks"),
+            Self::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes of filter data, got {actual}")
+            }
+            Self::UnsupportedMapping(m) => write!(f, "unsupported block mapping {m}"),
+            Self::UnsupportedHash(h) => write!(f, "unsupported key hash algorithm {h}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl DecodeError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnsupportedVersion { .. }
+            | Self::OlderVersion { .. }
+            | Self::UnsupportedMapping(_)
+            | Self::UnsupportedHash(_) => ErrorKind::VersionMismatch,
+            Self::BadMagic | Self::NoBlocks | Self::LengthMismatch { .. } => ErrorKind::Corrupt,
+        }
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Returns the layout version written by `to_bytes`.
+    pub const fn layout_version() -> u32 {
+        FORMAT_VERSION
+    }
+
+    /// Returns the header describing this filter in the binary format.
+    pub fn header(&self) -> FilterHeader {
+        FilterHeader {
+            version: FORMAT_VERSION,
+            num_blocks: self.num_blocks,
+            mapping: self.mapping,
+            hash: self.hash,
+            seed: self.seed,
+        }
+    }
+
+    /// Encodes the filter in the binary format (header followed by block payload).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = self.header();
+        let mut out = Vec::with_capacity(FilterHeader::LEN + header.payload_len());
+        header.write(&mut out);
+        self.write_payload(&mut out);
+        out
+    }
+
+    /// Decodes a filter produced by `to_bytes`, upgrading older layouts.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::from_bytes_with_policy(bytes, CompatPolicy::default())
+    }
+
+    /// Decodes a filter produced by `to_bytes`, treating older layouts according to `policy`.
+    pub fn from_bytes_with_policy(bytes: &[u8], policy: CompatPolicy) -> Result<Self, DecodeError> {
+        let header = FilterHeader::parse(bytes)?;
+        header.check_version(policy)?;
+        Self::from_header_and_payload(&header, &bytes[FilterHeader::LEN..])
+    }
+
+    /// Decodes the block payload that follows an already parsed header.
+    pub fn from_header_and_payload(
+        header: &FilterHeader,
+        payload: &[u8],
+    ) -> Result<Self, DecodeError> {
+        if payload.len() != header.payload_len() {
+            return Err(DecodeError::LengthMismatch {
+                expected: header.payload_len(),
+                actual: payload.len(),
+            });
+        }
+
+        Ok(
+            Self::from_blocks(blocks_from_le_bytes(payload), header.seed)
+                .with_mapping(header.mapping)
+                .with_hash(header.hash),
+        )
+    }
+
+    /// Appends the block words to `out` in little-endian order.
+    pub(crate) fn write_payload(&self, out: &mut Vec<u8>) {
+        for block in self.blocks.iter() {
+            for w in block.words {
+                out.extend_from_slice(&w.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Decodes little-endian block words, ignoring any trailing partial block.
+pub(crate) fn blocks_from_le_bytes(bytes: &[u8]) -> Vec<CacheLineBlock> {
+    bytes
+        .chunks_exact(size_of::<CacheLineBlock>())
+        .map(|chunk| {
+            let mut block = CacheLineBlock::default();
+            for (w, bytes) in block.words.iter_mut().zip(chunk.chunks_exact(4)) {
+                *w = u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+            block
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 7);
+        bf.insert_key("Leopold Bloom");
+
+        let bytes = bf.to_bytes();
+        assert_eq!(bytes.len(), FilterHeader::LEN + bf.header().payload_len());
+
+        let decoded = BlockedBloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.header(), bf.header());
+        assert!(decoded.may_match_key("Leopold Bloom"));
+        assert!(!decoded.may_match_key("Molly Bloom"));
+    }
+
+    #[test]
+    fn test_binary_rejects_corrupt_input() {
+        let bytes = BlockedBloomFilter::new(100, 0.01).to_bytes();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(
+            BlockedBloomFilter::from_bytes(&bad_magic).unwrap_err(),
+            DecodeError::BadMagic
+        );
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 9;
+        assert_eq!(
+            BlockedBloomFilter::from_bytes(&bad_version).unwrap_err(),
+            DecodeError::UnsupportedVersion {
+                found: 9,
+                current: BlockedBloomFilter::layout_version()
+            }
+        );
+
+        assert!(matches!(
+            BlockedBloomFilter::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_compat_policy() {
+        let current = BlockedBloomFilter::new(100, 0.01).header();
+        assert!(current.check_version(CompatPolicy::RejectOlder).is_ok());
+
+        let older = FilterHeader {
+            version: current.version - 1,
+            ..current
+        };
+        assert!(older.check_version(CompatPolicy::AcceptAndUpgrade).is_ok());
+        assert_eq!(
+            older.check_version(CompatPolicy::RejectOlder).unwrap_err(),
+            DecodeError::OlderVersion {
+                found: older.version,
+                current: current.version
+            }
+        );
+    }
+
+    #[test]
+    fn test_hash_algorithm_survives_encoding() {
+        let mut bf = BlockedBloomFilter::new_with_hash(1000, 0.01, 7, HashAlgorithm::Xxh3);
+        bf.insert_key("Leopold Bloom");
+        let bytes = bf.to_bytes();
+        assert_eq!(bytes[12..16], [0, 0, 1, 0]);
+
+        let decoded = BlockedBloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.hash_algorithm(), HashAlgorithm::Xxh3);
+        assert!(decoded.may_match_key("Leopold Bloom"));
+        let xxh64 = BlockedBloomFilter::from_blocks(decoded.blocks.to_vec(), 7);
+        assert!(!xxh64.may_match_key("Leopold Bloom"));
+
+        let mut unknown = bytes.clone();
+        unknown[14] = 9;
+        assert_eq!(
+            BlockedBloomFilter::from_bytes(&unknown).unwrap_err(),
+            DecodeError::UnsupportedHash(9)
+        );
+    }
+
+    #[test]
+    fn test_version_1_files_are_xxh64_and_direct() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 7);
+        bf.insert_key("Leopold Bloom");
+        let mut v1 = bf.to_bytes();
+        v1[4] = 1;
+        let decoded = BlockedBloomFilter::from_bytes(&v1).unwrap();
+        assert_eq!(decoded.params(), bf.params());
+        assert!(decoded.may_match_key("Leopold Bloom"));
+
+        // Version 1 never wrote the hashing word, so a v1 header with one set is not genuine.
+        let mut xxh3 =
+            BlockedBloomFilter::new_with_hash(1000, 0.01, 7, HashAlgorithm::Xxh3).to_bytes();
+        xxh3[4] = 1;
+        assert_eq!(
+            BlockedBloomFilter::from_bytes(&xxh3).unwrap_err(),
+            DecodeError::UnsupportedMapping(1 << 16)
+        );
+    }
+}
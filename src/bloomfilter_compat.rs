@@ -0,0 +1,148 @@
+//! A drop-in adapter with the API of the `bloomfilter` crate's `Bloom`.
+//!
+//! Projects on `bloomfilter` can switch by changing an import,
+//!
+//! ```
+//! use bloomsday::bloomfilter_compat::Bloom;
+//!
+//! let mut seen: Bloom<str> = Bloom::new_for_fp_rate(1000, 0.01);
+//! assert!(!seen.check_and_set("Leopold Bloom"));
+//! assert!(seen.check("Leopold Bloom"));
+//! ```
+//!
+//! and then move to `BlockedBloomFilter` one call site at a time through `filter` and
+//! `into_inner`. Only the code carries over: the filter underneath is blocked and hashes with
+//! xxHash rather than SipHash, so bitmaps saved by `bloomfilter` cannot be loaded, and
+//! `number_of_hash_functions` is always the 8 probes of a block.
+
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::hash_compat::xxh64_bytes;
+use crate::{BlockedBloomFilter, CacheLineBlock, math};
+
+/// A filter over items of type `T` with the method names of `bloomfilter::Bloom`.
+#[derive(Clone, Debug)]
+pub struct Bloom<T: ?Sized> {
+    filter: BlockedBloomFilter,
+    items: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> Bloom<T> {
+    /// Creates a filter with a bitmap of about `bitmap_size` bytes, rounded up to whole
+    /// blocks. `items_count` is accepted for compatibility; the blocked layout does not need it.
+    pub fn new(bitmap_size: usize, items_count: usize) -> Self {
+        Self::new_with_seed(bitmap_size, items_count, &[0; 32])
+    }
+
+    /// Creates a filter sized for `items_count` items at false positive rate `fp_p`.
+    pub fn new_for_fp_rate(items_count: usize, fp_p: f64) -> Self {
+        Self::new_for_fp_rate_with_seed(items_count, fp_p, &[0; 32])
+    }
+
+    /// Like `new`, hashing with a seed derived from the 32 seed bytes.
+    pub fn new_with_seed(bitmap_size: usize, _items_count: usize, seed: &[u8; 32]) -> Self {
+        let num_blocks = bitmap_size.div_ceil(size_of::<CacheLineBlock>()).max(1);
+        Self::from_filter(BlockedBloomFilter::from_blocks(
+            vec![CacheLineBlock::default(); num_blocks],
+            xxh64_bytes(0, seed),
+        ))
+    }
+
+    /// Like `new_for_fp_rate`, hashing with a seed derived from the 32 seed bytes.
+    pub fn new_for_fp_rate_with_seed(items_count: usize, fp_p: f64, seed: &[u8; 32]) -> Self {
+        Self::new_with_seed(
+            Self::compute_bitmap_size(items_count, fp_p),
+            items_count,
+            seed,
+        )
+    }
+
+    /// Returns the bitmap size in bytes needed for `items_count` items at `fp_p`, sized with
+    /// the blocked model so the rate holds for the blocked layout.
+    pub fn compute_bitmap_size(items_count: usize, fp_p: f64) -> usize {
+        math::blocked_num_blocks_for(items_count, fp_p) as usize * size_of::<CacheLineBlock>()
+    }
+
+    /// Wraps an existing filter.
+    pub fn from_filter(filter: BlockedBloomFilter) -> Self {
+        Self {
+            filter,
+            items: PhantomData,
+        }
+    }
+
+    /// Records an item.
+    pub fn set(&mut self, item: &T) {
+        self.filter.insert_key(item);
+    }
+
+    /// Checks if an item might have been recorded.
+    pub fn check(&self, item: &T) -> bool {
+        self.filter.may_match_key(item)
+    }
+
+    /// Records an item and returns whether it might have been recorded before.
+    pub fn check_and_set(&mut self, item: &T) -> bool {
+        !self.filter.insert_hash_checked(self.filter.hash_key(item))
+    }
+
+    /// Forgets every item.
+    pub fn clear(&mut self) {
+        self.filter.clear();
+    }
+
+    /// Returns a copy of the bitmap, the block words in little-endian order.
+    pub fn bitmap(&self) -> Vec<u8> {
+        let mut bitmap = Vec::with_capacity(self.filter.header().payload_len());
+        self.filter.write_payload(&mut bitmap);
+        bitmap
+    }
+
+    /// Returns the bitmap size in bits.
+    pub fn number_of_bits(&self) -> u64 {
+        self.filter.num_blocks as u64 * CacheLineBlock::BITS as u64
+    }
+
+    /// Returns the number of bits set per item, always 8.
+    pub fn number_of_hash_functions(&self) -> u32 {
+        BlockedBloomFilter::PROBES
+    }
+
+    /// Returns the underlying filter.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Returns the underlying filter, for code that has finished migrating.
+    pub fn into_inner(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_api() {
+        let mut bloom = Bloom::new_for_fp_rate(10_000, 0.01);
+        let repeats = (0..10_000u64).filter(|i| bloom.check_and_set(i)).count();
+        assert!(repeats < 100, "{repeats}");
+        assert!((0..10_000u64).all(|i| bloom.check(&i)));
+        let fp = (10_000..110_000u64).filter(|i| bloom.check(i)).count();
+        assert!(fp < 1000, "{fp}");
+
+        assert_eq!(bloom.bitmap().len() as u64 * 8, bloom.number_of_bits());
+        assert_eq!(bloom.number_of_hash_functions(), 8);
+        assert_eq!(
+            Bloom::<u64>::compute_bitmap_size(10_000, 0.01) as u64 * 8,
+            bloom.number_of_bits()
+        );
+        assert!(bloom.filter().may_match_key(&1u64));
+
+        bloom.clear();
+        assert!(!bloom.check(&1));
+        assert_eq!(Bloom::<str>::new(33, 0).number_of_bits(), 512);
+    }
+}
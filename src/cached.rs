@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::BlockedBloomFilter;
+
+const NIL: usize = usize::MAX;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    hash: u64,
+    result: bool,
+    prev: usize,
+    next: usize,
+}
+
+/// A filter fronted by a small LRU of recent query results.
+///
+/// For workloads that probe the same hot keys over and over, a hit answers from a compact
+/// table that stays in L1/L2 instead of touching the filter's block. Inserts go through the
+/// wrapper and update cached results, so the cache never returns a stale negative.
+#[derive(Clone, Debug)]
+pub struct CachedFilter {
+    filter: BlockedBloomFilter,
+    capacity: usize,
+    index: HashMap<u64, usize>,
+    entries: Vec<Entry>,
+    head: usize,
+    tail: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl CachedFilter {
+    /// Wraps `filter` with a cache of up to `capacity` query results.
+    pub fn new(filter: BlockedBloomFilter, capacity: usize) -> Self {
+        Self {
+            filter,
+            capacity,
+            index: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let Entry { prev, next, .. } = self.entries[i];
+        match prev {
+            NIL => self.head = next,
+            p => self.entries[p].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            n => self.entries[n].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, i: usize) {
+        self.entries[i].prev = NIL;
+        self.entries[i].next = self.head;
+        match self.head {
+            NIL => self.tail = i,
+            h => self.entries[h].prev = i,
+        }
+        self.head = i;
+    }
+
+    /// Checks if the filter might contain the hash, consulting the cache first.
+    pub fn may_match_hash(&mut self, h: u64) -> bool {
+        if let Some(&i) = self.index.get(&h) {
+            self.hits += 1;
+            self.unlink(i);
+            self.push_front(i);
+            return self.entries[i].result;
+        }
+
+        self.misses += 1;
+        let result = self.filter.may_match_hash(h);
+        if self.capacity == 0 {
+            return result;
+        }
+        let entry = Entry {
+            hash: h,
+            result,
+            prev: NIL,
+            next: NIL,
+        };
+        let i = if self.entries.len() < self.capacity {
+            self.entries.push(entry);
+            self.entries.len() - 1
+        } else {
+            let lru = self.tail;
+            self.unlink(lru);
+            self.index.remove(&self.entries[lru].hash);
+            self.entries[lru] = entry;
+            lru
+        };
+        self.index.insert(h, i);
+        self.push_front(i);
+        result
+    }
+
+    /// Hashes the key and checks if it might be present, consulting the cache first.
+    pub fn may_match_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        let h = self.filter.hash_key(key);
+        self.may_match_hash(h)
+    }
+
+    /// Inserts a hash into the filter and marks any cached result for it as a match.
+    ///
+    /// Other cached negatives stay valid: inserting one hash never makes another one match
+    /// less, and a new false positive missed by the cache is still a correct answer.
+    pub fn insert_hash(&mut self, h: u64) {
+        self.filter.insert_hash(h);
+        if let Some(&i) = self.index.get(&h) {
+            self.entries[i].result = true;
+        }
+    }
+
+    /// Hashes the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        let h = self.filter.hash_key(key);
+        self.insert_hash(h);
+    }
+
+    /// Returns the number of queries answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of queries that probed the filter.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the wrapped filter.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Drops the cache and returns the filter.
+    pub fn into_inner(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cached = CachedFilter::new(BlockedBloomFilter::new(1000, 0.01), 2);
+        assert!(!cached.may_match_key("Leopold Bloom"));
+        assert!(!cached.may_match_key("Molly Bloom"));
+        assert!(!cached.may_match_key("Leopold Bloom"));
+        assert_eq!((cached.hits(), cached.misses()), (1, 2));
+
+        // "Molly Bloom" is the least recently used and makes room for "Blazes Boylan".
+        assert!(!cached.may_match_key("Blazes Boylan"));
+        assert!(!cached.may_match_key("Leopold Bloom"));
+        assert_eq!((cached.hits(), cached.misses()), (2, 3));
+        assert!(!cached.may_match_key("Molly Bloom"));
+        assert_eq!((cached.hits(), cached.misses()), (2, 4));
+
+        // Inserting updates the cached negative.
+        cached.insert_key("Molly Bloom");
+        assert!(cached.may_match_key("Molly Bloom"));
+        assert_eq!(cached.hits(), 3);
+    }
+}
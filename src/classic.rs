@@ -0,0 +1,168 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, key_hash, math};
+
+/// A standard partitioned Bloom filter, for systems that do not understand the blocked layout.
+///
+/// The bit array is split into `num_hashes` partitions of `partition_bits` bits. A 64-bit hash
+/// `h` sets, in partition `i`, the bit `(h1 + i * h2) % partition_bits` where `h1` and `h2` are
+/// its lower and upper 32 bits (Kirsch-Mitzenmacher double hashing). Bit `b` of the array is
+/// bit `b % 64` of word `b / 64`. Keys are hashed with xxh64 like `BlockedBloomFilter`, so the
+/// same seed gives the same hashes in both.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassicBloomFilter {
+    words: Vec<u64>,
+    partition_bits: u32,
+    num_hashes: u32,
+    seed: u64,
+}
+
+impl ClassicBloomFilter {
+    /// Creates a new filter with the given entries and false positive rate.
+    pub fn new(entries: usize, fpr: f64) -> Self {
+        Self::new_with_seed(entries, fpr, 0)
+    }
+
+    /// Creates a new filter with a custom seed.
+    pub fn new_with_seed(entries: usize, fpr: f64, seed: u64) -> Self {
+        let num_hashes = math::optimal_k(math::bits_per_key(fpr) as f64);
+        Self::with_geometry(math::bits_for(entries, fpr), num_hashes, seed)
+    }
+
+    /// Creates an empty filter of about `bits` bits split into `num_hashes` partitions.
+    pub fn with_geometry(bits: usize, num_hashes: u32, seed: u64) -> Self {
+        let num_hashes = num_hashes.max(1);
+        let partition_bits = bits
+            .div_ceil(num_hashes as usize)
+            .clamp(1, u32::MAX as usize) as u32;
+        let total_bits = partition_bits as usize * num_hashes as usize;
+        Self {
+            words: vec![0; total_bits.div_ceil(64)],
+            partition_bits,
+            num_hashes,
+            seed,
+        }
+    }
+
+    /// Returns the number of bits in each partition.
+    pub fn partition_bits(&self) -> u32 {
+        self.partition_bits
+    }
+
+    /// Returns the number of partitions, which is the number of bits set per key.
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Returns the seed keys are hashed with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the bit array as 64-bit words.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    fn bit_indices(&self, h: u64) -> impl Iterator<Item = usize> + use<> {
+        let (h1, h2) = (h as u32, (h >> 32) as u32);
+        let partition_bits = self.partition_bits;
+        (0..self.num_hashes).map(move |i| {
+            let offset = h1.wrapping_add(i.wrapping_mul(h2)) % partition_bits;
+            i as usize * partition_bits as usize + offset as usize
+        })
+    }
+
+    /// Inserts a hash into the filter.
+    #[inline]
+    pub fn insert_hash(&mut self, h: u64) {
+        for bit in self.bit_indices(h) {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Checks if the filter might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.bit_indices(h)
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Hashes the key and inserts it.
+    #[inline]
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.insert_hash(key_hash(self.seed, key));
+    }
+
+    /// Hashes the key and checks if it might be present.
+    #[inline]
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(key_hash(self.seed, key))
+    }
+
+    /// Rebuilds a blocked filter of about the same size from the hashes inserted into `self`.
+    ///
+    /// Bits cannot be re-mapped between the layouts, because neither records which keys set
+    /// which bits, so conversions need the inserted hashes. Hashes missing from `hashes` are
+    /// lost.
+    pub fn to_blocked(&self, hashes: &[u64]) -> BlockedBloomFilter {
+        BlockedBloomFilter::from_classic(self, hashes)
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Rebuilds a classic filter with the same bit count from the hashes inserted into `self`.
+    ///
+    /// The classic filter uses the optimal number of partitions for `hashes.len()` keys. See
+    /// `ClassicBloomFilter::to_blocked` for why the hashes are needed.
+    pub fn to_classic(&self, hashes: &[u64]) -> ClassicBloomFilter {
+        let bits = self.num_blocks as usize * CacheLineBlock::BITS;
+        let num_hashes = math::optimal_k(bits as f64 / hashes.len().max(1) as f64);
+        let mut classic = ClassicBloomFilter::with_geometry(bits, num_hashes, self.seed);
+        for &h in hashes {
+            classic.insert_hash(h);
+        }
+        classic
+    }
+
+    /// Rebuilds a blocked filter of about the same size as `classic` from its inserted hashes.
+    pub fn from_classic(classic: &ClassicBloomFilter, hashes: &[u64]) -> Self {
+        let bits = classic.partition_bits as usize * classic.num_hashes as usize;
+        // Round to the nearest block count, so a round trip keeps the original geometry.
+        let num_blocks = ((bits + CacheLineBlock::BITS / 2) / CacheLineBlock::BITS).max(1);
+        let mut blocked =
+            Self::from_blocks(vec![CacheLineBlock::default(); num_blocks], classic.seed);
+        for &h in hashes {
+            blocked.insert_hash(h);
+        }
+        blocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions_keep_inserted_keys() {
+        let mut blocked = BlockedBloomFilter::new_with_seed(10_000, 0.01, 3);
+        let hashes: Vec<u64> = (0..10_000u64).map(|i| blocked.hash_key(&i)).collect();
+        for &h in &hashes {
+            blocked.insert_hash(h);
+        }
+
+        let classic = blocked.to_classic(&hashes);
+        assert_eq!(classic.num_hashes(), 7);
+        for i in 0..10_000u64 {
+            assert!(classic.may_match_key(&i));
+        }
+        let false_positives = (10_000..20_000u64)
+            .filter(|i| classic.may_match_key(i))
+            .count();
+        assert!(false_positives < 150, "{false_positives}");
+
+        let round_trip = classic.to_blocked(&hashes);
+        assert_eq!(round_trip.blocks[..], blocked.blocks[..]);
+    }
+}
@@ -0,0 +1,265 @@
+//! A client for `bloomsday.v1.FilterService` that answers queries through `ApproxMembership`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tonic::body::Body;
+use tonic::client::{Grpc, GrpcService};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::codegen::{Bytes, StdError};
+use tonic::{Request, Status};
+
+use crate::proto::{BulkProbeRequest, BulkProbeResponse, Probe};
+use crate::{ApproxMembership, HashAlgorithm};
+
+/// Hashes sent per `BulkProbe` call by default.
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Negative answers cached by default.
+pub const DEFAULT_NEGATIVE_CACHE_CAPACITY: usize = 65_536;
+
+/// How long a negative answer is trusted by default.
+pub const DEFAULT_NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// One filter on a remote `FilterService`, queried with batched `BulkProbe` calls.
+///
+/// Hashes the server answered "absent" for are remembered for a TTL and answered locally.
+/// Absence only lasts until the next insert of that hash on the server, so the TTL bounds how
+/// long a stale "absent" can be returned. Positive answers are never cached, since the server
+/// may rotate or replace the filter.
+///
+/// `T` is any gRPC transport, e.g. a `tonic::transport::Channel`.
+#[derive(Debug)]
+pub struct RemoteFilter<T> {
+    grpc: Grpc<T>,
+    filter_id: u64,
+    seed: u64,
+    hash: HashAlgorithm,
+    batch_size: usize,
+    negatives: Mutex<NegativeCache>,
+}
+
+#[derive(Debug)]
+struct NegativeCache {
+    ttl: Duration,
+    capacity: usize,
+    expiries: HashMap<u64, Instant>,
+}
+
+impl NegativeCache {
+    fn contains(&mut self, h: u64, now: Instant) -> bool {
+        match self.expiries.get(&h) {
+            Some(&expiry) if expiry > now => true,
+            Some(_) => {
+                self.expiries.remove(&h);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, h: u64, now: Instant) {
+        if self.expiries.len() >= self.capacity {
+            self.expiries.retain(|_, &mut expiry| expiry > now);
+        }
+        // Still full of live entries: skip rather than evict, the next expiry frees room.
+        if self.expiries.len() < self.capacity {
+            self.expiries.insert(h, now + self.ttl);
+        }
+    }
+}
+
+impl<T> RemoteFilter<T> {
+    /// Queries filter `filter_id` over `service`.
+    ///
+    /// `seed` and `hash` must be the remote filter's seed and hash algorithm for
+    /// `may_match_keys` to hash keys the way its inserts did.
+    pub fn new(service: T, filter_id: u64, seed: u64, hash: HashAlgorithm) -> Self {
+        Self {
+            grpc: Grpc::new(service),
+            filter_id,
+            seed,
+            hash,
+            batch_size: DEFAULT_BATCH_SIZE,
+            negatives: Mutex::new(NegativeCache {
+                ttl: DEFAULT_NEGATIVE_CACHE_TTL,
+                capacity: DEFAULT_NEGATIVE_CACHE_CAPACITY,
+                expiries: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Sets the number of hashes sent per call.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sets how many negative answers are cached and for how long. A zero capacity disables
+    /// the cache.
+    pub fn with_negative_cache(self, capacity: usize, ttl: Duration) -> Self {
+        {
+            let mut negatives = self.negatives.lock().unwrap();
+            negatives.capacity = capacity;
+            negatives.ttl = ttl;
+            negatives.expiries.clear();
+        }
+        self
+    }
+
+    /// Returns the remote filter's ID.
+    pub fn filter_id(&self) -> u64 {
+        self.filter_id
+    }
+
+    /// Forgets all cached negative answers, e.g. after inserting through another client.
+    pub fn clear_cache(&self) {
+        self.negatives.lock().unwrap().expiries.clear();
+    }
+}
+
+impl<T> ApproxMembership for RemoteFilter<T>
+where
+    T: GrpcService<Body> + Clone + Send + Sync,
+    T::Future: Send,
+    T::Error: Into<StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<StdError> + Send,
+{
+    type Error = Status;
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
+
+    fn may_match_hashes(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Result<Vec<bool>, Status>> + Send {
+        let now = Instant::now();
+        let mut results = vec![false; hashes.len()];
+        let pending: Vec<usize> = {
+            let mut negatives = self.negatives.lock().unwrap();
+            (0..hashes.len())
+                .filter(|&i| !negatives.contains(hashes[i], now))
+                .collect()
+        };
+        async move {
+            for batch in pending.chunks(self.batch_size) {
+                let request = BulkProbeRequest {
+                    probes: batch
+                        .iter()
+                        .map(|&i| Probe {
+                            filter_id: self.filter_id,
+                            hash: hashes[i],
+                        })
+                        .collect(),
+                };
+                let answers = self.bulk_probe(request).await?;
+                if answers.len() != batch.len() {
+                    return Err(Status::internal(format!(
+                        "expected {} probe results, got {}",
+                        batch.len(),
+                        answers.len()
+                    )));
+                }
+                let mut negatives = self.negatives.lock().unwrap();
+                for (&i, answer) in batch.iter().zip(answers) {
+                    results[i] = answer;
+                    if !answer {
+                        negatives.insert(hashes[i], now);
+                    }
+                }
+            }
+            Ok(results)
+        }
+    }
+}
+
+impl<T> RemoteFilter<T>
+where
+    T: GrpcService<Body> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<StdError> + Send,
+{
+    async fn bulk_probe(&self, request: BulkProbeRequest) -> Result<Vec<bool>, Status> {
+        let mut grpc = self.grpc.clone();
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("service not ready: {}", e.into())))?;
+        let path = PathAndQuery::from_static("/bloomsday.v1.FilterService/BulkProbe");
+        let codec = tonic_prost::ProstCodec::<BulkProbeRequest, BulkProbeResponse>::default();
+        let response = grpc.unary(Request::new(request), path, codec).await?;
+        Ok(response.into_inner().results)
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::server::{FilterServer, FilterServiceServer};
+    use crate::{BlockedBloomFilter, FilterId, FilterRegistry};
+
+    #[tokio::test]
+    async fn test_remote_filter_batches_and_caches_negatives() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 5);
+        bf.insert_key("Leopold Bloom");
+        let mut registry = FilterRegistry::new();
+        registry.insert(FilterId(1), bf.clone());
+        let server = FilterServer::new(registry);
+        let shared = std::sync::Arc::clone(server.registry());
+
+        let remote =
+            RemoteFilter::new(FilterServiceServer::new(server), 1, 5, HashAlgorithm::Xxh64)
+                .with_batch_size(1);
+        let keys = ["Molly Bloom", "Leopold Bloom"];
+        assert_eq!(
+            remote.may_match_keys(&keys).await.unwrap(),
+            bf.may_match_keys(&keys)
+        );
+        assert_eq!(remote.may_match_keys(&keys).await.unwrap(), [false, true]);
+
+        // The cached negative hides the new insert until the cache is cleared.
+        shared
+            .write()
+            .unwrap()
+            .get_mut(FilterId(1))
+            .unwrap()
+            .insert_key("Molly Bloom");
+        assert_eq!(remote.may_match_keys(&keys).await.unwrap(), [false, true]);
+        remote.clear_cache();
+        assert_eq!(remote.may_match_keys(&keys).await.unwrap(), [true, true]);
+
+        let missing = RemoteFilter::new(
+            FilterServiceServer::new(FilterServer::new(FilterRegistry::new())),
+            2,
+            5,
+            HashAlgorithm::Xxh64,
+        );
+        let results = missing.may_match_hashes(&[1, 2]).await.unwrap();
+        assert_eq!(results, [false, false]);
+    }
+
+    #[tokio::test]
+    async fn test_remote_filter_hashes_keys_with_the_filter_algorithm() {
+        let mut bf = BlockedBloomFilter::new_with_hash(1000, 0.01, 5, HashAlgorithm::Xxh3);
+        bf.insert_key("Leopold Bloom");
+        let mut registry = FilterRegistry::new();
+        registry.insert(FilterId(1), bf);
+        let service = FilterServiceServer::new(FilterServer::new(registry));
+
+        let remote = RemoteFilter::new(service, 1, 5, HashAlgorithm::Xxh3);
+        assert_eq!(
+            remote.may_match_keys(&["Leopold Bloom"]).await.unwrap(),
+            [true]
+        );
+    }
+}
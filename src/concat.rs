@@ -0,0 +1,92 @@
+use std::hash::Hash;
+
+use crate::{BlockMapping, BlockedBloomFilter, key_hash};
+
+impl BlockedBloomFilter {
+    /// Routes a hash to one of `parts` range-partitioned builders.
+    ///
+    /// Returns the index of the part owning the hash and the hash to insert into that part.
+    /// The part index comes from the top bits of the block-selecting half, which are then
+    /// shifted out, so the part's own block mapping picks the same block the concatenated
+    /// filter will. Query the concatenated filter with the original hash.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parts` is not a power of two.
+    pub fn partition_hash(h: u64, parts: usize) -> (usize, u64) {
+        assert!(parts.is_power_of_two(), "part count must be a power of two");
+        let bits = parts.trailing_zeros();
+        if bits == 0 {
+            return (0, h);
+        }
+        let hi = (h >> 32) as u32;
+        let part = (hi >> (32 - bits)) as usize;
+        let routed = ((hi << bits) as u64) << 32 | (h & 0xffff_ffff);
+        (part, routed)
+    }
+
+    /// Hashes the key with `seed` and routes it like `partition_hash`.
+    ///
+    /// Keys are hashed with xxHash64; for parts built with another `HashAlgorithm`, hash keys
+    /// with a part's `may_match_key` algorithm and call `partition_hash` instead.
+    pub fn partition_key<T: Hash + ?Sized>(seed: u64, key: &T, parts: usize) -> (usize, u64) {
+        Self::partition_hash(key_hash(seed, key), parts)
+    }
+
+    /// Concatenates filters built over consecutive block ranges into one filter.
+    ///
+    /// Each part must have been filled with hashes routed to it by `partition_hash` with
+    /// `parts.len()` parts. The result has the parts' blocks in order and answers queries for
+    /// the original, unrouted hashes and keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the part count is not a power of two, if the parts differ in block count,
+    /// seed or hash algorithm, or if they use `BlockMapping::Mixed`, which `partition_hash`
+    /// cannot route for.
+    pub fn concat(parts: &[Self]) -> Self {
+        assert!(
+            parts.len().is_power_of_two(),
+            "part count must be a power of two"
+        );
+        let first = &parts[0];
+        assert!(
+            parts.iter().all(|p| p.num_blocks == first.num_blocks
+                && p.seed == first.seed
+                && p.hash == first.hash),
+            "parts must have the same block count, seed and hash algorithm"
+        );
+        assert!(
+            parts.iter().all(|p| p.mapping == BlockMapping::Direct),
+            "parts must use direct block mapping"
+        );
+
+        let mut blocks = Vec::with_capacity(first.num_blocks as usize * parts.len());
+        for part in parts {
+            blocks.extend_from_slice(&part.blocks);
+        }
+        Self::from_blocks(blocks, first.seed).with_hash(first.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_matches_single_build() {
+        let mut parts = vec![BlockedBloomFilter::new_with_seed(2500, 0.01, 4); 4];
+        for i in 0..10_000u64 {
+            let (part, h) = BlockedBloomFilter::partition_key(4, &i, parts.len());
+            parts[part].insert_hash(h);
+        }
+        let merged = BlockedBloomFilter::concat(&parts);
+
+        let mut single = BlockedBloomFilter::with_params(merged.params()).unwrap();
+        for i in 0..10_000u64 {
+            single.insert_key(&i);
+            assert!(merged.may_match_key(&i));
+        }
+        assert_eq!(merged.blocks[..], single.blocks[..]);
+    }
+}
@@ -0,0 +1,279 @@
+use std::hash::Hash;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+use crate::{BlockMapping, BlockedBloomFilter, CacheLineBlock, key_hash};
+
+/// Hashes a writer buffers per foreign shard before handing them over.
+const FLUSH_BATCH: usize = 256;
+
+/// Builds one filter from many cores, each owning a contiguous range of blocks.
+///
+/// `ParallelBuilder` gives every thread a full private copy and ORs them at the end, and
+/// `SharedBloomFilter` has every thread OR into shared lines with atomics. At very high insert
+/// rates on large NUMA machines, the first costs a copy per core and the second bounces cache
+/// lines between sockets. Here the blocks are split into one range per shard and each range is
+/// written only by whoever holds it, so no line is ever written from two cores and the result
+/// needs no OR: `finish` just concatenates the ranges.
+///
+/// Run one `ShardWriter` per core, each on the thread pinned to that core so its range is
+/// allocated and kept in local memory. `insert_local` sets bits directly when the hash falls in
+/// the writer's own range and otherwise buffers it for the owning shard. Full buffers are
+/// handed over in batches: if the owner has no active writer, the batch is applied directly
+/// (stealing the work), otherwise it is queued in the owner's inbox, which the owner drains as
+/// it flushes. Batches still queued when the writers finish are applied by `finish`.
+#[derive(Debug)]
+pub struct CoreShardedBuilder {
+    num_blocks: u32,
+    seed: u64,
+    shards: Vec<Shard>,
+}
+
+#[derive(Debug)]
+struct Shard {
+    first_block: usize,
+    has_writer: AtomicBool,
+    blocks: Mutex<Vec<CacheLineBlock>>,
+    inbox: Mutex<Vec<Vec<u64>>>,
+}
+
+impl CoreShardedBuilder {
+    /// Creates a builder for a filter sized like `BlockedBloomFilter::new_with_seed`, split
+    /// into `shards` block ranges, usually one per core. There are never more shards than
+    /// blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn new(entries: usize, fpr: f64, seed: u64, shards: usize) -> Self {
+        assert!(shards > 0, "a sharded builder needs at least one shard");
+        let num_blocks = BlockedBloomFilter::new_with_seed(entries, fpr, seed).num_blocks;
+        let shards = shards.min(num_blocks as usize);
+        let shards = (0..shards)
+            .map(|i| {
+                let range = Self::range_of(num_blocks as usize, shards, i);
+                Shard {
+                    first_block: range.start,
+                    has_writer: AtomicBool::new(false),
+                    blocks: Mutex::new(vec![CacheLineBlock::default(); range.len()]),
+                    inbox: Mutex::new(Vec::new()),
+                }
+            })
+            .collect();
+        Self {
+            num_blocks,
+            seed,
+            shards,
+        }
+    }
+
+    fn range_of(num_blocks: usize, shards: usize, shard: usize) -> Range<usize> {
+        num_blocks * shard / shards..num_blocks * (shard + 1) / shards
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the blocks owned by `shard`.
+    pub fn shard_range(&self, shard: usize) -> Range<usize> {
+        Self::range_of(self.num_blocks as usize, self.shards.len(), shard)
+    }
+
+    fn shard_of(&self, block: usize) -> usize {
+        // Ranges differ in length by at most one block, so the estimate is off by at most one.
+        let guess = block * self.shards.len() / self.num_blocks as usize;
+        if block < self.shards[guess].first_block {
+            guess - 1
+        } else if self
+            .shards
+            .get(guess + 1)
+            .is_some_and(|next| block >= next.first_block)
+        {
+            guess + 1
+        } else {
+            guess
+        }
+    }
+
+    /// Takes ownership of `shard`'s blocks for writing from the current core.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard` is out of range or already has a live writer.
+    pub fn writer(&self, shard: usize) -> ShardWriter<'_> {
+        assert!(
+            !self.shards[shard].has_writer.swap(true, Ordering::Acquire),
+            "shard {shard} already has a writer"
+        );
+        // Waits out another writer applying a batch it stole before this writer existed.
+        let blocks = lock(&self.shards[shard].blocks);
+        ShardWriter {
+            builder: self,
+            shard,
+            blocks,
+            outboxes: vec![Vec::new(); self.shards.len()],
+        }
+    }
+
+    /// Applies a batch of hashes to a shard's blocks.
+    fn apply(&self, shard: usize, blocks: &mut [CacheLineBlock], hashes: &[u64]) {
+        let first_block = self.shards[shard].first_block;
+        for &h in hashes {
+            let block = BlockMapping::Direct.block_index(h, self.num_blocks);
+            set_bits(&mut blocks[block - first_block], h);
+        }
+    }
+
+    /// Applies any queued batches and returns the assembled filter.
+    pub fn finish(self) -> BlockedBloomFilter {
+        let mut blocks = Vec::with_capacity(self.num_blocks as usize);
+        for (i, shard) in self.shards.iter().enumerate() {
+            let mut shard_blocks = lock(&shard.blocks);
+            for batch in lock(&shard.inbox).drain(..) {
+                self.apply(i, &mut shard_blocks, &batch);
+            }
+            blocks.append(&mut shard_blocks);
+        }
+        BlockedBloomFilter::from_blocks(blocks, self.seed)
+    }
+}
+
+/// A core's exclusive handle on one shard of a `CoreShardedBuilder`.
+///
+/// Dropping it flushes its buffers and drains its inbox.
+#[derive(Debug)]
+pub struct ShardWriter<'a> {
+    builder: &'a CoreShardedBuilder,
+    shard: usize,
+    blocks: MutexGuard<'a, Vec<CacheLineBlock>>,
+    outboxes: Vec<Vec<u64>>,
+}
+
+impl ShardWriter<'_> {
+    /// Inserts a hash, directly if it maps to this writer's shard and buffered otherwise.
+    pub fn insert_local(&mut self, h: u64) {
+        let block = BlockMapping::Direct.block_index(h, self.builder.num_blocks);
+        let first_block = self.builder.shards[self.shard].first_block;
+        if let Some(own) = block
+            .checked_sub(first_block)
+            .and_then(|i| self.blocks.get_mut(i))
+        {
+            set_bits(own, h);
+            return;
+        }
+        let owner = self.builder.shard_of(block);
+        self.outboxes[owner].push(h);
+        if self.outboxes[owner].len() >= FLUSH_BATCH {
+            self.hand_over(owner);
+            self.drain_inbox();
+        }
+    }
+
+    /// Hashes the key and inserts it like `insert_local`.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.insert_local(key_hash(self.builder.seed, key));
+    }
+
+    /// Hands over every buffered hash and applies the batches other writers queued here.
+    pub fn flush(&mut self) {
+        for owner in 0..self.outboxes.len() {
+            if !self.outboxes[owner].is_empty() {
+                self.hand_over(owner);
+            }
+        }
+        self.drain_inbox();
+    }
+
+    /// Returns the shard this writer owns.
+    pub fn shard(&self) -> usize {
+        self.shard
+    }
+
+    /// Applies the buffer for `owner` to its blocks if it has no writer, or queues it.
+    fn hand_over(&mut self, owner: usize) {
+        let batch = std::mem::take(&mut self.outboxes[owner]);
+        let shard = &self.builder.shards[owner];
+        if shard.has_writer.load(Ordering::Relaxed) {
+            lock(&shard.inbox).push(batch);
+            return;
+        }
+        match shard.blocks.try_lock() {
+            Ok(mut blocks) => self.builder.apply(owner, &mut blocks, &batch),
+            Err(TryLockError::Poisoned(poisoned)) => {
+                self.builder
+                    .apply(owner, &mut poisoned.into_inner(), &batch)
+            }
+            Err(TryLockError::WouldBlock) => lock(&shard.inbox).push(batch),
+        }
+    }
+
+    fn drain_inbox(&mut self) {
+        let batches = std::mem::take(&mut *lock(&self.builder.shards[self.shard].inbox));
+        for batch in batches {
+            self.builder.apply(self.shard, &mut self.blocks, &batch);
+        }
+    }
+}
+
+impl Drop for ShardWriter<'_> {
+    fn drop(&mut self) {
+        self.flush();
+        self.builder.shards[self.shard]
+            .has_writer
+            .store(false, Ordering::Release);
+    }
+}
+
+fn set_bits(block: &mut CacheLineBlock, h: u64) {
+    for (w, &salt) in block.words.iter_mut().zip(BlockedBloomFilter::SALT.iter()) {
+        *w |= 1 << ((h as u32).wrapping_mul(salt) >> 27);
+    }
+}
+
+/// Locks a mutex, ignoring poisoning: a panicking writer leaves bits set, never wrong ones.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sharded_build_matches_sequential() {
+        let builder = CoreShardedBuilder::new(100_000, 0.01, 7, 4);
+        assert_eq!(builder.shard_range(3).end, builder.num_blocks as usize);
+        std::thread::scope(|s| {
+            for shard in 0..builder.shards() {
+                let builder = &builder;
+                s.spawn(move || {
+                    let mut writer = builder.writer(shard);
+                    for i in (shard as u64..100_000).step_by(4) {
+                        writer.insert_key(&i);
+                    }
+                });
+            }
+        });
+        let merged = builder.finish();
+
+        let mut sequential = BlockedBloomFilter::new_with_seed(100_000, 0.01, 7);
+        (0..100_000u64).for_each(|i| sequential.insert_key(&i));
+        assert_eq!(merged.blocks[..], sequential.blocks[..]);
+    }
+
+    #[test]
+    fn test_shard_of_matches_ranges() {
+        let builder = CoreShardedBuilder::new(1000, 0.01, 0, 7);
+        for shard in 0..builder.shards() {
+            for block in builder.shard_range(shard) {
+                assert_eq!(builder.shard_of(block), shard);
+            }
+        }
+        assert_eq!(CoreShardedBuilder::new(1, 0.5, 0, 64).shards(), 1);
+    }
+}
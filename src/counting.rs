@@ -0,0 +1,248 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, HashAlgorithm, math, probe_positions};
+
+/// Bits per counter of a `CountingBloomFilter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CounterWidth {
+    /// Counters saturate at 3. Twice the memory of a plain filter.
+    Two,
+    /// Counters saturate at 15. The classic choice, four times the memory of a plain filter.
+    #[default]
+    Four,
+    /// Counters saturate at 255. Eight times the memory of a plain filter.
+    Eight,
+}
+
+impl CounterWidth {
+    /// Returns the width in bits.
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::Two => 2,
+            Self::Four => 4,
+            Self::Eight => 8,
+        }
+    }
+
+    /// Returns the largest count a counter holds before it saturates.
+    pub fn max_count(self) -> u8 {
+        ((1u32 << self.bits()) - 1) as u8
+    }
+}
+
+/// A blocked filter with a small counter per position, so keys can be removed.
+///
+/// Counters saturate instead of wrapping: once a counter reaches `CounterWidth::max_count` it
+/// is stuck there, and removals leave it alone, since its true count is no longer known. A
+/// stuck counter can only cause false positives, never false negatives, but the keys sharing it
+/// can no longer be fully removed. `overflows` counts the increments lost this way, which tells
+/// whether a narrower width is holding up for a workload.
+#[derive(Clone, Debug)]
+pub struct CountingBloomFilter {
+    counters: Vec<u64>,
+    num_blocks: u32,
+    seed: u64,
+    hash: HashAlgorithm,
+    width: CounterWidth,
+    overflows: u64,
+}
+
+impl CountingBloomFilter {
+    /// Creates a filter sized for `entries` keys at `fpr` with `width`-bit counters.
+    pub fn new(entries: usize, fpr: f64, width: CounterWidth) -> Self {
+        Self::new_with_seed(entries, fpr, width, 0)
+    }
+
+    /// Creates a filter with a custom seed.
+    pub fn new_with_seed(entries: usize, fpr: f64, width: CounterWidth, seed: u64) -> Self {
+        let num_blocks = math::num_blocks_for(entries, fpr);
+        let words = num_blocks as usize * CacheLineBlock::BITS * width.bits() as usize / 64;
+        Self {
+            counters: vec![0; words],
+            num_blocks,
+            seed,
+            hash: HashAlgorithm::Xxh64,
+            width,
+            overflows: 0,
+        }
+    }
+
+    /// Creates a filter whose `*_key` methods hash keys with `hash`.
+    pub fn new_with_hash(
+        entries: usize,
+        fpr: f64,
+        width: CounterWidth,
+        seed: u64,
+        hash: HashAlgorithm,
+    ) -> Self {
+        Self {
+            hash,
+            ..Self::new_with_seed(entries, fpr, width, seed)
+        }
+    }
+
+    /// Returns the seed keys are hashed with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the algorithm the `*_key` methods hash keys with.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
+
+    /// Returns the counter width.
+    pub fn width(&self) -> CounterWidth {
+        self.width
+    }
+
+    /// Returns the number of increments dropped by saturated counters.
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    /// Returns the number of counters stuck at their maximum.
+    pub fn saturated_counters(&self) -> usize {
+        let max = self.width.max_count();
+        (0..self.counters.len() * 64 / self.width.bits() as usize)
+            .filter(|&i| self.get(i) == max)
+            .count()
+    }
+
+    #[inline]
+    fn locate(&self, i: usize) -> (usize, u32) {
+        let bits = self.width.bits() as usize;
+        (i * bits / 64, (i * bits % 64) as u32)
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> u8 {
+        let (word, shift) = self.locate(i);
+        ((self.counters[word] >> shift) & self.width.max_count() as u64) as u8
+    }
+
+    #[inline]
+    fn set(&mut self, i: usize, count: u8) {
+        let (word, shift) = self.locate(i);
+        let mask = (self.width.max_count() as u64) << shift;
+        self.counters[word] = (self.counters[word] & !mask) | ((count as u64) << shift);
+    }
+
+    /// Inserts a hash, incrementing its counters.
+    pub fn insert_hash(&mut self, h: u64) {
+        let max = self.width.max_count();
+        for i in probe_positions(self.num_blocks, h) {
+            match self.get(i) {
+                count if count == max => self.overflows += 1,
+                count => self.set(i, count + 1),
+            }
+        }
+    }
+
+    /// Removes a hash, decrementing its counters, and returns whether it might have been
+    /// present.
+    ///
+    /// A hash that does not match is left alone, so removing a key that was never inserted
+    /// does not disturb the others. Saturated counters are not decremented.
+    pub fn remove_hash(&mut self, h: u64) -> bool {
+        if !self.may_match_hash(h) {
+            return false;
+        }
+        let max = self.width.max_count();
+        for i in probe_positions(self.num_blocks, h) {
+            let count = self.get(i);
+            if count != max {
+                self.set(i, count - 1);
+            }
+        }
+        true
+    }
+
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        probe_positions(self.num_blocks, h)
+            .into_iter()
+            .all(|i| self.get(i) != 0)
+    }
+
+    /// Hashes the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.insert_hash(self.hash.hash_key(self.seed, key));
+    }
+
+    /// Hashes the key and removes it.
+    pub fn remove_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        self.remove_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Collapses the counters into a plain filter with a bit set wherever a counter is nonzero.
+    pub fn to_filter(&self) -> BlockedBloomFilter {
+        let blocks = (0..self.num_blocks as usize)
+            .map(|block| CacheLineBlock {
+                words: std::array::from_fn(|word| {
+                    let base = block * CacheLineBlock::BITS + word * 32;
+                    (0..32).fold(0, |w, bit| w | ((self.get(base + bit) != 0) as u32) << bit)
+                }),
+            })
+            .collect();
+        BlockedBloomFilter::from_blocks(blocks, self.seed).with_hash(self.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_restores_filter() {
+        for width in [CounterWidth::Two, CounterWidth::Four, CounterWidth::Eight] {
+            let mut cf = CountingBloomFilter::new(1000, 0.01, width);
+            let mut plain = BlockedBloomFilter::new(1000, 0.01);
+            for i in 0..500u64 {
+                cf.insert_key(&i);
+                if i % 2 == 0 {
+                    plain.insert_key(&i);
+                }
+            }
+            for i in (1..500u64).step_by(2) {
+                assert!(cf.remove_key(&i));
+            }
+            assert!((0..500u64).step_by(2).all(|i| cf.may_match_key(&i)));
+            if cf.overflows() == 0 {
+                assert_eq!(cf.to_filter().blocks[..], plain.blocks[..]);
+            }
+        }
+
+        let mut xxh3 = CountingBloomFilter::new_with_hash(
+            1000,
+            0.01,
+            CounterWidth::Four,
+            7,
+            HashAlgorithm::Xxh3,
+        );
+        xxh3.insert_key("Molly Bloom");
+        assert!(xxh3.to_filter().may_match_key("Molly Bloom"));
+        assert!(xxh3.remove_key("Molly Bloom"));
+        assert!(!xxh3.may_match_key("Molly Bloom"));
+    }
+
+    #[test]
+    fn test_counters_saturate() {
+        let mut cf = CountingBloomFilter::new(1000, 0.01, CounterWidth::Two);
+        for _ in 0..5 {
+            cf.insert_key("Stephen Dedalus");
+        }
+        assert_eq!(cf.overflows(), 16);
+        assert_eq!(cf.saturated_counters(), 8);
+        for _ in 0..5 {
+            assert!(cf.remove_key("Stephen Dedalus"));
+        }
+        assert!(cf.may_match_key("Stephen Dedalus"));
+        assert!(!cf.remove_key("Buck Mulligan"));
+    }
+}
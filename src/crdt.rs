@@ -0,0 +1,230 @@
+use std::ops::Range;
+
+use crate::binary::blocks_from_le_bytes;
+use crate::hash_compat::xxh64_bytes;
+use crate::{BlockedBloomFilter, CacheLineBlock, DecodeError, Error, FilterParams};
+
+impl BlockedBloomFilter {
+    /// Merges another replica's state into this one, so the filter matches every hash either
+    /// filter matched.
+    ///
+    /// This makes the filter a state-based CRDT: state is the bit array ordered by inclusion,
+    /// inserts only set bits, and `join` (a bitwise OR) is the least upper bound. It is
+    /// idempotent, commutative and associative, so replicas that have seen the same inserts and
+    /// exchanged state in any order, any number of times, hold identical bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filters differ in block count, seed, mapping or hash algorithm.
+    pub fn join(&mut self, other: &Self) {
+        if let Err(e) = self.check_compatible(other) {
+            panic!("{e}");
+        }
+        if other.is_subset_of(self) {
+            // Nothing to learn; skip the write (and the copy of shared blocks under `cow`).
+            return;
+        }
+        for (dst, src) in self.blocks_mut().iter_mut().zip(other.blocks.iter()) {
+            for (d, s) in dst.words.iter_mut().zip(src.words) {
+                *d |= s;
+            }
+        }
+    }
+
+    /// Returns true if every bit set here is also set in `other`, i.e. joining this filter into
+    /// `other` would not change it.
+    ///
+    /// Filters with different block counts, seeds, mappings or hash algorithms are never
+    /// subsets of each other.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        self.check_compatible(other).is_ok()
+            && self
+                .blocks
+                .iter()
+                .zip(other.blocks.iter())
+                .all(|(a, b)| a.words.iter().zip(b.words).all(|(&a, b)| a & !b == 0))
+    }
+
+    /// Returns a digest of the filter's state for anti-entropy comparison.
+    ///
+    /// Replicas with equal digests almost certainly hold identical state and need not exchange
+    /// blocks. The digest is XXH64, seeded with the filter's seed, of the little-endian payload,
+    /// so it is stable across platforms and builds.
+    pub fn digest(&self) -> u64 {
+        let mut payload = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut payload);
+        xxh64_bytes(self.seed, &payload)
+    }
+
+    /// Returns a digest per chunk of `chunk` consecutive blocks (the last may be shorter).
+    ///
+    /// Each digest is XXH64, seeded with the filter's seed, of the chunk's little-endian
+    /// payload. Replicas exchange these during anti-entropy and pass the other side's digests
+    /// to `diff_chunks` to find what to transfer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is zero.
+    pub fn block_digests(&self, chunk: usize) -> Vec<u64> {
+        assert!(chunk > 0, "chunks must cover at least one block");
+        let mut payload = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut payload);
+        payload
+            .chunks(chunk * size_of::<CacheLineBlock>())
+            .map(|bytes| xxh64_bytes(self.seed, bytes))
+            .collect()
+    }
+
+    /// Returns the block ranges whose digests differ from `other_digests`, computed by a
+    /// replica with `block_digests(chunk)`.
+    ///
+    /// Adjacent ranges are merged. If the digest count does not match this filter's geometry,
+    /// every block is reported. Send the ranges with `blocks_payload` and apply them on the
+    /// other side with `join_blocks`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is zero.
+    pub fn diff_chunks(&self, chunk: usize, other_digests: &[u64]) -> Vec<Range<usize>> {
+        let num_blocks = self.num_blocks as usize;
+        let digests = self.block_digests(chunk);
+        let mut diff: Vec<Range<usize>> = Vec::new();
+        if digests.len() != other_digests.len() {
+            diff.push(0..num_blocks);
+            return diff;
+        }
+        for (i, (a, b)) in digests.iter().zip(other_digests).enumerate() {
+            if a == b {
+                continue;
+            }
+            let range = i * chunk..((i + 1) * chunk).min(num_blocks);
+            match diff.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => diff.push(range),
+            }
+        }
+        diff
+    }
+
+    /// Returns the little-endian payload of a range of blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn blocks_payload(&self, blocks: Range<usize>) -> Vec<u8> {
+        let mut out = Vec::with_capacity(blocks.len() * size_of::<CacheLineBlock>());
+        for block in &self.blocks[blocks] {
+            for w in block.words {
+                out.extend_from_slice(&w.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Joins blocks received from a replica, starting at block `start`, into this filter.
+    ///
+    /// `sender` is the replica's `params()`; a replica with a different geometry, seed,
+    /// mapping or hash algorithm is refused with a `MergeError`. The payload must hold whole
+    /// blocks, as produced by `blocks_payload`, that fit in the filter, or a `DecodeError` is
+    /// returned.
+    pub fn join_blocks(
+        &mut self,
+        sender: &FilterParams,
+        start: usize,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        self.check_params(sender)?;
+        let block_bytes = size_of::<CacheLineBlock>();
+        let room = (self.num_blocks as usize).saturating_sub(start);
+        let expected = (payload.len() / block_bytes).min(room) * block_bytes;
+        if payload.len() != expected {
+            return Err(DecodeError::LengthMismatch {
+                expected,
+                actual: payload.len(),
+            }
+            .into());
+        }
+        let received = blocks_from_le_bytes(payload);
+        for (dst, src) in self.blocks_mut()[start..].iter_mut().zip(received) {
+            for (d, s) in dst.words.iter_mut().zip(src.words) {
+                *d |= s;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HashAlgorithm, MergeError};
+
+    #[test]
+    fn test_replicas_converge_in_any_order() {
+        let mut replicas = vec![BlockedBloomFilter::new_with_seed(1000, 0.01, 9); 3];
+        for i in 0..900u64 {
+            replicas[i as usize % 3].insert_key(&i);
+        }
+        let [a, b, c] = [&replicas[0], &replicas[1], &replicas[2]];
+
+        let mut left = a.clone();
+        left.join(b);
+        left.join(c);
+        let mut right = c.clone();
+        right.join(a);
+        right.join(b);
+        right.join(a);
+        right.join(&right.clone());
+
+        assert_eq!(left.digest(), right.digest());
+        assert!(left.is_subset_of(&right) && right.is_subset_of(&left));
+        assert!(a.is_subset_of(&left) && !left.is_subset_of(a));
+        let xxh3 = BlockedBloomFilter::new_with_hash(1000, 0.01, 9, HashAlgorithm::Xxh3);
+        assert!(!xxh3.is_subset_of(&left));
+        assert_ne!(a.digest(), left.digest());
+        for i in 0..900u64 {
+            assert!(left.may_match_key(&i));
+        }
+    }
+
+    #[test]
+    fn test_anti_entropy_transfers_only_changed_chunks() {
+        let mut a = BlockedBloomFilter::new_with_seed(10_000, 0.01, 9);
+        for i in 0..5000u64 {
+            a.insert_key(&i);
+        }
+        let mut b = a.clone();
+        b.insert_hash(0x10_0000_0000);
+        b.insert_hash(u64::MAX);
+        assert_eq!(a.diff_chunks(16, &a.block_digests(16)), []);
+
+        let diff = b.diff_chunks(16, &a.block_digests(16));
+        assert_eq!(diff.len(), 2);
+        for range in diff {
+            assert!(range.len() <= 16);
+            a.join_blocks(&b.params(), range.start, &b.blocks_payload(range))
+                .unwrap();
+        }
+        assert_eq!(a.digest(), b.digest());
+
+        let too_long = b.blocks_payload(0..2);
+        let last = a.num_blocks as usize - 1;
+        let e = a.join_blocks(&b.params(), last, &too_long).unwrap_err();
+        assert!(matches!(
+            e.downcast_ref(),
+            Some(DecodeError::LengthMismatch { .. })
+        ));
+        let other_seed = FilterParams {
+            seed: 1,
+            ..b.params()
+        };
+        let e = a
+            .join_blocks(&other_seed, 0, &b.blocks_payload(0..1))
+            .unwrap_err();
+        assert_eq!(
+            e.downcast_ref(),
+            Some(&MergeError::Seed { left: 9, right: 1 })
+        );
+        assert_eq!(a.diff_chunks(16, &[])[0], 0..last + 1);
+    }
+}
@@ -0,0 +1,471 @@
+use std::hash::Hash;
+use std::mem;
+
+use crate::HashAlgorithm;
+
+/// Fingerprints per bucket.
+const BUCKET_SIZE: usize = 4;
+
+/// Evictions tried before an insert gives up and parks the homeless fingerprint.
+const MAX_KICKS: usize = 500;
+
+/// Marks an empty slot; fingerprints are never zero.
+const EMPTY: u16 = 0;
+
+/// Bits per semi-sorted bucket: a 12-bit code for the sorted high nibbles of its four
+/// fingerprints, then their four 12-bit low parts.
+const SEMI_SORTED_BITS: usize = 60;
+
+const SEMI_SORTED_MASK: u64 = (1 << SEMI_SORTED_BITS) - 1;
+
+/// Every non-decreasing sequence of four nibbles, packed first nibble highest, in increasing
+/// order. A semi-sorted bucket stores the index of its high nibbles here.
+const NIBBLE_QUADS: [u16; 3876] = nibble_quads();
+
+const fn nibble_quads() -> [u16; 3876] {
+    let mut quads = [0; 3876];
+    let mut i = 0;
+    let mut a = 0;
+    while a < 16 {
+        let mut b = a;
+        while b < 16 {
+            let mut c = b;
+            while c < 16 {
+                let mut d = c;
+                while d < 16 {
+                    quads[i] = a << 12 | b << 8 | c << 4 | d;
+                    i += 1;
+                    d += 1;
+                }
+                c += 1;
+            }
+            b += 1;
+        }
+        a += 1;
+    }
+    quads
+}
+
+/// How a `CuckooFilter` lays out its buckets in memory.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BucketLayout {
+    /// Four 16-bit fingerprints per bucket, 64 bits.
+    #[default]
+    Plain,
+    /// Semi-sorted buckets (Fan et al., 2014), 60 bits each.
+    ///
+    /// Slot order within a bucket does not matter, so the fingerprints are kept sorted and
+    /// their high nibbles form one of only 3876 sequences, which a 12-bit code replaces. That
+    /// saves one bit per slot, at the cost of decoding the bucket on every lookup and
+    /// re-encoding it on every change. The rate and the keys held are the same as `Plain`.
+    SemiSorted,
+}
+
+/// Bucket storage in one of the `BucketLayout`s.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Buckets {
+    Plain(Vec<[u16; BUCKET_SIZE]>),
+    /// `len` buckets of `SEMI_SORTED_BITS` each, packed back to back from the low bits.
+    SemiSorted {
+        words: Vec<u64>,
+        len: usize,
+    },
+}
+
+impl Buckets {
+    fn new(len: usize, layout: BucketLayout) -> Self {
+        match layout {
+            BucketLayout::Plain => Self::Plain(vec![[EMPTY; BUCKET_SIZE]; len]),
+            BucketLayout::SemiSorted => Self::SemiSorted {
+                words: vec![0; (len * SEMI_SORTED_BITS).div_ceil(64)],
+                len,
+            },
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Plain(buckets) => buckets.len(),
+            Self::SemiSorted { len, .. } => *len,
+        }
+    }
+
+    fn layout(&self) -> BucketLayout {
+        match self {
+            Self::Plain(_) => BucketLayout::Plain,
+            Self::SemiSorted { .. } => BucketLayout::SemiSorted,
+        }
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        match self {
+            Self::Plain(buckets) => buckets.len() * size_of::<[u16; BUCKET_SIZE]>(),
+            Self::SemiSorted { words, .. } => words.len() * size_of::<u64>(),
+        }
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> [u16; BUCKET_SIZE] {
+        match self {
+            Self::Plain(buckets) => buckets[index],
+            Self::SemiSorted { words, .. } => {
+                let (word, shift) = (index * SEMI_SORTED_BITS / 64, index * SEMI_SORTED_BITS % 64);
+                let mut bits = words[word] >> shift;
+                if shift + SEMI_SORTED_BITS > 64 {
+                    bits |= words[word + 1] << (64 - shift);
+                }
+                decode_semi_sorted(bits & SEMI_SORTED_MASK)
+            }
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, index: usize, bucket: [u16; BUCKET_SIZE]) {
+        match self {
+            Self::Plain(buckets) => buckets[index] = bucket,
+            Self::SemiSorted { words, .. } => {
+                let bits = encode_semi_sorted(bucket);
+                let (word, shift) = (index * SEMI_SORTED_BITS / 64, index * SEMI_SORTED_BITS % 64);
+                words[word] = words[word] & !(SEMI_SORTED_MASK << shift) | bits << shift;
+                if shift + SEMI_SORTED_BITS > 64 {
+                    let high = 64 - shift;
+                    words[word + 1] = words[word + 1] & !(SEMI_SORTED_MASK >> high) | bits >> high;
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Self::Plain(buckets) => buckets.fill([EMPTY; BUCKET_SIZE]),
+            // All-empty buckets encode to zero.
+            Self::SemiSorted { words, .. } => words.fill(0),
+        }
+    }
+}
+
+fn encode_semi_sorted(mut bucket: [u16; BUCKET_SIZE]) -> u64 {
+    bucket.sort_unstable();
+    let quad = bucket.iter().fold(0, |quad, &fp| quad << 4 | fp >> 12);
+    let code = NIBBLE_QUADS
+        .binary_search(&quad)
+        .expect("sorted nibbles are in the table");
+    bucket
+        .iter()
+        .enumerate()
+        .fold(code as u64, |bits, (i, &fp)| {
+            bits | u64::from(fp & 0xfff) << (12 + 12 * i)
+        })
+}
+
+fn decode_semi_sorted(bits: u64) -> [u16; BUCKET_SIZE] {
+    let quad = NIBBLE_QUADS[(bits & 0xfff) as usize];
+    std::array::from_fn(|i| {
+        let high = (quad >> (12 - 4 * i)) & 0xf;
+        high << 12 | ((bits >> (12 + 12 * i)) & 0xfff) as u16
+    })
+}
+
+/// A cuckoo filter with 16-bit fingerprints in buckets of four, supporting removal.
+///
+/// A hash `h` has fingerprint `h as u16` (1 if that is zero) and primary bucket
+/// `(h >> 32) % num_buckets`; its alternate bucket is the primary XORed with a hash of the
+/// fingerprint, so either bucket can be found from the other when a fingerprint is evicted.
+/// Keys are hashed like `BlockedBloomFilter`, with xxh64 unless created with
+/// `new_with_hash`, so the same seed and algorithm give the same hashes in both.
+///
+/// The false positive rate is at most `8 / 65536`, about 0.012%, at about 17 bits per key
+/// when the filter is 95% full, less than a blocked filter needs for the same rate, or about
+/// 16 with `BucketLayout::SemiSorted`. Unlike a
+/// Bloom filter it can fill up: when an insert cannot find room after `MAX_KICKS` evictions
+/// the fingerprint left over is kept aside, and further inserts fail until a removal makes
+/// room for it. Only remove keys that were inserted; removing any other key that happens to
+/// match takes out the fingerprint of a key that was.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CuckooFilter {
+    buckets: Buckets,
+    victim: Option<(usize, u16)>,
+    len: usize,
+    seed: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    hash: HashAlgorithm,
+}
+
+impl CuckooFilter {
+    /// The highest false positive rate the filter reaches when full: two buckets of four
+    /// 16-bit fingerprints, `8 / 65536`.
+    pub const MAX_FPR: f64 = (2 * BUCKET_SIZE) as f64 / 65536.0;
+
+    /// Creates a filter with room for at least `capacity` keys.
+    pub fn new(capacity: usize) -> Self {
+        Self::new_with_seed(capacity, 0)
+    }
+
+    /// Creates a filter with a custom seed.
+    ///
+    /// The bucket count is a power of two, sized so `capacity` keys fill at most 95% of the
+    /// slots, the load buckets of four reliably reach.
+    pub fn new_with_seed(capacity: usize, seed: u64) -> Self {
+        Self::new_with_layout(capacity, seed, BucketLayout::Plain)
+    }
+
+    /// Creates a filter whose buckets are laid out with `layout`.
+    pub fn new_with_layout(capacity: usize, seed: u64, layout: BucketLayout) -> Self {
+        Self {
+            buckets: Buckets::new(Self::num_buckets_for(capacity), layout),
+            victim: None,
+            len: 0,
+            seed,
+            hash: HashAlgorithm::Xxh64,
+        }
+    }
+
+    /// Creates a filter whose `*_key` methods hash keys with `hash`.
+    pub fn new_with_hash(capacity: usize, seed: u64, hash: HashAlgorithm) -> Self {
+        Self {
+            hash,
+            ..Self::new_with_seed(capacity, seed)
+        }
+    }
+
+    fn num_buckets_for(capacity: usize) -> usize {
+        ((capacity as f64 / (BUCKET_SIZE as f64 * 0.95)).ceil() as usize)
+            .max(1)
+            .next_power_of_two()
+    }
+
+    /// Returns the size in bytes of a filter created with room for `capacity` keys in the
+    /// `Plain` layout.
+    pub fn bytes_for(capacity: usize) -> usize {
+        Self::num_buckets_for(capacity) * size_of::<[u16; BUCKET_SIZE]>()
+    }
+
+    /// Returns the seed keys are hashed with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the algorithm the `*_key` methods hash keys with.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
+
+    /// Returns the layout of the buckets.
+    pub fn layout(&self) -> BucketLayout {
+        self.buckets.layout()
+    }
+
+    /// Returns the size of the bucket array in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.buckets.size_in_bytes()
+    }
+
+    /// Returns the number of keys held.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the filter holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of fingerprint slots.
+    pub fn slots(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    /// Returns the fraction of slots in use.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots() as f64
+    }
+
+    #[inline]
+    fn index_and_fingerprint(&self, h: u64) -> (usize, u16) {
+        let index = (h >> 32) as usize & (self.buckets.len() - 1);
+        (index, (h as u16).max(1))
+    }
+
+    #[inline]
+    fn alt_index(&self, index: usize, fp: u16) -> usize {
+        index ^ ((fp as u32).wrapping_mul(0x5bd1_e995) as usize & (self.buckets.len() - 1))
+    }
+
+    /// Puts the fingerprint in an empty slot of bucket `index`, returning false if it is full.
+    fn put(&mut self, index: usize, fp: u16) -> bool {
+        let mut bucket = self.buckets.get(index);
+        let Some(slot) = bucket.iter_mut().find(|s| **s == EMPTY) else {
+            return false;
+        };
+        *slot = fp;
+        self.buckets.set(index, bucket);
+        true
+    }
+
+    /// Puts the fingerprint in bucket `index` or, evicting others, in one of their
+    /// alternates. Returns the fingerprint left without a slot, if any.
+    fn place(&mut self, mut index: usize, mut fp: u16) -> Option<(usize, u16)> {
+        for kick in 0..=MAX_KICKS {
+            if self.put(index, fp) {
+                return None;
+            }
+            if kick == MAX_KICKS {
+                break;
+            }
+            let mut bucket = self.buckets.get(index);
+            let slot = (fp as usize + kick) % BUCKET_SIZE;
+            mem::swap(&mut fp, &mut bucket[slot]);
+            self.buckets.set(index, bucket);
+            index = self.alt_index(index, fp);
+        }
+        Some((index, fp))
+    }
+
+    /// Inserts a hash, returning false if the filter is full.
+    ///
+    /// A full filter is left unchanged. Inserting the same hash more than eight times fills
+    /// its two buckets, so deduplicate first when keys repeat.
+    pub fn insert_hash(&mut self, h: u64) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (index, fp) = self.index_and_fingerprint(h);
+        if !self.put(index, fp) {
+            self.victim = self.place(self.alt_index(index, fp), fp);
+        }
+        self.len += 1;
+        true
+    }
+
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let (i1, fp) = self.index_and_fingerprint(h);
+        let i2 = self.alt_index(i1, fp);
+        self.buckets.get(i1).contains(&fp)
+            || self.buckets.get(i2).contains(&fp)
+            || self.victim == Some((i1, fp))
+            || self.victim == Some((i2, fp))
+    }
+
+    /// Removes one copy of a hash, returning whether it was found.
+    pub fn remove_hash(&mut self, h: u64) -> bool {
+        let (i1, fp) = self.index_and_fingerprint(h);
+        let i2 = self.alt_index(i1, fp);
+        if self.victim == Some((i1, fp)) || self.victim == Some((i2, fp)) {
+            self.victim = None;
+            self.len -= 1;
+            return true;
+        }
+        for index in [i1, i2] {
+            let mut bucket = self.buckets.get(index);
+            if let Some(slot) = bucket.iter_mut().find(|s| **s == fp) {
+                *slot = EMPTY;
+                self.buckets.set(index, bucket);
+                self.len -= 1;
+                if let Some((index, fp)) = self.victim.take() {
+                    self.victim = self.place(index, fp);
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Hashes the key and inserts it, returning false if the filter is full.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        self.insert_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Hashes the key and removes one copy of it.
+    pub fn remove_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        self.remove_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Removes every key.
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+        self.victim = None;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_lookup_remove() {
+        let mut cf = CuckooFilter::new(10_000);
+        assert!((0..10_000u64).all(|i| cf.insert_key(&i)));
+        assert_eq!(cf.len(), 10_000);
+        assert!((0..10_000u64).all(|i| cf.may_match_key(&i)));
+        let fp = (10_000..110_000u64).filter(|i| cf.may_match_key(i)).count();
+        assert!(fp < 50, "{fp}");
+
+        assert!((0..10_000u64).step_by(2).all(|i| cf.remove_key(&i)));
+        assert_eq!(cf.len(), 5000);
+        assert!((1..10_000u64).step_by(2).all(|i| cf.may_match_key(&i)));
+        let left = (0..10_000u64)
+            .step_by(2)
+            .filter(|i| cf.may_match_key(i))
+            .count();
+        assert!(left < 10, "{left}");
+    }
+
+    #[test]
+    fn test_full_filter_keeps_its_keys() {
+        let mut cf = CuckooFilter::new(1000);
+        let inserted = (0..u64::MAX).take_while(|i| cf.insert_key(i)).count() as u64;
+        assert!(cf.load_factor() > 0.9, "{}", cf.load_factor());
+        assert!(!cf.insert_key(&u64::MAX));
+        assert!((0..inserted).all(|i| cf.may_match_key(&i)));
+
+        // Removals make room for the parked fingerprint, then for new keys.
+        assert!((0..100u64).all(|i| cf.remove_key(&i)));
+        assert!(cf.insert_key(&u64::MAX));
+        assert!((100..inserted).all(|i| cf.may_match_key(&i)));
+    }
+
+    #[test]
+    fn test_semi_sorted_layout_saves_a_bit_per_slot() {
+        let mut plain = CuckooFilter::new_with_seed(10_000, 7);
+        let mut semi = CuckooFilter::new_with_layout(10_000, 7, BucketLayout::SemiSorted);
+        assert_eq!(semi.layout(), BucketLayout::SemiSorted);
+        assert_eq!(semi.size_in_bytes(), plain.size_in_bytes() * 15 / 16);
+
+        // Slots move as buckets are re-sorted, so evictions take other paths, but every
+        // fingerprint still sits in one of its two buckets and answers the same.
+        let inserted = 10_000;
+        assert!((0..inserted).all(|i| plain.insert_key(&i)));
+        assert!((0..inserted).all(|i| semi.insert_key(&i)));
+        assert!((0..inserted).step_by(3).all(|i| semi.remove_key(&i)));
+        assert!((0..inserted).step_by(3).all(|i| plain.remove_key(&i)));
+        assert!((0..inserted + 100_000).all(|i| semi.may_match_key(&i) == plain.may_match_key(&i)));
+        assert_eq!(semi.len(), plain.len());
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&semi).unwrap();
+            assert_eq!(serde_json::from_str::<CuckooFilter>(&json).unwrap(), semi);
+        }
+        semi.clear();
+        assert!(!semi.may_match_key(&1u64));
+    }
+
+    #[test]
+    fn test_keys_hash_with_the_filter_algorithm() {
+        let mut cf = CuckooFilter::new_with_hash(1000, 7, HashAlgorithm::Xxh3);
+        assert!(cf.insert_key("Leopold Bloom"));
+        assert!(cf.may_match_hash(HashAlgorithm::Xxh3.hash_key(7, "Leopold Bloom")));
+        assert!(cf.remove_key("Leopold Bloom"));
+        assert!(cf.is_empty());
+    }
+}
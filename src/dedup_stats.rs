@@ -0,0 +1,107 @@
+use std::hash::Hash;
+
+use crate::BlockedBloomFilter;
+
+/// Approximate deduplication report of the inserts made through a `DedupStatsFilter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DedupStats {
+    /// Inserts made.
+    pub inserts: u64,
+    /// Estimated number of distinct keys among them.
+    pub unique_estimate: f64,
+    /// Estimated number of inserts that repeated an earlier key.
+    pub duplicate_estimate: f64,
+}
+
+/// A filter that estimates how many of its inserts were duplicates while it is built.
+///
+/// Every insert goes through `BlockedBloomFilter::insert_hash_checked`. A key that already
+/// matched is either a duplicate or a false positive, so counting the "new" answers
+/// undercounts distinct keys. Each new key is instead weighted by `1 / (1 - fpr)`, with `fpr`
+/// the filter's expected false positive rate at that point, which corrects for the distinct
+/// keys lost to false positives on average. The report is free in that it costs no probes
+/// beyond the insert itself.
+#[derive(Clone, Debug)]
+pub struct DedupStatsFilter {
+    filter: BlockedBloomFilter,
+    inserts: u64,
+    new_inserts: u64,
+    unique_estimate: f64,
+}
+
+impl DedupStatsFilter {
+    /// Wraps `filter`, counting only the inserts made from now on.
+    pub fn new(filter: BlockedBloomFilter) -> Self {
+        Self {
+            filter,
+            inserts: 0,
+            new_inserts: 0,
+            unique_estimate: 0.0,
+        }
+    }
+
+    /// Expected false positive rate after `n` distinct keys, treating every block as holding
+    /// the average load.
+    fn expected_fpr(&self, n: f64) -> f64 {
+        let word_fill = 1.0 - (-n / (32.0 * self.filter.num_blocks as f64)).exp();
+        word_fill.powi(BlockedBloomFilter::PROBES as i32)
+    }
+
+    /// Inserts a hash and returns whether it was new.
+    pub fn insert_hash_checked(&mut self, h: u64) -> bool {
+        self.inserts += 1;
+        let new = self.filter.insert_hash_checked(h);
+        if new {
+            self.new_inserts += 1;
+            let fpr = self.expected_fpr(self.new_inserts as f64);
+            self.unique_estimate += 1.0 / (1.0 - fpr);
+        }
+        new
+    }
+
+    /// Hashes the key and inserts it, returning whether it was new.
+    pub fn insert_key_checked<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        self.insert_hash_checked(self.filter.hash_key(key))
+    }
+
+    /// Returns the deduplication report so far.
+    pub fn stats(&self) -> DedupStats {
+        let unique_estimate = self.unique_estimate.min(self.inserts as f64);
+        DedupStats {
+            inserts: self.inserts,
+            unique_estimate,
+            duplicate_estimate: self.inserts as f64 - unique_estimate,
+        }
+    }
+
+    /// Returns the wrapped filter.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Returns the filter, dropping the counters.
+    pub fn into_inner(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_estimates() {
+        let mut filter = DedupStatsFilter::new(BlockedBloomFilter::new(10_000, 0.05));
+        for i in 0..30_000u64 {
+            filter.insert_key_checked(&(i % 10_000));
+        }
+        let stats = filter.stats();
+        assert_eq!(stats.inserts, 30_000);
+        assert!(
+            (stats.unique_estimate - 10_000.0).abs() < 100.0,
+            "{stats:?}"
+        );
+        assert!((stats.duplicate_estimate - 20_000.0).abs() < 100.0);
+        assert!(filter.filter().may_match_key(&9_999u64));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{BlockedBloomFilter, CacheLineBlock};
+
+/// Number of buckets in `FilterDiagnostics::block_fill_histogram`.
+pub const HISTOGRAM_BUCKETS: usize = 16;
+
+/// A snapshot of a filter's shape and fill that is safe to share.
+///
+/// It carries no keys, hashes, seed or block contents, only aggregate counts, so it can be
+/// attached to bug reports without leaking membership information.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterDiagnostics {
+    /// Number of cache-line blocks.
+    pub num_blocks: u32,
+    /// Size of one block in bytes.
+    pub block_bytes: usize,
+    /// Total number of bits set.
+    pub bits_set: u64,
+    /// Fraction of all bits that are set.
+    pub fill_ratio: f64,
+    /// Number of distinct inserts implied by the fill.
+    pub estimated_entries: f64,
+    /// Probability that a random absent hash matches.
+    pub estimated_fpr: f64,
+    /// Blocks counted by fill ratio, in equal-width buckets from empty to full.
+    pub block_fill_histogram: [u64; HISTOGRAM_BUCKETS],
+}
+
+impl BlockedBloomFilter {
+    /// Produces a diagnostic snapshot without any key- or hash-derived payload.
+    pub fn export_diagnostics(&self) -> FilterDiagnostics {
+        let block_bits = CacheLineBlock::BITS as u64;
+        let mut bits_set = 0;
+        let mut fpr_sum = 0.0;
+        let mut word_fill_sum = 0.0;
+        let mut block_fill_histogram = [0; HISTOGRAM_BUCKETS];
+
+        for block in self.blocks.iter() {
+            let ones: u64 = block.words.iter().map(|w| w.count_ones() as u64).sum();
+            bits_set += ones;
+
+            let bucket = (ones * HISTOGRAM_BUCKETS as u64 / block_bits) as usize;
+            block_fill_histogram[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+
+            // A probe sets one bit per word, so it matches with the product of word fills.
+            fpr_sum += block
+                .words
+                .iter()
+                .map(|w| w.count_ones() as f64 / 32.0)
+                .product::<f64>();
+            word_fill_sum += ones as f64 / block_bits as f64;
+        }
+
+        let num_blocks = self.num_blocks as f64;
+        let word_fill = word_fill_sum / num_blocks;
+        FilterDiagnostics {
+            num_blocks: self.num_blocks,
+            block_bytes: size_of::<CacheLineBlock>(),
+            bits_set,
+            fill_ratio: bits_set as f64 / (num_blocks * block_bits as f64),
+            // Each insert sets one bit in each word of its block: fill = 1 - (31/32)^(n/blocks).
+            estimated_entries: num_blocks * (1.0 - word_fill).ln() / (31.0f64 / 32.0).ln(),
+            estimated_fpr: fpr_sum / num_blocks,
+            block_fill_histogram,
+        }
+    }
+
+    /// Measures how unevenly inserts spread over blocks, to detect hashes with weak upper bits.
+    ///
+    /// Returns the index of dispersion (variance over mean) of the per-block insert counts
+    /// implied by each block's fill. Well-mixed hashes land in blocks like a Poisson process,
+    /// giving about 1; hashes whose upper bits carry little entropy pile into a few blocks and
+    /// give much larger values. Above 2 or so, rebuild with `BlockMapping::Mixed`. An empty
+    /// filter reports 1.
+    pub fn block_skew(&self) -> f64 {
+        let block_bits = CacheLineBlock::BITS as f64;
+        let loads: Vec<f64> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let ones: u32 = block.words.iter().map(|w| w.count_ones()).sum();
+                // A full block implies at least as many inserts as one bit short of full.
+                let fill = (ones as f64).min(block_bits - 0.5) / block_bits;
+                (1.0 - fill).ln() / (31.0f64 / 32.0).ln()
+            })
+            .collect();
+        let n = loads.len() as f64;
+        let mean = loads.iter().sum::<f64>() / n;
+        if mean == 0.0 {
+            return 1.0;
+        }
+        let variance = loads.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / n;
+        variance / mean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_diagnostics() {
+        let mut bf = BlockedBloomFilter::new(10_000, 0.01);
+        let empty = bf.export_diagnostics();
+        assert_eq!(empty.bits_set, 0);
+        assert_eq!(empty.estimated_fpr, 0.0);
+        assert_eq!(empty.block_fill_histogram[0], bf.num_blocks as u64);
+
+        for i in 0..10_000u64 {
+            bf.insert_key(&i);
+        }
+        let d = bf.export_diagnostics();
+        assert_eq!(
+            d.block_fill_histogram.iter().sum::<u64>(),
+            bf.num_blocks as u64
+        );
+        assert!((d.estimated_entries - 10_000.0).abs() < 500.0);
+        assert!(d.estimated_fpr > 0.001 && d.estimated_fpr < 0.02);
+    }
+
+    #[test]
+    fn test_block_skew_detects_weak_upper_bits() {
+        let mut good = BlockedBloomFilter::new(10_000, 0.01);
+        let mut weak = BlockedBloomFilter::new(10_000, 0.01);
+        let mut mixed =
+            BlockedBloomFilter::new_with_mapping(10_000, 0.01, 0, crate::BlockMapping::Mixed);
+        for i in 0..10_000u64 {
+            let h = good.hash_key(&i);
+            good.insert_hash(h);
+            // Widened 32-bit hashes: the upper half is always zero.
+            weak.insert_hash(h & 0xffff_ffff);
+            mixed.insert_hash(h & 0xffff_ffff);
+        }
+        assert_eq!(BlockedBloomFilter::new(100, 0.01).block_skew(), 1.0);
+        assert!(good.block_skew() < 2.0, "{}", good.block_skew());
+        assert!(weak.block_skew() > 10.0, "{}", weak.block_skew());
+        assert!(mixed.block_skew() < 2.0, "{}", mixed.block_skew());
+        assert!((0..10_000u64).all(|i| mixed.may_match_hash(mixed.hash_key(&i) & 0xffff_ffff)));
+    }
+}
@@ -0,0 +1,132 @@
+use std::hash::Hash;
+
+use crate::BlockedBloomFilter;
+
+/// An include set with exceptions: a key passes if it may be in the include filter and is not
+/// in the exclude filter.
+///
+/// Both halves are approximate, and their errors pull in opposite directions:
+///
+/// - A false positive of the include filter lets a key that was never included pass, at the
+///   include filter's false positive rate. This is the usual Bloom filter error.
+/// - A false positive of the exclude filter rejects a key that was included and never
+///   excluded. These are false negatives, which a plain filter never has, at the exclude
+///   filter's false positive rate.
+///
+/// So `may_match_key` answering `false` does not prove a key is outside the set, unlike a plain
+/// filter. Size the exclude filter for the false negative rate the caller can tolerate;
+/// `false_positive_rate` and `false_negative_rate` report the current estimates. A key in
+/// both sets is rejected, so exceptions always win.
+#[derive(Clone, Debug)]
+pub struct DiffFilter {
+    include: BlockedBloomFilter,
+    exclude: BlockedBloomFilter,
+}
+
+impl DiffFilter {
+    /// Creates an empty pair, the include filter sized for `include_entries` at
+    /// `include_fpr` and the exclude filter for `exclude_entries` at `false_negative_rate`.
+    pub fn new(
+        include_entries: usize,
+        include_fpr: f64,
+        exclude_entries: usize,
+        false_negative_rate: f64,
+        seed: u64,
+    ) -> Self {
+        Self::from_filters(
+            BlockedBloomFilter::new_with_seed(include_entries, include_fpr, seed),
+            BlockedBloomFilter::new_with_seed(exclude_entries, false_negative_rate, seed),
+        )
+    }
+
+    /// Combines existing filters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filters use different seeds, since each key is hashed once for both.
+    pub fn from_filters(include: BlockedBloomFilter, exclude: BlockedBloomFilter) -> Self {
+        assert_eq!(
+            include.seed, exclude.seed,
+            "include and exclude filters must share a seed"
+        );
+        Self { include, exclude }
+    }
+
+    /// Adds a hash to the include set.
+    pub fn include_hash(&mut self, h: u64) {
+        self.include.insert_hash(h);
+    }
+
+    /// Adds a hash to the exclude set.
+    pub fn exclude_hash(&mut self, h: u64) {
+        self.exclude.insert_hash(h);
+    }
+
+    /// Hashes the key and adds it to the include set.
+    pub fn include_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.include.insert_key(key);
+    }
+
+    /// Hashes the key and adds it to the exclude set.
+    pub fn exclude_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.exclude.insert_key(key);
+    }
+
+    /// Checks if the hash may be included and is not excluded.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.include.may_match_hash(h) && !self.exclude.may_match_hash(h)
+    }
+
+    /// Hashes the key and checks it like `may_match_hash`.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.include.hash_key(key))
+    }
+
+    /// Returns the estimated rate at which keys never included pass, ignoring exclusions.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.include.export_diagnostics().estimated_fpr
+    }
+
+    /// Returns the estimated rate at which included keys that were never excluded are
+    /// rejected.
+    pub fn false_negative_rate(&self) -> f64 {
+        self.exclude.export_diagnostics().estimated_fpr
+    }
+
+    /// Returns the include filter.
+    pub fn include(&self) -> &BlockedBloomFilter {
+        &self.include
+    }
+
+    /// Returns the exclude filter.
+    pub fn exclude(&self) -> &BlockedBloomFilter {
+        &self.exclude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceptions_override_includes() {
+        let mut filter = DiffFilter::new(10_000, 0.01, 1000, 0.001, 7);
+        for i in 0..10_000u64 {
+            filter.include_key(&i);
+        }
+        for i in (0..10_000u64).step_by(10) {
+            filter.exclude_key(&i);
+        }
+        assert!(
+            (0..10_000u64)
+                .step_by(10)
+                .all(|i| !filter.may_match_key(&i))
+        );
+        let rejected = (0..10_000u64)
+            .filter(|i| i % 10 != 0 && !filter.may_match_key(i))
+            .count();
+        assert!(rejected < 30, "{rejected}");
+        assert!(filter.false_negative_rate() < 0.003);
+        assert!(filter.false_positive_rate() < 0.02);
+    }
+}
@@ -0,0 +1,176 @@
+use std::hash::Hash;
+
+use crate::BlockedBloomFilter;
+
+/// Every this many queries, an adaptive ensemble probes all members to sample their rejection
+/// rates.
+const SAMPLE_EVERY: u64 = 64;
+
+/// Samples between reorderings of the members.
+const REORDER_EVERY: u64 = 16;
+
+/// Samples after which the counts are halved, so the order follows a changing workload.
+const DECAY_WINDOW: u64 = 1024;
+
+/// The order in which an `EnsembleFilter` probes its members.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProbeOrder {
+    /// The order the members were added in.
+    #[default]
+    Fixed,
+    /// Most selective first, by rejection rates observed on live queries.
+    Adaptive,
+}
+
+/// A conjunction of filters: a key may match only if every member may contain it.
+///
+/// Useful for intersections, such as "seen this week and in the cohort", where each member
+/// covers one set. A query ends at the first member that rejects the key, so negative lookups
+/// are cheapest when the most selective member comes first. With `ProbeOrder::Adaptive` the
+/// ensemble finds that order itself: every `SAMPLE_EVERY`-th query probes all members without
+/// short-circuiting and counts who rejects, which measures each member's own rejection rate
+/// rather than its rate among keys that earlier members let through. The members are
+/// reordered from these counts as they accumulate, ties going to the smaller and so cheaper
+/// filter, and the counts decay so the order tracks a shifting workload.
+#[derive(Clone, Debug)]
+pub struct EnsembleFilter {
+    members: Vec<BlockedBloomFilter>,
+    order: Vec<usize>,
+    probe_order: ProbeOrder,
+    queries: u64,
+    samples: u64,
+    rejections: Vec<u64>,
+}
+
+impl EnsembleFilter {
+    /// Creates an empty ensemble probing its members in `probe_order`.
+    pub fn new(probe_order: ProbeOrder) -> Self {
+        Self {
+            members: Vec::new(),
+            order: Vec::new(),
+            probe_order,
+            queries: 0,
+            samples: 0,
+            rejections: Vec::new(),
+        }
+    }
+
+    /// Adds a member and returns its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the member's seed or hash algorithm differs from the first member's, since
+    /// each key is hashed once for all of them.
+    pub fn push(&mut self, filter: BlockedBloomFilter) -> usize {
+        if let Some(first) = self.members.first() {
+            assert!(
+                filter.seed == first.seed && filter.hash == first.hash,
+                "ensemble members must share a seed and hash algorithm"
+            );
+        }
+        self.members.push(filter);
+        self.rejections.push(0);
+        self.order.push(self.members.len() - 1);
+        self.members.len() - 1
+    }
+
+    /// Checks if every member might contain the hash, stopping at the first that rejects it.
+    ///
+    /// An empty ensemble matches everything.
+    pub fn may_match_hash(&mut self, h: u64) -> bool {
+        self.queries += 1;
+        if self.probe_order == ProbeOrder::Adaptive && self.queries.is_multiple_of(SAMPLE_EVERY) {
+            return self.sample(h);
+        }
+        self.order
+            .iter()
+            .all(|&i| self.members[i].may_match_hash(h))
+    }
+
+    /// Hashes the key and checks it like `may_match_hash`.
+    pub fn may_match_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        match self.members.first() {
+            Some(first) => self.may_match_hash(first.hash_key(key)),
+            None => true,
+        }
+    }
+
+    /// Probes every member, counting rejections, and reorders when enough samples are in.
+    fn sample(&mut self, h: u64) -> bool {
+        let mut matched = true;
+        for (member, rejections) in self.members.iter().zip(&mut self.rejections) {
+            if !member.may_match_hash(h) {
+                *rejections += 1;
+                matched = false;
+            }
+        }
+        self.samples += 1;
+        if self.samples.is_multiple_of(REORDER_EVERY) {
+            let (rejections, members) = (&self.rejections, &self.members);
+            self.order
+                .sort_by_key(|&i| (std::cmp::Reverse(rejections[i]), members[i].num_blocks));
+        }
+        if self.samples >= DECAY_WINDOW {
+            self.samples /= 2;
+            self.rejections.iter_mut().for_each(|r| *r /= 2);
+        }
+        matched
+    }
+
+    /// Returns each member's sampled rejection rate, by member index. Zero until sampled, and
+    /// always zero with `ProbeOrder::Fixed`.
+    pub fn rejection_rates(&self) -> Vec<f64> {
+        self.rejections
+            .iter()
+            .map(|&r| r as f64 / self.samples.max(1) as f64)
+            .collect()
+    }
+
+    /// Returns the member indices in the order queries probe them.
+    pub fn probe_order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Returns the members, by index.
+    pub fn members(&self) -> &[BlockedBloomFilter] {
+        &self.members
+    }
+
+    /// Returns a member for inserting into it.
+    pub fn member_mut(&mut self, index: usize) -> Option<&mut BlockedBloomFilter> {
+        self.members.get_mut(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_order_probes_most_selective_first() {
+        let mut ensemble = EnsembleFilter::new(ProbeOrder::Adaptive);
+        // The first member holds almost every queried key, the second almost none.
+        let mut broad = BlockedBloomFilter::new(100_000, 0.01);
+        (0..100_000u64).for_each(|i| broad.insert_key(&i));
+        let mut narrow = BlockedBloomFilter::new(1000, 0.01);
+        (0..1000u64).for_each(|i| narrow.insert_key(&i));
+        ensemble.push(broad);
+        ensemble.push(narrow);
+        assert_eq!(ensemble.probe_order(), [0, 1]);
+
+        let hits = (0..100_000u64)
+            .filter(|i| ensemble.may_match_key(i))
+            .count();
+        assert!((1000..2500).contains(&hits), "{hits}");
+        assert_eq!(ensemble.probe_order(), [1, 0]);
+        let rates = ensemble.rejection_rates();
+        assert!(rates[0] < 0.05 && rates[1] > 0.95, "{rates:?}");
+
+        let mut fixed = EnsembleFilter::new(ProbeOrder::Fixed);
+        fixed.push(ensemble.members()[0].clone());
+        fixed.push(ensemble.members()[1].clone());
+        assert!((0..10_000u64).all(|i| fixed.may_match_key(&i) == ensemble.may_match_key(&i)));
+        assert_eq!(fixed.probe_order(), [0, 1]);
+        assert_eq!(fixed.rejection_rates(), [0.0, 0.0]);
+    }
+}
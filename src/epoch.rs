@@ -0,0 +1,137 @@
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{SharedBloomFilter, key_hash};
+
+/// A sliding-window filter whose rotation never blocks readers or writers.
+///
+/// Like `ExpiringBloomFilter`, keys go into the newest of `segments` live segments and queries
+/// check all of them. Segments are `SharedBloomFilter`s, so inserts and queries take `&self`
+/// and no lock. One extra retired segment is kept out of the rotation: `advance_epoch`
+/// publishes the new epoch first, which hands the already cleared retired segment to writers
+/// and retires the oldest live one, and only then wipes the newly retired segment with atomic
+/// stores. Operations that loaded the previous epoch still find their segment intact.
+///
+/// A key stays visible for between `segments - 1` and `segments` epochs. The exception is a
+/// writer stalled across `segments` epochs, whose insert may land in a retired segment.
+#[derive(Debug)]
+pub struct EpochExpiringFilter {
+    segments: Vec<SharedBloomFilter>,
+    live: u64,
+    seed: u64,
+    epoch: AtomicU64,
+    advancing: Mutex<()>,
+}
+
+impl EpochExpiringFilter {
+    /// Creates a filter of `segments` live segments, each sized for `entries_per_segment`.
+    pub fn new(entries_per_segment: usize, fpr: f64, segments: usize) -> Self {
+        Self::new_with_seed(entries_per_segment, fpr, segments, 0)
+    }
+
+    /// Creates a filter with a custom seed shared by all segments.
+    pub fn new_with_seed(entries_per_segment: usize, fpr: f64, segments: usize, seed: u64) -> Self {
+        assert!(
+            segments > 0,
+            "an expiring filter needs at least one segment"
+        );
+        Self {
+            segments: (0..=segments)
+                .map(|_| SharedBloomFilter::new_with_seed(entries_per_segment, fpr, seed))
+                .collect(),
+            live: segments as u64,
+            seed,
+            epoch: AtomicU64::new(0),
+            advancing: Mutex::new(()),
+        }
+    }
+
+    /// Returns the number of live segments.
+    pub fn num_segments(&self) -> usize {
+        self.live as usize
+    }
+
+    /// Returns the current epoch, the number of times `advance_epoch` has completed.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    fn segment(&self, epoch: u64) -> &SharedBloomFilter {
+        &self.segments[(epoch % self.segments.len() as u64) as usize]
+    }
+
+    /// Starts a new epoch, expiring the oldest live segment.
+    ///
+    /// Meant to be called periodically from a single maintenance task. Concurrent callers
+    /// are serialized with each other but never with inserts or queries.
+    pub fn advance_epoch(&self) {
+        let _advancing = self.advancing.lock().unwrap_or_else(|e| e.into_inner());
+        let epoch = self.epoch.load(Ordering::Relaxed) + 1;
+        self.epoch.store(epoch, Ordering::Release);
+        // The segment of epoch `epoch - live` just left the window; it becomes the newest
+        // segment again at epoch `epoch + 1`.
+        self.segment(epoch + 1).clear();
+    }
+
+    /// Inserts a hash into the newest segment.
+    #[inline]
+    pub fn insert_hash(&self, h: u64) {
+        self.segment(self.epoch()).insert_hash(h);
+    }
+
+    /// Checks if any live segment might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let epoch = self.epoch();
+        (0..self.live.min(epoch + 1)).any(|age| self.segment(epoch - age).may_match_hash(h))
+    }
+
+    /// Hashes the key and inserts it into the newest segment.
+    #[inline]
+    pub fn insert_key<T: Hash + ?Sized>(&self, key: &T) {
+        self.insert_hash(key_hash(self.seed, key));
+    }
+
+    /// Hashes the key and checks if any live segment might contain it.
+    #[inline]
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(key_hash(self.seed, key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_expire_while_others_insert() {
+        let bf = EpochExpiringFilter::new(10_000, 0.01, 3);
+        bf.insert_key("Leopold Bloom");
+        bf.advance_epoch();
+        bf.insert_key("Molly Bloom");
+        bf.advance_epoch();
+        assert!(bf.may_match_key("Leopold Bloom"));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..5000u64 {
+                    bf.insert_key(&i);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..100 {
+                    assert!(bf.may_match_key("Molly Bloom"));
+                }
+            });
+            s.spawn(|| bf.advance_epoch());
+        });
+        assert_eq!(bf.epoch(), 3);
+        assert!(!bf.may_match_key("Leopold Bloom"));
+        assert!(bf.may_match_key("Molly Bloom"));
+
+        bf.advance_epoch();
+        bf.advance_epoch();
+        assert!(!bf.may_match_key("Molly Bloom"));
+    }
+}
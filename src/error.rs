@@ -0,0 +1,187 @@
+use std::fmt;
+use std::io;
+
+use crate::{DecodeError, InspectError, MergeError, ParamsError, QuotaError, WorkloadError};
+
+/// The broad category of an `Error`, stable across releases so callers can match on it.
+///
+/// New kinds may be added, so matches need a wildcard arm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Filters that must share a geometry, seed or hash algorithm do not, or a filter's
+    /// geometry is one this build does not implement.
+    IncompatibleGeometry,
+    /// Encoded filter data is malformed or truncated.
+    Corrupt,
+    /// Encoded filter data was written by a format version, or names a hash or mapping, that
+    /// this build cannot read.
+    VersionMismatch,
+    /// A tenant's memory or insert rate quota refused the operation.
+    QuotaExceeded,
+    /// The requested filter does not exist.
+    NotFound,
+    /// The caller may not modify the filter, such as another tenant's.
+    PermissionDenied,
+    /// The parameters describe no filter this crate can build.
+    InvalidParameters,
+    /// Reading or writing the underlying storage failed.
+    Io,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::IncompatibleGeometry => "incompatible filter geometry",
+            Self::Corrupt => "corrupt filter data",
+            Self::VersionMismatch => "unsupported filter version",
+            Self::QuotaExceeded => "quota exceeded",
+            Self::NotFound => "filter not found",
+            Self::PermissionDenied => "permission denied",
+            Self::InvalidParameters => "invalid filter parameters",
+            Self::Io => "I/O error",
+        })
+    }
+}
+
+/// Any error produced by this crate, classified by `ErrorKind`.
+///
+/// Each fallible operation keeps returning its own error type, such as `DecodeError` or
+/// `MergeError`, and every one of them converts into this with `?`. Code handling several of
+/// them can match on `kind` rather than on each type or its message, and still reach the
+/// original through `get_ref` or `downcast_ref`.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    inner: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl Error {
+    /// Wraps an error of the given kind.
+    pub fn new(
+        kind: ErrorKind,
+        error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            kind,
+            inner: error.into(),
+        }
+    }
+
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the wrapped error.
+    pub fn get_ref(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        &*self.inner
+    }
+
+    /// Returns the wrapped error if it is an `E`.
+    pub fn downcast_ref<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        self.inner.downcast_ref()
+    }
+
+    /// Returns the wrapped error.
+    pub fn into_inner(self) -> Box<dyn std::error::Error + Send + Sync> {
+        self.inner
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::new(ErrorKind::Io, e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+impl From<MergeError> for Error {
+    fn from(e: MergeError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+impl From<ParamsError> for Error {
+    fn from(e: ParamsError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+impl From<QuotaError> for Error {
+    fn from(e: QuotaError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+impl From<InspectError> for Error {
+    fn from(e: InspectError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+impl From<WorkloadError> for Error {
+    fn from(e: WorkloadError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+#[cfg(feature = "object_store")]
+impl From<crate::LoadError> for Error {
+    fn from(e: crate::LoadError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+#[cfg(feature = "proto")]
+impl From<crate::proto::ProtoError> for Error {
+    fn from(e: crate::proto::ProtoError) -> Self {
+        Self::new(e.kind(), e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockedBloomFilter;
+
+    #[test]
+    fn test_errors_convert_with_kind() {
+        fn load(bytes: &[u8]) -> Result<BlockedBloomFilter, Error> {
+            Ok(BlockedBloomFilter::from_bytes(bytes)?)
+        }
+        let e = load(&[0; 64]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Corrupt);
+        assert_eq!(e.downcast_ref(), Some(&DecodeError::BadMagic));
+        assert_eq!(e.to_string(), DecodeError::BadMagic.to_string());
+
+        let mut bytes = BlockedBloomFilter::new(100, 0.01).to_bytes();
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(load(&bytes).unwrap_err().kind(), ErrorKind::VersionMismatch);
+
+        let mut small = BlockedBloomFilter::new(100, 0.01);
+        let e = Error::from(
+            small
+                .union(&BlockedBloomFilter::new(10_000, 0.01))
+                .unwrap_err(),
+        );
+        assert_eq!(e.kind(), ErrorKind::IncompatibleGeometry);
+    }
+}
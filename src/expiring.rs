@@ -0,0 +1,350 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::clock::system_clock;
+use crate::{BlockedBloomFilter, Clock};
+
+/// A sliding-window filter made of rotating segments.
+///
+/// Keys are inserted into the newest segment and queried against all of them. Each `rotate`
+/// clears the oldest segment and makes it the newest, so a key stays visible for between
+/// `segments - 1` and `segments` rotation periods. The false positive rate is roughly the sum
+/// of the segments' rates.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug)]
+pub struct ExpiringBloomFilter {
+    segments: Vec<BlockedBloomFilter>,
+    head: usize,
+    // Filters serialized before segment metadata existed decode with none.
+    #[cfg_attr(feature = "serde", serde(default))]
+    meta: Vec<SegmentMeta>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sizing: Option<AdaptiveSizing>,
+    /// Smoothed inserts per segment, once a segment has been rotated out with adaptive sizing.
+    #[cfg_attr(feature = "serde", serde(default))]
+    insert_rate: Option<f64>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "system_clock"))]
+    clock: Arc<dyn Clock>,
+}
+
+/// How `ExpiringBloomFilter` resizes segments to follow the insert rate.
+///
+/// On each rotation the finished segment's insert count updates an exponentially weighted
+/// moving average, and the segment being reused is rebuilt for `headroom` times that average,
+/// clamped to `min_entries..=max_entries`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSizing {
+    /// Target false positive rate of each segment.
+    pub fpr: f64,
+    /// Weight of the newest observation in the moving average, in `(0, 1]`.
+    pub alpha: f64,
+    /// Capacity to allocate relative to the average, so spikes do not saturate a segment.
+    pub headroom: f64,
+    /// Smallest capacity a segment is sized for.
+    pub min_entries: usize,
+    /// Largest capacity a segment is sized for.
+    pub max_entries: usize,
+}
+
+impl AdaptiveSizing {
+    /// Sizes segments between `min_entries` and `max_entries` at `fpr`, with an `alpha` of 0.3
+    /// and 1.5x headroom.
+    pub fn new(fpr: f64, min_entries: usize, max_entries: usize) -> Self {
+        Self {
+            fpr,
+            alpha: 0.3,
+            headroom: 1.5,
+            min_entries,
+            max_entries,
+        }
+    }
+}
+
+/// Bookkeeping for one segment, parallel to `segments`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default)]
+struct SegmentMeta {
+    created: Option<SystemTime>,
+    inserts: u64,
+}
+
+impl SegmentMeta {
+    fn at(created: SystemTime) -> Self {
+        Self {
+            created: Some(created),
+            inserts: 0,
+        }
+    }
+}
+
+/// What `ExpiringBloomFilter::segment_info` reports about one segment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SegmentInfo {
+    /// When the segment was created or last cleared by a rotation, if known.
+    pub created: Option<SystemTime>,
+    /// Inserts into the segment since then, duplicates included.
+    pub inserts: u64,
+    /// Fraction of the segment's bits that are set.
+    pub fill_ratio: f64,
+}
+
+impl ExpiringBloomFilter {
+    /// Creates a filter of `segments` segments, each sized for `entries_per_segment`.
+    pub fn new(entries_per_segment: usize, fpr: f64, segments: usize) -> Self {
+        Self::new_with_seed(entries_per_segment, fpr, segments, 0)
+    }
+
+    /// Creates a filter with a custom seed shared by all segments.
+    pub fn new_with_seed(entries_per_segment: usize, fpr: f64, segments: usize, seed: u64) -> Self {
+        assert!(
+            segments > 0,
+            "an expiring filter needs at least one segment"
+        );
+        let segment = BlockedBloomFilter::new_with_seed(entries_per_segment, fpr, seed);
+        let clock = system_clock();
+        Self {
+            segments: vec![segment; segments],
+            head: 0,
+            meta: vec![SegmentMeta::at(clock.now()); segments],
+            sizing: None,
+            insert_rate: None,
+            clock,
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock, restamping every segment.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        for meta in &mut self.meta {
+            meta.created = Some(now);
+        }
+        self.clock = clock;
+    }
+
+    /// Returns the clock segment creation times are read from.
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Resizes each segment as it is reused, following the observed insert rate.
+    pub fn with_adaptive_sizing(mut self, sizing: AdaptiveSizing) -> Self {
+        self.sizing = Some(sizing);
+        self
+    }
+
+    /// Returns the smoothed inserts per segment that adaptive sizing is tracking.
+    pub fn insert_rate(&self) -> Option<f64> {
+        self.insert_rate
+    }
+
+    /// Returns the number of segments.
+    pub fn num_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Expires the oldest segment and starts inserting into it.
+    pub fn rotate(&mut self) {
+        let finished = self.meta.get(self.head).map_or(0, |meta| meta.inserts) as f64;
+        self.head = (self.head + 1) % self.segments.len();
+        match self.sizing {
+            Some(sizing) => {
+                let rate = match self.insert_rate {
+                    Some(rate) => sizing.alpha * finished + (1.0 - sizing.alpha) * rate,
+                    None => finished,
+                };
+                self.insert_rate = Some(rate);
+                let entries = ((rate * sizing.headroom).ceil() as usize)
+                    .clamp(sizing.min_entries, sizing.max_entries);
+                let seed = self.segments[self.head].seed;
+                self.segments[self.head] =
+                    BlockedBloomFilter::new_with_seed(entries, sizing.fpr, seed);
+            }
+            None => self.segments[self.head].clear(),
+        }
+        let now = self.clock.now();
+        if let Some(meta) = self.meta.get_mut(self.head) {
+            *meta = SegmentMeta::at(now);
+        }
+    }
+
+    /// Reports creation time, insert count and fill for each segment, oldest first.
+    ///
+    /// Insert counts start at zero for segments restored from a checkpoint, and creation times
+    /// are unknown for filters serialized without segment metadata.
+    pub fn segment_info(&self) -> Vec<SegmentInfo> {
+        let order = (self.head + 1..self.segments.len()).chain(0..=self.head);
+        order
+            .map(|i| {
+                let meta = self.meta.get(i).copied().unwrap_or_default();
+                SegmentInfo {
+                    created: meta.created,
+                    inserts: meta.inserts,
+                    fill_ratio: self.segments[i].export_diagnostics().fill_ratio,
+                }
+            })
+            .collect()
+    }
+
+    /// Builds a filter from segments ordered oldest to newest.
+    pub(crate) fn from_segments(segments: Vec<BlockedBloomFilter>) -> Self {
+        assert!(
+            !segments.is_empty(),
+            "an expiring filter needs at least one segment"
+        );
+        let clock = system_clock();
+        Self {
+            head: segments.len() - 1,
+            meta: vec![SegmentMeta::at(clock.now()); segments.len()],
+            segments,
+            sizing: None,
+            insert_rate: None,
+            clock,
+        }
+    }
+
+    /// Returns the segments ordered oldest to newest.
+    pub(crate) fn segments_oldest_first(&self) -> impl Iterator<Item = &BlockedBloomFilter> {
+        let (newer, older) = self.segments.split_at(self.head + 1);
+        older.iter().chain(newer)
+    }
+
+    /// Inserts a hash into the newest segment.
+    #[inline]
+    pub fn insert_hash(&mut self, h: u64) {
+        self.segments[self.head].insert_hash(h);
+        if let Some(meta) = self.meta.get_mut(self.head) {
+            meta.inserts += 1;
+        }
+    }
+
+    /// Checks if any segment might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.segments.iter().any(|s| s.may_match_hash(h))
+    }
+
+    /// Hashes the key and inserts it into the newest segment.
+    #[inline]
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        let h = self.segments[self.head].hash_key(key);
+        self.insert_hash(h);
+    }
+
+    /// Hashes the key and checks if any segment might contain it.
+    #[inline]
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.segments[self.head].hash_key(key))
+    }
+
+    /// Pre-populates the newest segment with a sample of recent keys, returning how many were
+    /// inserted.
+    ///
+    /// Meant for the first segment after a restart, which would otherwise start empty. With
+    /// adaptive sizing and no insert rate observed yet, the rate is seeded with the sample's
+    /// size times `scale_factor`, the live keys each sampled key stands for, so the next
+    /// rotation sizes segments for the real load instead of starting from `min_entries`. The
+    /// sample should fit in the newest segment's capacity.
+    pub fn warm_from_sample<I>(&mut self, keys: I, scale_factor: f64) -> usize
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        let mut sampled = 0;
+        for key in keys {
+            self.insert_key(&key);
+            sampled += 1;
+        }
+        if self.sizing.is_some() && self.insert_rate.is_none() {
+            self.insert_rate = Some(sampled as f64 * scale_factor.max(1.0));
+        }
+        sampled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_expire_after_rotations() {
+        let mut bf = ExpiringBloomFilter::new(1000, 0.01, 3);
+        bf.insert_key("Leopold Bloom");
+
+        bf.rotate();
+        bf.insert_key("Molly Bloom");
+        bf.rotate();
+        assert!(bf.may_match_key("Leopold Bloom"));
+        assert!(bf.may_match_key("Molly Bloom"));
+
+        bf.rotate();
+        assert!(!bf.may_match_key("Leopold Bloom"));
+        assert!(bf.may_match_key("Molly Bloom"));
+    }
+
+    #[test]
+    fn test_segment_info_tracks_inserts_per_segment() {
+        let mut bf = ExpiringBloomFilter::new(1000, 0.01, 3);
+        for i in 0..100u64 {
+            bf.insert_key(&i);
+        }
+        bf.rotate();
+        bf.insert_key("Molly Bloom");
+
+        let info = bf.segment_info();
+        let inserts: Vec<u64> = info.iter().map(|s| s.inserts).collect();
+        assert_eq!(inserts, [0, 100, 1]);
+        assert!(info[1].fill_ratio > info[2].fill_ratio);
+        assert_eq!(info[0].fill_ratio, 0.0);
+        assert!(info.iter().all(|s| s.created.is_some()));
+    }
+
+    #[test]
+    fn test_adaptive_sizing_follows_insert_rate() {
+        let sizing = AdaptiveSizing::new(0.01, 100, 1_000_000);
+        let mut bf = ExpiringBloomFilter::new(1000, 0.01, 2).with_adaptive_sizing(sizing);
+        let initial = bf.segments[1].num_blocks;
+
+        for i in 0..10_000u64 {
+            bf.insert_key(&i);
+        }
+        bf.rotate();
+        assert_eq!(bf.insert_rate(), Some(10_000.0));
+        assert!(bf.segments[1].num_blocks > initial * 10);
+        assert!(bf.may_match_key(&0u64));
+
+        // A quiet period shrinks the next segment again.
+        bf.rotate();
+        assert_eq!(bf.insert_rate(), Some(7_000.0));
+        bf.rotate();
+        assert!(bf.segments[1].num_blocks < bf.segments[0].num_blocks);
+    }
+
+    #[test]
+    fn test_segment_times_follow_the_clock() {
+        let clock = Arc::new(crate::ManualClock::default());
+        let mut bf = ExpiringBloomFilter::new(1000, 0.01, 2).with_clock(clock.clone());
+        clock.advance(std::time::Duration::from_secs(60));
+        bf.rotate();
+
+        let created: Vec<_> = bf.segment_info().iter().map(|s| s.created).collect();
+        let start = SystemTime::UNIX_EPOCH;
+        let later = start + std::time::Duration::from_secs(60);
+        assert_eq!(created, [Some(start), Some(later)]);
+    }
+
+    #[test]
+    fn test_warm_from_sample_seeds_insert_rate() {
+        let mut bf = ExpiringBloomFilter::new_with_seed(1000, 0.01, 3, 4)
+            .with_adaptive_sizing(AdaptiveSizing::new(0.01, 100, 1_000_000));
+        assert_eq!(bf.warm_from_sample(0..1000u64, 10.0), 1000);
+        assert!(bf.may_match_key(&999u64));
+        assert_eq!(bf.insert_rate(), Some(10_000.0));
+    }
+}
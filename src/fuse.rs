@@ -0,0 +1,209 @@
+use std::hash::Hash;
+
+use crate::xor::mix;
+use crate::{XorFingerprint, key_hash};
+
+/// The largest segment, past which longer segments stop improving construction.
+const MAX_SEGMENT_LENGTH: u32 = 1 << 18;
+
+/// An immutable 3-wise binary fuse filter over a set fixed at construction.
+///
+/// Like `XorFilter`, each hash maps to three slots whose fingerprints XOR to its own, but the
+/// slots fall in three consecutive segments of a window chosen per key rather than in fixed
+/// thirds of the array. That lets construction succeed with only about 1.13 fingerprints per
+/// key for large sets, against 1.23 for an xor filter, the best space efficiency of the
+/// filters in this crate: about 9 bits per key at 0.39% false positives with `u8`
+/// fingerprints, or 18 at 0.0015% with `u16`. Keys are hashed with xxh64 like
+/// `BlockedBloomFilter`, so the same seed gives the same hashes in both.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryFuseFilter<F = u8> {
+    fingerprints: Vec<F>,
+    segment_length: u32,
+    segment_count_length: u32,
+    len: usize,
+    seed: u64,
+    mix_seed: u64,
+}
+
+/// Returns the segment length and the number of segments windows can start in for `size`
+/// distinct hashes. The fingerprint array has two more segments than windows can start in.
+pub(crate) fn geometry_for(size: usize) -> (u32, usize) {
+    let segment_length = if size == 0 {
+        4
+    } else {
+        (1u32 << ((size as f64).ln() / 3.33f64.ln() + 2.25).floor() as u32).min(MAX_SEGMENT_LENGTH)
+    };
+    let capacity = if size <= 1 {
+        0
+    } else {
+        let size_factor = (0.875 + 0.25 * 1e6f64.ln() / (size as f64).ln()).max(1.125);
+        (size as f64 * size_factor).round() as usize
+    };
+    let segment_count = capacity
+        .div_ceil(segment_length as usize)
+        .saturating_sub(2)
+        .max(1);
+    (segment_length, segment_count)
+}
+
+impl<F: XorFingerprint> BinaryFuseFilter<F> {
+    /// Builds a filter from key hashes made with `seed`, the seed `may_match_key` hashes with.
+    ///
+    /// Duplicate hashes are ignored. Construction rehashes and retries in the rare case the
+    /// slots cannot be solved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than about 3.8 billion distinct hashes, past which slots no
+    /// longer fit in 32 bits.
+    pub fn from_hashes<I: IntoIterator<Item = u64>>(hashes: I, seed: u64) -> Self {
+        let mut hashes: Vec<u64> = hashes.into_iter().collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let size = hashes.len();
+        let (segment_length, segment_count) = geometry_for(size);
+        let segment_count_length = u32::try_from(segment_count * segment_length as usize)
+            .expect("too many hashes for a binary fuse filter");
+        let array_len = (segment_count + 2) * segment_length as usize;
+
+        let mut counts = vec![0u32; array_len];
+        let mut xors = vec![0u64; array_len];
+        let mut queue = Vec::new();
+        let mut stack = Vec::with_capacity(size);
+        let mut mix_seed = seed;
+        let slots = |hm| Self::slots(hm, segment_length, segment_count_length);
+
+        loop {
+            counts.fill(0);
+            xors.fill(0);
+            for &h in &hashes {
+                let hm = mix(h, mix_seed);
+                for slot in slots(hm) {
+                    counts[slot] += 1;
+                    xors[slot] ^= hm;
+                }
+            }
+
+            // Peel slots used by a single key until none are left.
+            queue.clear();
+            queue.extend((0..array_len).filter(|&slot| counts[slot] == 1));
+            stack.clear();
+            while let Some(slot) = queue.pop() {
+                if counts[slot] != 1 {
+                    continue;
+                }
+                let hm = xors[slot];
+                stack.push((hm, slot));
+                for other in slots(hm) {
+                    xors[other] ^= hm;
+                    counts[other] -= 1;
+                    if counts[other] == 1 {
+                        queue.push(other);
+                    }
+                }
+            }
+            if stack.len() == size {
+                break;
+            }
+            mix_seed = mix(mix_seed, 0x9e37_79b9_7f4a_7c15);
+        }
+
+        // Assign in reverse peeling order, so each key's slot is the last of its three set.
+        let mut fingerprints = vec![F::default(); array_len];
+        for &(hm, slot) in stack.iter().rev() {
+            let [a, b, c] = slots(hm);
+            fingerprints[slot] =
+                F::from_hash(hm) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+        }
+        Self {
+            fingerprints,
+            segment_length,
+            segment_count_length,
+            len: size,
+            seed,
+            mix_seed,
+        }
+    }
+
+    /// Hashes the keys with `seed` and builds a filter from them.
+    pub fn from_keys<I>(keys: I, seed: u64) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        Self::from_hashes(keys.into_iter().map(|key| key_hash(seed, &key)), seed)
+    }
+
+    /// Picks the window's first segment from the high bits of the product, then one slot in
+    /// each of its three segments.
+    #[inline]
+    fn slots(hm: u64, segment_length: u32, segment_count_length: u32) -> [usize; 3] {
+        let mask = segment_length - 1;
+        let h0 = ((hm as u128 * segment_count_length as u128) >> 64) as u32;
+        let h1 = (h0 + segment_length) ^ ((hm >> 18) as u32 & mask);
+        let h2 = (h0 + 2 * segment_length) ^ (hm as u32 & mask);
+        [h0 as usize, h1 as usize, h2 as usize]
+    }
+
+    /// Checks if the set might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let hm = mix(h, self.mix_seed);
+        let [a, b, c] = Self::slots(hm, self.segment_length, self.segment_count_length);
+        F::from_hash(hm) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+
+    /// Hashes the key and checks if the set might contain it.
+    #[inline]
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(key_hash(self.seed, key))
+    }
+
+    /// Returns the number of distinct hashes the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the filter was built from no hashes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the seed keys are hashed with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the size of the fingerprint array in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.fingerprints.len() * size_of::<F>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorFilter8;
+
+    #[test]
+    fn test_fuse_filter() {
+        let keys = (0..100_000u64).chain(0..1000);
+        let fuse: BinaryFuseFilter = BinaryFuseFilter::from_keys(keys.clone(), 5);
+        let fuse16 = BinaryFuseFilter::<u16>::from_keys(keys.clone(), 5);
+        assert_eq!(fuse.len(), 100_000);
+        assert!((0..100_000u64).all(|i| fuse.may_match_key(&i) && fuse16.may_match_key(&i)));
+
+        let fp = (100_000..1_100_000u64)
+            .filter(|i| fuse.may_match_key(i))
+            .count();
+        assert!((3400..4400).contains(&fp), "{fp}");
+        assert!(fuse.size_in_bytes() < XorFilter8::from_keys(keys, 5).size_in_bytes());
+
+        for n in 0..5u64 {
+            let small: BinaryFuseFilter = BinaryFuseFilter::from_hashes(0..n, 0);
+            assert!((0..n).all(|h| small.may_match_hash(h)));
+        }
+    }
+}
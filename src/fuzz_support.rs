@@ -0,0 +1,159 @@
+//! Invariant checks for fuzzing the filters, shared by the crate's own targets and downstream
+//! fuzzers.
+//!
+//! Every check panics with a description of the broken invariant, which is how fuzzers
+//! detect failures. Inputs are plain bytes decoded deterministically, so any fuzzer's corpus
+//! works and a crashing input replays exactly.
+
+use std::collections::HashSet;
+
+use crate::{ArchivedFilter, BlockedBloomFilter};
+
+/// Entries the filters built by these helpers are sized for. Small enough that fuzz inputs
+/// fill them and exercise saturated blocks.
+pub const FUZZ_ENTRIES: usize = 256;
+
+/// One step of an operation sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Inserts the hash.
+    Insert(u64),
+    /// Queries the hash, checking it matches if it was inserted.
+    Query(u64),
+    /// Joins a filter holding only the hash.
+    Join(u64),
+    /// Replaces the filter with its decoded encoding.
+    RoundTrip,
+    /// Clears the filter.
+    Clear,
+}
+
+/// Decodes an operation sequence, nine bytes per operation: a tag byte and a little-endian
+/// hash. A trailing partial operation is ignored.
+pub fn decode_ops(data: &[u8]) -> Vec<Op> {
+    data.chunks_exact(9)
+        .map(|chunk| {
+            let h = u64::from_le_bytes(chunk[1..].try_into().unwrap());
+            match chunk[0] % 5 {
+                0 => Op::Insert(h),
+                1 => Op::Query(h),
+                2 => Op::Join(h),
+                3 => Op::RoundTrip,
+                _ => Op::Clear,
+            }
+        })
+        .collect()
+}
+
+/// Builds a filter holding the little-endian hashes in `data`, eight bytes each.
+pub fn filter_from_bytes(data: &[u8], seed: u64) -> BlockedBloomFilter {
+    let mut filter = BlockedBloomFilter::new_with_seed(FUZZ_ENTRIES, 0.01, seed);
+    for chunk in data.chunks_exact(8) {
+        filter.insert_hash(u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    filter
+}
+
+/// Runs `ops` against a fresh filter, checking after every step that each hash inserted since
+/// the last clear still matches.
+///
+/// # Panics
+///
+/// Panics if an inserted hash stops matching.
+pub fn check_no_false_negatives(ops: &[Op]) {
+    let mut filter = BlockedBloomFilter::new(FUZZ_ENTRIES, 0.01);
+    let mut inserted = HashSet::new();
+    for (step, &op) in ops.iter().enumerate() {
+        match op {
+            Op::Insert(h) => {
+                filter.insert_hash(h);
+                inserted.insert(h);
+            }
+            Op::Query(h) => {
+                assert!(
+                    filter.may_match_hash(h) || !inserted.contains(&h),
+                    "step {step}: inserted hash {h:#x} does not match"
+                );
+            }
+            Op::Join(h) => {
+                let mut other = BlockedBloomFilter::new(FUZZ_ENTRIES, 0.01);
+                other.insert_hash(h);
+                filter.join(&other);
+                inserted.insert(h);
+            }
+            Op::RoundTrip => {
+                check_round_trip(&filter);
+                filter = BlockedBloomFilter::from_bytes(&filter.to_bytes()).unwrap();
+            }
+            Op::Clear => {
+                filter.clear();
+                inserted.clear();
+            }
+        }
+    }
+    if let Some(h) = inserted.iter().find(|&&h| !filter.may_match_hash(h)) {
+        panic!(
+            "inserted hash {h:#x} does not match after {} steps",
+            ops.len()
+        );
+    }
+}
+
+/// Checks that encoding and decoding is idempotent and that the zero-copy view agrees with
+/// the owned filter.
+///
+/// # Panics
+///
+/// Panics if decoding fails or changes the encoding.
+pub fn check_round_trip(filter: &BlockedBloomFilter) {
+    let bytes = filter.to_bytes();
+    let decoded = BlockedBloomFilter::from_bytes(&bytes).expect("encoding does not decode");
+    assert_eq!(decoded.to_bytes(), bytes, "round trip changed the encoding");
+    let view = ArchivedFilter::from_bytes(&bytes).expect("encoding does not decode as a view");
+    assert_eq!(view.as_bytes(), &bytes[..], "view changed the encoding");
+}
+
+/// Checks that joining is associative and commutative for three filters of the same
+/// geometry.
+///
+/// # Panics
+///
+/// Panics if the join orders disagree.
+pub fn check_join_associative(
+    a: &BlockedBloomFilter,
+    b: &BlockedBloomFilter,
+    c: &BlockedBloomFilter,
+) {
+    let mut left = a.clone();
+    left.join(b);
+    left.join(c);
+    let mut bc = b.clone();
+    bc.join(c);
+    let mut right = a.clone();
+    right.join(&bc);
+    assert_eq!(left.to_bytes(), right.to_bytes(), "join is not associative");
+    let mut swapped = c.clone();
+    swapped.join(a);
+    swapped.join(b);
+    assert_eq!(
+        left.to_bytes(),
+        swapped.to_bytes(),
+        "join is not commutative"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_pass_on_random_inputs() {
+        let data: Vec<u8> = (0..9000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        check_no_false_negatives(&decode_ops(&data));
+        let thirds: Vec<_> = data.chunks(3000).map(|d| filter_from_bytes(d, 0)).collect();
+        check_round_trip(&thirds[0]);
+        check_join_associative(&thirds[0], &thirds[1], &thirds[2]);
+    }
+}
@@ -0,0 +1,156 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, key_hash, math, probe_positions};
+
+/// A blocked filter whose inserts are tagged with a generation, so a whole generation can be
+/// removed at once.
+///
+/// Where a `BlockedBloomFilter` keeps one bit per position, this keeps one byte holding a bit
+/// per generation that set the position. `expire_generation` clears that bit everywhere, a
+/// linear pass with no per-key work, which suits time-bucketed retention (one generation per
+/// hour or day) better than deleting keys one by one. A position counts as set while any
+/// generation holds it, so queries behave like a filter of the live generations' keys. It uses
+/// eight times the memory of a `BlockedBloomFilter` of the same size.
+#[derive(Clone, Debug)]
+pub struct GenerationalBloomFilter {
+    /// `CacheLineBlock::BITS` generation masks per block.
+    masks: Vec<u8>,
+    num_blocks: u32,
+    seed: u64,
+    generations: u8,
+}
+
+impl GenerationalBloomFilter {
+    /// The most generations a filter can track, one per bit of a mask.
+    pub const MAX_GENERATIONS: u8 = 8;
+
+    /// Creates a filter sized for `entries` live keys at `fpr`, tracking `generations`
+    /// generations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `generations` is zero or above `MAX_GENERATIONS`.
+    pub fn new(entries: usize, fpr: f64, generations: u8) -> Self {
+        Self::new_with_seed(entries, fpr, generations, 0)
+    }
+
+    /// Creates a filter with a custom seed.
+    pub fn new_with_seed(entries: usize, fpr: f64, generations: u8, seed: u64) -> Self {
+        assert!(
+            (1..=Self::MAX_GENERATIONS).contains(&generations),
+            "a filter tracks between 1 and 8 generations"
+        );
+        let num_blocks = math::num_blocks_for(entries, fpr);
+        Self {
+            masks: vec![0; num_blocks as usize * CacheLineBlock::BITS],
+            num_blocks,
+            seed,
+            generations,
+        }
+    }
+
+    /// Returns the number of generations tracked.
+    pub fn num_generations(&self) -> u8 {
+        self.generations
+    }
+
+    fn generation_bit(&self, generation: u8) -> u8 {
+        assert!(
+            generation < self.generations,
+            "generation {generation} is out of range"
+        );
+        1 << generation
+    }
+
+    /// Inserts a hash as part of `generation`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `generation` is not below `num_generations`.
+    pub fn insert_hash(&mut self, h: u64, generation: u8) {
+        let bit = self.generation_bit(generation);
+        for i in probe_positions(self.num_blocks, h) {
+            self.masks[i] |= bit;
+        }
+    }
+
+    /// Checks if any live generation might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        probe_positions(self.num_blocks, h)
+            .into_iter()
+            .all(|i| self.masks[i] != 0)
+    }
+
+    /// Checks if `generation` alone might contain the hash.
+    pub fn may_match_hash_in(&self, h: u64, generation: u8) -> bool {
+        let bit = self.generation_bit(generation);
+        probe_positions(self.num_blocks, h)
+            .into_iter()
+            .all(|i| self.masks[i] & bit != 0)
+    }
+
+    /// Hashes the key and inserts it as part of `generation`.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T, generation: u8) {
+        self.insert_hash(key_hash(self.seed, key), generation);
+    }
+
+    /// Hashes the key and checks if any live generation might contain it.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(key_hash(self.seed, key))
+    }
+
+    /// Removes everything inserted as part of `generation`, which can then be reused.
+    pub fn expire_generation(&mut self, generation: u8) {
+        let keep = !self.generation_bit(generation);
+        for mask in &mut self.masks {
+            *mask &= keep;
+        }
+    }
+
+    /// Collapses the live generations into a plain filter with the same bits set.
+    pub fn to_filter(&self) -> BlockedBloomFilter {
+        let blocks = self
+            .masks
+            .chunks_exact(CacheLineBlock::BITS)
+            .map(|masks| CacheLineBlock {
+                words: std::array::from_fn(|word| {
+                    (0..32).fold(0, |w, bit| {
+                        w | ((masks[word * 32 + bit] != 0) as u32) << bit
+                    })
+                }),
+            })
+            .collect();
+        BlockedBloomFilter::from_blocks(blocks, self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiring_a_generation_removes_only_its_keys() {
+        let mut bf = GenerationalBloomFilter::new(1000, 0.01, 3);
+        for i in 0..300u64 {
+            bf.insert_key(&i, (i % 3) as u8);
+        }
+        assert!((0..300u64).all(|i| bf.may_match_key(&i)));
+
+        bf.expire_generation(1);
+        let survivors = (0..300u64).filter(|i| bf.may_match_key(i)).count();
+        assert!(survivors < 205, "{survivors}");
+        assert!(
+            (0..300u64)
+                .filter(|i| i % 3 != 1)
+                .all(|i| bf.may_match_key(&i))
+        );
+
+        let mut plain = BlockedBloomFilter::new(1000, 0.01);
+        for i in (0..300u64).filter(|i| i % 3 != 1) {
+            plain.insert_key(&i);
+        }
+        assert_eq!(bf.to_filter().blocks[..], plain.blocks[..]);
+        assert!(bf.may_match_hash_in(key_hash(0, &3u64), 0));
+        assert!(!bf.may_match_hash_in(key_hash(0, &3u64), 2));
+    }
+}
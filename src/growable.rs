@@ -0,0 +1,237 @@
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, FilterHeader, key_hash, math};
+
+/// A filter that grows by chaining segments on disk, for dedup sets with no known bound.
+///
+/// Keys go into an active segment held in memory. When it reaches its capacity it is written
+/// to its own file in the directory and sealed, and a new active segment twice as large is
+/// started. Sealed segments stay on disk: a query reads the one block it needs from each with
+/// a positioned read, so memory use is bounded by the active segment however long the chain
+/// gets. Segment `i` is sized with the blocked model for `initial_entries << i` keys at
+/// `fpr / 2^(i + 1)`, so the whole chain stays under `fpr` as it grows.
+///
+/// `open` restores the chain from the directory, reloading the newest segment as the active
+/// one. Call `sync` to persist the active segment; inserts since the last `sync` or seal are
+/// lost on a crash, which for a dedup set means a few keys may be seen as new again.
+#[derive(Debug)]
+pub struct GrowableFileFilter {
+    dir: PathBuf,
+    initial_entries: usize,
+    fpr: f64,
+    seed: u64,
+    sealed: Vec<SealedSegment>,
+    active: BlockedBloomFilter,
+    active_entries: usize,
+}
+
+#[derive(Debug)]
+struct SealedSegment {
+    header: FilterHeader,
+    file: Mutex<File>,
+}
+
+impl SealedSegment {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; FilterHeader::LEN];
+        file.read_exact(&mut header)?;
+        let header = FilterHeader::parse(&header)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            header,
+            file: Mutex::new(file),
+        })
+    }
+
+    fn may_match_hash(&self, h: u64) -> io::Result<bool> {
+        let block = self.header.mapping.block_index(h, self.header.num_blocks);
+        let mut bytes = [0u8; size_of::<CacheLineBlock>()];
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.seek(SeekFrom::Start(
+            (FilterHeader::LEN + block * bytes.len()) as u64,
+        ))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .zip(BlockedBloomFilter::SALT.iter())
+            .all(|(word, &salt)| {
+                let word = u32::from_le_bytes(word.try_into().unwrap());
+                word & (1 << ((h as u32).wrapping_mul(salt) >> 27)) != 0
+            }))
+    }
+}
+
+impl GrowableFileFilter {
+    /// Opens the chain in `dir`, creating the directory and an empty chain if needed.
+    ///
+    /// The parameters must be the ones the chain was created with, since they decide the size
+    /// of the segments still to come; existing segments keep the sizes they were written with.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        initial_entries: usize,
+        fpr: f64,
+        seed: u64,
+    ) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if Self::segment_index(&path).is_some() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut filter = Self {
+            initial_entries: initial_entries.max(1),
+            fpr,
+            seed,
+            sealed: Vec::new(),
+            active: BlockedBloomFilter::new_with_seed(1, fpr, seed),
+            active_entries: 0,
+            dir,
+        };
+        match paths.pop() {
+            Some(newest) => {
+                for path in &paths {
+                    filter.sealed.push(SealedSegment::open(path)?);
+                }
+                let active = BlockedBloomFilter::from_bytes(&fs::read(&newest)?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                filter.active_entries =
+                    active.export_diagnostics().estimated_entries.round() as usize;
+                filter.active = active;
+            }
+            None => filter.active = filter.new_segment(0),
+        }
+        Ok(filter)
+    }
+
+    fn segment_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("segment-{index:06}.blm"))
+    }
+
+    fn segment_index(path: &Path) -> Option<usize> {
+        let name = path.file_name()?.to_str()?;
+        name.strip_prefix("segment-")?
+            .strip_suffix(".blm")?
+            .parse()
+            .ok()
+    }
+
+    fn capacity(&self, index: usize) -> usize {
+        self.initial_entries.saturating_mul(1 << index.min(40))
+    }
+
+    fn new_segment(&self, index: usize) -> BlockedBloomFilter {
+        let fpr = self.fpr / 2f64.powi(index as i32 + 1);
+        let num_blocks = math::blocked_num_blocks_for(self.capacity(index), fpr);
+        BlockedBloomFilter::from_blocks(
+            vec![CacheLineBlock::default(); num_blocks as usize],
+            self.seed,
+        )
+    }
+
+    /// Inserts a hash into the active segment, sealing it first if it is full.
+    pub fn insert_hash(&mut self, h: u64) -> io::Result<()> {
+        if self.active_entries >= self.capacity(self.sealed.len()) {
+            self.seal()?;
+        }
+        self.active.insert_hash(h);
+        self.active_entries += 1;
+        Ok(())
+    }
+
+    /// Checks if any segment might contain the hash, reading one block from each sealed one.
+    pub fn may_match_hash(&self, h: u64) -> io::Result<bool> {
+        if self.active.may_match_hash(h) {
+            return Ok(true);
+        }
+        for segment in self.sealed.iter().rev() {
+            if segment.may_match_hash(h)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Hashes the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) -> io::Result<()> {
+        self.insert_hash(key_hash(self.seed, key))
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> io::Result<bool> {
+        self.may_match_hash(key_hash(self.seed, key))
+    }
+
+    /// Writes the active segment to its file, replacing the previous copy atomically.
+    pub fn sync(&self) -> io::Result<()> {
+        let path = self.segment_path(self.sealed.len());
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.active.to_bytes())?;
+        File::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, &path)
+    }
+
+    /// Writes out the active segment, seals it and starts a larger one.
+    fn seal(&mut self) -> io::Result<()> {
+        self.sync()?;
+        let index = self.sealed.len();
+        self.sealed
+            .push(SealedSegment::open(&self.segment_path(index))?);
+        self.active = self.new_segment(index + 1);
+        self.active_entries = 0;
+        Ok(())
+    }
+
+    /// Returns the number of segments, the active one included.
+    pub fn segments(&self) -> usize {
+        self.sealed.len() + 1
+    }
+
+    /// Returns the directory holding the segment files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_grows_and_reopens() {
+        let dir = std::env::temp_dir().join(format!("bloomsday-growable-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut filter = GrowableFileFilter::open(&dir, 1000, 0.01, 3).unwrap();
+        for i in 0..10_000u64 {
+            filter.insert_key(&i).unwrap();
+        }
+        assert_eq!(filter.segments(), 4);
+        filter.sync().unwrap();
+        drop(filter);
+
+        let mut reopened = GrowableFileFilter::open(&dir, 1000, 0.01, 3).unwrap();
+        assert_eq!(reopened.segments(), 4);
+        assert!((0..10_000u64).all(|i| reopened.may_match_key(&i).unwrap()));
+        let fp = (10_000..110_000u64)
+            .filter(|i| reopened.may_match_key(i).unwrap())
+            .count();
+        assert!(fp < 1000, "{fp}");
+
+        // The reloaded active segment keeps filling toward the same capacity.
+        for i in 10_000..16_000u64 {
+            reopened.insert_key(&i).unwrap();
+        }
+        assert_eq!(reopened.segments(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
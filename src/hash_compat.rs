@@ -0,0 +1,66 @@
+//! Plain xxHash functions over raw bytes, for filters shared with non-Rust code.
+//!
+//! Unlike the `*_key` methods, which feed keys through `Hash` and so add Rust-specific framing
+//! (such as the `0xff` terminator after a `str`), these hash exactly the given bytes and agree
+//! with the reference C implementation and its Java and C++ ports.
+
+use crate::{BlockedBloomFilter, HashAlgorithm};
+
+/// XXH64 of `bytes` with `seed`.
+#[inline]
+pub fn xxh64_bytes(seed: u64, bytes: &[u8]) -> u64 {
+    xxhash_rust::xxh64::xxh64(bytes, seed)
+}
+
+/// XXH3 (64-bit) of `bytes` with `seed`.
+#[inline]
+pub fn xxh3_bytes(seed: u64, bytes: &[u8]) -> u64 {
+    xxhash_rust::xxh3::xxh3_64_with_seed(bytes, seed)
+}
+
+impl BlockedBloomFilter {
+    /// Hashes raw bytes with the filter's seed and algorithm: `xxh64_bytes` or `xxh3_bytes`.
+    #[inline]
+    pub(crate) fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        match self.hash {
+            HashAlgorithm::Xxh64 => xxh64_bytes(self.seed, bytes),
+            HashAlgorithm::Xxh3 => xxh3_bytes(self.seed, bytes),
+        }
+    }
+
+    /// Hashes raw bytes with `xxh64_bytes`, or `xxh3_bytes` for `HashAlgorithm::Xxh3`
+    /// filters, and the filter's seed, and inserts them.
+    #[inline]
+    pub fn insert_bytes(&mut self, bytes: &[u8]) {
+        self.insert_hash(self.hash_bytes(bytes));
+    }
+
+    /// Hashes raw bytes like `insert_bytes` and checks if they might be present.
+    #[inline]
+    pub fn may_match_bytes(&self, bytes: &[u8]) -> bool {
+        self.may_match_hash(self.hash_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden_vectors() {
+        // Reference values from the xxHash C implementation.
+        assert_eq!(xxh64_bytes(0, b""), 0xef46_db37_51d8_e999);
+        assert_eq!(xxh64_bytes(0, b"abc"), 0x44bc_2cf5_ad77_0999);
+        assert_eq!(xxh3_bytes(0, b""), 0x2d06_8005_38d3_94c2);
+        assert_eq!(xxh3_bytes(0, b"abc"), 0x78af_5f94_892f_3950);
+
+        let mut bf = BlockedBloomFilter::new_with_seed(100, 0.01, 5);
+        bf.insert_bytes(b"Leopold Bloom");
+        assert!(bf.may_match_hash(xxh64_bytes(5, b"Leopold Bloom")));
+
+        let mut bf = BlockedBloomFilter::new_with_hash(100, 0.01, 5, HashAlgorithm::Xxh3);
+        bf.insert_bytes(b"Leopold Bloom");
+        assert!(bf.may_match_hash(xxh3_bytes(5, b"Leopold Bloom")));
+        assert!(bf.may_match_bytes(b"Leopold Bloom"));
+    }
+}
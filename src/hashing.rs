@@ -0,0 +1,110 @@
+use std::hash::{Hash, Hasher};
+
+use crate::BlockedBloomFilter;
+
+/// Expands a 32-bit hash to the 64 bits the filter consumes.
+///
+/// The upper half of a filter hash picks the block and the lower half the bits, so a 32-bit
+/// hash cannot be zero-extended. This is the `fmix64` finalizer from MurmurHash3, a fixed
+/// bijection: the same 32-bit hash always maps to the same bits, in every version.
+#[inline]
+pub fn widen_hash32(h: u32) -> u64 {
+    let mut h = h as u64;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// Folds a 128-bit hash to the 64 bits the filter consumes by XOR-ing its halves.
+#[inline]
+pub fn fold_hash128(h: u128) -> u64 {
+    (h as u64) ^ ((h >> 64) as u64)
+}
+
+impl BlockedBloomFilter {
+    /// Inserts a 32-bit hash, widened with `widen_hash32`.
+    #[inline]
+    pub fn insert_hash32(&mut self, h: u32) {
+        self.insert_hash(widen_hash32(h));
+    }
+
+    /// Checks if the filter might contain a 32-bit hash.
+    #[inline]
+    pub fn may_match_hash32(&self, h: u32) -> bool {
+        self.may_match_hash(widen_hash32(h))
+    }
+
+    /// Inserts a 128-bit hash, folded with `fold_hash128`.
+    #[inline]
+    pub fn insert_hash128(&mut self, h: u128) {
+        self.insert_hash(fold_hash128(h));
+    }
+
+    /// Checks if the filter might contain a 128-bit hash.
+    #[inline]
+    pub fn may_match_hash128(&self, h: u128) -> bool {
+        self.may_match_hash(fold_hash128(h))
+    }
+
+    /// Hashes the key with a caller-supplied hasher instead of the filter's, and inserts it.
+    ///
+    /// Useful for keyed hashers, e.g. one per tenant. Query with `may_match_key_with` and an
+    /// identically constructed hasher; the filter's seed is not used.
+    #[inline]
+    pub fn insert_key_with<T: Hash + ?Sized, H: Hasher>(&mut self, key: &T, mut hasher: H) {
+        key.hash(&mut hasher);
+        self.insert_hash(hasher.finish());
+    }
+
+    /// Hashes the key with a caller-supplied hasher and checks if it might be present.
+    #[inline]
+    pub fn may_match_key_with<T: Hash + ?Sized, H: Hasher>(&self, key: &T, mut hasher: H) -> bool {
+        key.hash(&mut hasher);
+        self.may_match_hash(hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_narrow_and_wide_hashes() {
+        // Pinned so a change to the widening scheme cannot go unnoticed.
+        assert_eq!(widen_hash32(0), 0);
+        assert_eq!(widen_hash32(1), 0xb456_bcfc_34c2_cb2c);
+        assert_eq!(fold_hash128(0x1234 << 64 | 0x1234), 0);
+
+        let mut bf = BlockedBloomFilter::new(10_000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert_hash32(i);
+            bf.insert_hash128((i as u128) << 100 | 0xdead_beef);
+        }
+        for i in 0..1000u32 {
+            assert!(bf.may_match_hash32(i));
+            assert!(bf.may_match_hash128((i as u128) << 100 | 0xdead_beef));
+        }
+        let false_positives = (1000..11_000u32)
+            .filter(|&i| bf.may_match_hash32(i))
+            .count();
+        assert!(false_positives < 200, "{false_positives}");
+    }
+
+    #[test]
+    fn test_key_with_custom_hasher() {
+        use xxhash_rust::xxh64::Xxh64;
+
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key_with("Leopold Bloom", Xxh64::new(1));
+        assert!(bf.may_match_key_with("Leopold Bloom", Xxh64::new(1)));
+        assert!(!bf.may_match_key_with("Leopold Bloom", Xxh64::new(2)));
+        assert!(!bf.may_match_key("Leopold Bloom"));
+
+        // With the filter's own seed the result matches `insert_key`.
+        let mut default = BlockedBloomFilter::new(1000, 0.01);
+        default.insert_key_with("Molly Bloom", Xxh64::new(0));
+        assert!(default.may_match_key("Molly Bloom"));
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{self, BufRead};
+
+use crate::BlockedBloomFilter;
+
+impl BlockedBloomFilter {
+    /// Inserts every line of `reader` as a key, returning the number of lines read.
+    ///
+    /// Lines are hashed like `&str` keys (without the trailing `\n` or `\r\n`), so they can be
+    /// queried with `may_match_key`. A single line buffer is reused for the whole stream.
+    pub fn ingest_lines<R: BufRead>(&mut self, mut reader: R) -> io::Result<usize> {
+        let mut line = Vec::new();
+        let mut count = 0;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(count);
+            }
+            let h = self.hash_str_bytes(trim_line_ending(&line));
+            self.insert_hash(h);
+            count += 1;
+        }
+    }
+
+    /// Inserts every line of a gzip-compressed stream, see `ingest_lines`.
+    ///
+    /// Concatenated gzip members are decoded as a single stream.
+    #[cfg(feature = "gzip")]
+    pub fn ingest_lines_gz<R: io::Read>(&mut self, reader: R) -> io::Result<usize> {
+        let decoder = flate2::read::MultiGzDecoder::new(reader);
+        self.ingest_lines(io::BufReader::new(decoder))
+    }
+
+    /// Inserts one column of a CSV stream as keys, returning the number of rows read.
+    ///
+    /// The first row is treated as the header and `column` is looked up by name. Fields are
+    /// hashed like `&str` keys and a single record buffer is reused for the whole stream.
+    #[cfg(feature = "csv")]
+    pub fn ingest_csv_column<R: io::Read>(
+        &mut self,
+        reader: R,
+        column: &str,
+    ) -> csv::Result<usize> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let index = reader
+            .byte_headers()?
+            .iter()
+            .position(|name| name == column.as_bytes())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CSV column `{column}` not found"),
+                )
+            })?;
+
+        let mut record = csv::ByteRecord::new();
+        let mut count = 0;
+        while reader.read_byte_record(&mut record)? {
+            if let Some(field) = record.get(index) {
+                let h = self.hash_str_bytes(field);
+                self.insert_hash(h);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashAlgorithm;
+
+    #[test]
+    fn test_ingest_lines() {
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+        let input = "http://a.example\nhttp://b.example\r\n\nhttp://c.example";
+
+        assert_eq!(bf.ingest_lines(input.as_bytes()).unwrap(), 4);
+        assert!(bf.may_match_key("http://a.example"));
+        assert!(bf.may_match_key("http://b.example"));
+        assert!(bf.may_match_key("http://c.example"));
+        assert!(bf.may_match_key(""));
+        assert!(!bf.may_match_key("http://d.example"));
+    }
+
+    #[test]
+    fn test_ingest_lines_xxh3() {
+        let mut bf = BlockedBloomFilter::new_with_hash(100, 0.01, 7, HashAlgorithm::Xxh3);
+        assert_eq!(
+            bf.ingest_lines("Leopold Bloom\nMolly Bloom".as_bytes())
+                .unwrap(),
+            2
+        );
+        assert!(bf.may_match_key("Leopold Bloom"));
+        assert!(bf.may_match_key("Molly Bloom"));
+        assert!(!bf.may_match_key("Stephen Dedalus"));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_ingest_lines_gz() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Leopold Bloom\nMolly Bloom\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+        assert_eq!(bf.ingest_lines_gz(compressed.as_slice()).unwrap(), 2);
+        assert!(bf.may_match_key("Molly Bloom"));
+        assert!(!bf.may_match_key("Stephen Dedalus"));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_ingest_csv_column() {
+        let input = "id,url\n1,http://a.example\n2,\"http://b.example/?q=x,y\"\n";
+
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+        assert_eq!(bf.ingest_csv_column(input.as_bytes(), "url").unwrap(), 2);
+        assert!(bf.may_match_key("http://a.example"));
+        assert!(bf.may_match_key("http://b.example/?q=x,y"));
+        assert!(!bf.may_match_key("1"));
+
+        assert!(bf.ingest_csv_column(input.as_bytes(), "missing").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::BlockedBloomFilter;
+
+enum Command {
+    Insert(u64),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Feeds inserts through a bounded queue into a writer task that owns the filter's write lock.
+///
+/// The writer drains up to `max_batch` queued hashes at a time, sorts them by block and
+/// inserts them under one lock acquisition, so bursty producers neither contend on the filter
+/// nor thrash its cache lines. A full queue pushes back: `insert` waits for room and
+/// `try_insert` refuses the hash. Inserts become visible to `may_match_*` once the writer has
+/// applied them; `flush` waits for that. Dropping the inserter lets the writer apply what is
+/// queued and exit.
+#[derive(Debug)]
+pub struct AsyncInserter {
+    queue: mpsc::Sender<Command>,
+    filter: Arc<RwLock<BlockedBloomFilter>>,
+    task: JoinHandle<()>,
+}
+
+impl AsyncInserter {
+    /// Starts a writer task for `filter` on the current Tokio runtime, with room for
+    /// `capacity` queued hashes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `max_batch` is zero, or when called outside of a Tokio runtime.
+    pub fn start(filter: BlockedBloomFilter, capacity: usize, max_batch: usize) -> Self {
+        assert!(max_batch > 0, "batches must hold at least one hash");
+        let (queue, mut commands) = mpsc::channel(capacity);
+        let filter = Arc::new(RwLock::new(filter));
+        let writer = Arc::clone(&filter);
+        let task = tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(max_batch);
+            let mut flushes = Vec::new();
+            while let Some(command) = commands.recv().await {
+                let mut next = Some(command);
+                while let Some(command) = next.take() {
+                    match command {
+                        Command::Insert(h) => batch.push(h),
+                        Command::Flush(done) => flushes.push(done),
+                    }
+                    if batch.len() < max_batch {
+                        next = commands.try_recv().ok();
+                    }
+                }
+                // The upper half picks the block, so this visits blocks in order.
+                batch.sort_unstable_by_key(|&h| h >> 32);
+                {
+                    let mut filter = writer.write().unwrap();
+                    for &h in &batch {
+                        filter.insert_hash(h);
+                    }
+                }
+                batch.clear();
+                for done in flushes.drain(..) {
+                    let _ = done.send(());
+                }
+            }
+        });
+        Self {
+            queue,
+            filter,
+            task,
+        }
+    }
+
+    /// Queues a hash, waiting while the queue is full.
+    pub async fn insert(&self, h: u64) {
+        self.queue
+            .send(Command::Insert(h))
+            .await
+            .expect("writer task stopped");
+    }
+
+    /// Queues a hash if there is room, returning false if the queue is full.
+    pub fn try_insert(&self, h: u64) -> bool {
+        match self.queue.try_send(Command::Insert(h)) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => false,
+            Err(mpsc::error::TrySendError::Closed(_)) => panic!("writer task stopped"),
+        }
+    }
+
+    /// Hashes the key and queues it like `insert`.
+    pub async fn insert_key<T: Hash + ?Sized>(&self, key: &T) {
+        let h = self.filter.read().unwrap().hash_key(key);
+        self.insert(h).await;
+    }
+
+    /// Hashes the key and queues it like `try_insert`.
+    pub fn try_insert_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        let h = self.filter.read().unwrap().hash_key(key);
+        self.try_insert(h)
+    }
+
+    /// Waits until every hash queued before this call has been inserted.
+    pub async fn flush(&self) {
+        let (done, applied) = oneshot::channel();
+        self.queue
+            .send(Command::Flush(done))
+            .await
+            .expect("writer task stopped");
+        applied.await.expect("writer task stopped");
+    }
+
+    /// Checks if the filter might contain the hash, among the inserts applied so far.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.filter.read().unwrap().may_match_hash(h)
+    }
+
+    /// Hashes the key and checks it like `may_match_hash`.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.filter.read().unwrap().may_match_key(key)
+    }
+
+    /// Applies every queued hash, stops the writer and returns the filter.
+    pub async fn finish(self) -> BlockedBloomFilter {
+        let Self {
+            queue,
+            filter,
+            task,
+        } = self;
+        // Closing the queue lets the writer drain it and exit.
+        drop(queue);
+        task.await.expect("writer task panicked");
+        Arc::try_unwrap(filter)
+            .expect("writer task exited")
+            .into_inner()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_inserter_batches_and_pushes_back() {
+        let inserter = AsyncInserter::start(BlockedBloomFilter::new(10_000, 0.01), 4, 64);
+        for i in 0..1000u64 {
+            inserter.insert_key(&i).await;
+        }
+        inserter.flush().await;
+        assert!((0..1000u64).all(|i| inserter.may_match_key(&i)));
+
+        // The writer cannot run on this single-threaded runtime until we yield.
+        let accepted = (1000..1010u64)
+            .filter(|i| inserter.try_insert_key(i))
+            .count();
+        assert_eq!(accepted, 4);
+
+        let filter = inserter.finish().await;
+        assert!(filter.may_match_key(&1003u64));
+        assert!(filter.may_match_key(&999u64));
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::{
+    BlockedBloomFilter, CacheLineBlock, DecodeError, ErrorKind, FilterHeader, FilterParams,
+};
+
+/// Number of blocks read by `inspect` to estimate the fill.
+pub const INSPECT_SAMPLE_BLOCKS: u32 = 64;
+
+/// Whether a filter file's payload is covered by a checksum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumStatus {
+    /// The layout version stores no checksum, so the payload cannot be verified.
+    Absent,
+}
+
+/// What `inspect` learned about a filter file without loading its payload.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FilterFileInfo {
+    /// Layout version from the header.
+    pub version: u32,
+    /// Geometry, seed and hash algorithm of the stored filter.
+    pub params: FilterParams,
+    /// Size of the file in bytes.
+    pub file_len: u64,
+    /// Whether the file length matches the length implied by the header.
+    pub length_ok: bool,
+    /// Checksum status of the payload.
+    pub checksum: ChecksumStatus,
+    /// Number of blocks read to estimate the fill.
+    pub sampled_blocks: u32,
+    /// Fraction of bits set in the sampled blocks.
+    pub estimated_fill: f64,
+}
+
+/// Errors produced when inspecting a filter file.
+#[derive(Debug)]
+pub enum InspectError {
+    /// Reading the file failed.
+    Io(io::Error),
+    /// The file does not start with a valid header.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for InspectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read filter file: {e}"),
+            Self::Decode(e) => write!(f, "invalid filter file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for InspectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Decode(e) => Some(e),
+        }
+    }
+}
+
+impl InspectError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Io(_) => ErrorKind::Io,
+            Self::Decode(e) => e.kind(),
+        }
+    }
+}
+
+impl From<io::Error> for InspectError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<DecodeError> for InspectError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+/// Reads the header of a file written with `to_bytes` and samples its fill.
+///
+/// Only the header and up to `INSPECT_SAMPLE_BLOCKS` evenly spaced blocks are read, so large
+/// artifacts can be validated cheaply. Blocks past the end of a truncated file are skipped.
+pub fn inspect(path: impl AsRef<Path>) -> Result<FilterFileInfo, InspectError> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut header_bytes = [0u8; FilterHeader::LEN];
+    let read = read_up_to(&mut file, &mut header_bytes)?;
+    let header = FilterHeader::parse(&header_bytes[..read])?;
+    let block_bytes = size_of::<CacheLineBlock>() as u64;
+    let available = (file_len - FilterHeader::LEN as u64) / block_bytes;
+    let readable = available.min(header.num_blocks as u64);
+
+    let samples = readable.min(INSPECT_SAMPLE_BLOCKS as u64);
+    let mut ones = 0u64;
+    let mut block = [0u8; size_of::<CacheLineBlock>()];
+    for i in 0..samples {
+        let idx = i * readable / samples;
+        file.seek(SeekFrom::Start(
+            FilterHeader::LEN as u64 + idx * block_bytes,
+        ))?;
+        file.read_exact(&mut block)?;
+        ones += block.iter().map(|b| b.count_ones() as u64).sum::<u64>();
+    }
+
+    Ok(FilterFileInfo {
+        version: header.version,
+        params: FilterParams {
+            seed: header.seed,
+            num_blocks: header.num_blocks,
+            probes: BlockedBloomFilter::PROBES,
+            block_bytes: block_bytes as u32,
+            hash: header.hash,
+            mapping: header.mapping,
+        },
+        file_len,
+        length_ok: file_len == (FilterHeader::LEN + header.payload_len()) as u64,
+        checksum: ChecksumStatus::Absent,
+        sampled_blocks: samples as u32,
+        estimated_fill: if samples == 0 {
+            0.0
+        } else {
+            ones as f64 / (samples * CacheLineBlock::BITS as u64) as f64
+        },
+    })
+}
+
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_reads_header_and_samples_fill() {
+        let mut bf = BlockedBloomFilter::new_with_seed(10_000, 0.01, 21);
+        for i in 0..10_000u64 {
+            bf.insert_key(&i);
+        }
+        let path =
+            std::env::temp_dir().join(format!("bloomsday-inspect-{}.bin", std::process::id()));
+        let mut bytes = bf.to_bytes();
+        std::fs::write(&path, &bytes).unwrap();
+
+        let info = inspect(&path).unwrap();
+        assert_eq!(info.params, bf.params());
+        assert!(info.length_ok);
+        assert_eq!(info.sampled_blocks, INSPECT_SAMPLE_BLOCKS);
+        let fill = bf.export_diagnostics().fill_ratio;
+        assert!((info.estimated_fill - fill).abs() < 0.05);
+
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(!inspect(&path).unwrap().length_ok);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{BlockedBloomFilter, BloomProfile};
+
+/// Builds a filter over the build side of a hash join, for semi-join pruning of the probe side.
+///
+/// The filter is sized for `build_side_hashes.len()` at the default profile's false positive
+/// rate. Hashes are used as is, so compute them with the same function the join uses.
+pub fn build_join_filter(build_side_hashes: &[u64]) -> BlockedBloomFilter {
+    let fpr = BloomProfile::default().target_fpr();
+    let mut filter = BlockedBloomFilter::new(build_side_hashes.len(), fpr);
+    for &h in build_side_hashes {
+        filter.insert_hash(h);
+    }
+    filter
+}
+
+/// Probes a batch of hashes and writes the positions of possible matches to `out_selection`.
+///
+/// `out_selection` is overwritten with ascending row indices into `probe_hashes`, ready to be
+/// used as a selection vector. Returns the number of selected rows.
+pub fn probe_filter(
+    filter: &BlockedBloomFilter,
+    probe_hashes: &[u64],
+    out_selection: &mut Vec<u32>,
+) -> usize {
+    filter.select_hashes(probe_hashes, out_selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_selects_build_side_rows() {
+        let build: Vec<u64> = (0..1000u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect();
+        let filter = build_join_filter(&build);
+
+        let probe: Vec<u64> = (0..2000u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect();
+        let mut selection = vec![u32::MAX; 3];
+        let selected = probe_filter(&filter, &probe, &mut selection);
+
+        assert_eq!(selected, selection.len());
+        assert_eq!(selection[..1000], (0..1000).collect::<Vec<u32>>()[..]);
+        assert!(selected < 1000 + 100, "{selected}");
+        assert!(selection.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -1,29 +1,230 @@
-use std::f64::consts::LN_2;
 use std::hash::{Hash, Hasher};
 use xxhash_rust::xxh64::Xxh64;
 
+mod aligned;
+mod archived;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod audit;
+mod binary;
+pub mod bloomfilter_compat;
+mod cached;
+mod classic;
+#[cfg(feature = "client")]
+mod client;
+mod clock;
+mod concat;
+mod core_sharded;
+mod counting;
+mod crdt;
+mod cuckoo;
+mod dedup_stats;
+mod diagnostics;
+mod diff;
+mod ensemble;
+mod epoch;
+mod error;
+mod expiring;
+mod fuse;
+#[cfg(feature = "fuzz-support")]
+pub mod fuzz_support;
+mod generational;
+mod growable;
+pub mod hash_compat;
+mod hashing;
+mod ingest;
+#[cfg(feature = "tokio")]
+mod inserter;
+mod inspect;
+mod join;
+mod masks;
+pub mod math;
+mod membership;
+mod merge;
+mod migrate;
+mod observer;
+mod parallel;
+mod params;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod pool;
+mod prefault;
+mod privacy;
+mod profile;
+#[cfg(feature = "proto")]
+pub mod proto;
+mod raw;
+mod recording;
+mod registry;
+#[cfg(feature = "object_store")]
+mod remote;
+mod ribbon;
+#[cfg(feature = "axum")]
+mod routes;
+#[cfg(feature = "tokio")]
+mod scheduler;
+mod scrub;
+mod segmented;
+#[cfg(feature = "server")]
+pub mod server;
+mod shadow;
+mod sharded;
+mod shared;
+mod sizing;
+mod sorted;
+mod spool;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod static_bloom;
+mod static_filter;
+mod streaming;
+#[cfg(feature = "object_store")]
+mod subscriber;
+mod tiered;
+#[cfg(feature = "tower")]
+mod tower;
+mod tuning;
+mod verify;
+mod view;
+mod warmup;
+mod workload;
+mod xor;
+mod zone;
+
+pub use archived::ArchivedFilter;
+pub use audit::{FprAudit, QueryAudit, QuerySample, QuerySampler};
+pub use binary::{CompatPolicy, DecodeError, FilterHeader};
+pub use cached::CachedFilter;
+pub use classic::ClassicBloomFilter;
+#[cfg(feature = "client")]
+pub use client::{
+    DEFAULT_BATCH_SIZE, DEFAULT_NEGATIVE_CACHE_CAPACITY, DEFAULT_NEGATIVE_CACHE_TTL, RemoteFilter,
+};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use core_sharded::{CoreShardedBuilder, ShardWriter};
+pub use counting::{CounterWidth, CountingBloomFilter};
+pub use cuckoo::{BucketLayout, CuckooFilter};
+pub use dedup_stats::{DedupStats, DedupStatsFilter};
+pub use diagnostics::{FilterDiagnostics, HISTOGRAM_BUCKETS};
+pub use diff::DiffFilter;
+pub use ensemble::{EnsembleFilter, ProbeOrder};
+pub use epoch::EpochExpiringFilter;
+pub use error::{Error, ErrorKind};
+pub use expiring::{AdaptiveSizing, ExpiringBloomFilter, SegmentInfo};
+pub use fuse::BinaryFuseFilter;
+pub use generational::GenerationalBloomFilter;
+pub use growable::GrowableFileFilter;
+pub use hashing::{fold_hash128, widen_hash32};
+#[cfg(feature = "tokio")]
+pub use inserter::AsyncInserter;
+pub use inspect::{ChecksumStatus, FilterFileInfo, INSPECT_SAMPLE_BLOCKS, InspectError, inspect};
+pub use join::{build_join_filter, probe_filter};
+pub use masks::MaskCache;
+pub use membership::ApproxMembership;
+pub use merge::MergeError;
+pub use migrate::MigratableBuilder;
+pub use observer::{ObservedFilter, ProbeObserver, ProbeSample};
+pub use parallel::{LocalFilter, ParallelBuilder};
+pub use params::{BlockMapping, FilterParams, HashAlgorithm, ParamsError};
+pub use pool::{FilterPool, PooledFilter};
+pub use profile::BloomProfile;
+pub use raw::Endianness;
+pub use recording::RecordingBuilder;
+pub use registry::{
+    FilterId, FilterRegistry, QuotaError, QuotaPolicy, TenantId, TenantQuota, TenantUsage,
+};
+#[cfg(feature = "object_store")]
+pub use remote::LoadError;
+pub use ribbon::RibbonFilter;
+#[cfg(feature = "axum")]
+pub use routes::{PROBE_COUNT_HEADER, probe_router};
+#[cfg(feature = "tokio")]
+pub use scheduler::{ExpiringHandle, RotationScheduler};
+pub use scrub::{BlockChecksums, DEFAULT_BLOCKS_PER_CHECKSUM};
+pub use segmented::{SegmentId, SegmentedLogFilter};
+pub use shadow::{ShadowStats, ShadowedFilter};
+pub use sharded::PrefixShardedFilter;
+pub use shared::SharedBloomFilter;
+pub use sizing::{KeyStats, SizingProbe};
+pub use sorted::SortedRunBuilder;
+pub use spool::SpooledBuilder;
+#[cfg(feature = "sqlite")]
+pub use sqlite::register_sqlite_functions;
+pub use static_bloom::StaticBloom;
+pub use static_filter::{Priority, StaticFilter, StaticFilterBuilder};
+pub use streaming::DedupProcessor;
+#[cfg(feature = "object_store")]
+pub use subscriber::{FilterSubscriber, SubscriberHooks};
+pub use tiered::TieredFilter;
+#[cfg(feature = "tower")]
+pub use tower::{DedupAction, DedupFuture, DedupLayer, DedupService, PossibleDuplicate};
+pub use tuning::{PROBE_COUNTS, ProbeTuner, ProbeTuningReport, ReducedProbeFilter};
+pub use verify::{VerificationError, verify_implementations};
+pub use view::FilterView;
+pub use workload::{
+    ConfiguredFilter, DatasetSpec, FilterConfig, PackingPlan, WorkloadError, WorkloadSpec,
+    choose_filter, pack_filters,
+};
+pub use xor::{XorFilter, XorFilter8, XorFilter16, XorFingerprint};
+pub use zone::{
+    MicroBloom, build_zone_filters, select_zones, zone_filters_from_bytes, zone_filters_to_bytes,
+};
+
+use params::KeyHasher;
+
+/// A buffer of key material, wiped when dropped with the `zeroize` feature.
+///
+/// Only the buffer's final allocation is wiped; copies left behind when a `Vec` grows are not.
+#[cfg(feature = "zeroize")]
+pub(crate) type Wiped<T> = zeroize::Zeroizing<T>;
+#[cfg(not(feature = "zeroize"))]
+pub(crate) type Wiped<T> = T;
+
+/// How many keys or probes batch operations hash ahead, prefetching their blocks.
+pub(crate) const PREFETCH_DISTANCE: usize = 8;
+
 /// A cache-line blocked Bloom filter.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug)]
 pub struct BlockedBloomFilter {
-    blocks: Vec<CacheLineBlock>,
+    blocks: Blocks,
     num_blocks: u32,
     seed: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    mapping: BlockMapping,
+    #[cfg_attr(feature = "serde", serde(default))]
+    hash: HashAlgorithm,
 }
 
+/// Block storage, shared between clones until the first mutation with the `cow` feature.
+#[cfg(not(feature = "cow"))]
+type Blocks = aligned::AlignedBlocks;
+#[cfg(feature = "cow")]
+type Blocks = std::sync::Arc<aligned::AlignedBlocks>;
+
 #[repr(C, align(32))]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct CacheLineBlock {
     words: [u32; 8],
 }
 
+// `as_raw_words` promises blocks are exactly their eight words, with no padding between them.
+const _: () = assert!(size_of::<CacheLineBlock>() == 32);
+
+impl CacheLineBlock {
+    const BITS: usize = 256;
+}
+
 impl BlockedBloomFilter {
     const SALT: [u32; 8] = [
         0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947,
         0x5c6bfb31,
     ];
 
+    /// Number of bits set per inserted hash, one in each word of the block.
+    pub(crate) const PROBES: u32 = Self::SALT.len() as u32;
+
     /// Creates a new filter with the given entries and false positive rate.
     pub fn new(entries: usize, fpr: f64) -> Self {
         Self::new_with_seed(entries, fpr, 0)
@@ -31,88 +232,463 @@ impl BlockedBloomFilter {
 
     /// Creates a new filter with a custom seed.
     pub fn new_with_seed(entries: usize, fpr: f64, seed: u64) -> Self {
-        let bits_per_key = Self::bloom_bits_per_key(fpr);
-        let mut num_blocks = ((entries * bits_per_key + 255) / 256) as u32;
-        if num_blocks == 0 {
-            num_blocks = 1;
-        }
+        let num_blocks = math::num_blocks_for(entries, fpr);
+        Self::from_blocks(vec![CacheLineBlock::default(); num_blocks as usize], seed)
+    }
+
+    /// Creates a new filter whose hashes pick their block with `mapping`.
+    ///
+    /// Use `BlockMapping::Mixed` for externally produced hashes with weak upper bits; see
+    /// `block_skew` for detecting them.
+    pub fn new_with_mapping(entries: usize, fpr: f64, seed: u64, mapping: BlockMapping) -> Self {
+        Self::new_with_seed(entries, fpr, seed).with_mapping(mapping)
+    }
+
+    /// Returns how hashes pick their block.
+    pub fn block_mapping(&self) -> BlockMapping {
+        self.mapping
+    }
+
+    /// Creates a new filter whose `*_key` methods hash keys with `hash`.
+    pub fn new_with_hash(entries: usize, fpr: f64, seed: u64, hash: HashAlgorithm) -> Self {
+        Self::new_with_seed(entries, fpr, seed).with_hash(hash)
+    }
+
+    /// Returns the algorithm the `*_key` methods hash keys with.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
 
-        let blocks = vec![CacheLineBlock::default(); num_blocks as usize];
+    /// Alignment in bytes that every filter's first block is guaranteed to have, one cache
+    /// line.
+    pub const GUARANTEED_BLOCK_ALIGNMENT: usize = aligned::BLOCK_ARRAY_ALIGNMENT;
+
+    /// Returns the alignment of the first block in memory, capped at 64, which is always
+    /// `GUARANTEED_BLOCK_ALIGNMENT`.
+    ///
+    /// The allocator only promises the block type's 32 bytes, so when an allocation falls
+    /// halfway through a cache line the filter keeps one spare block in front of its blocks
+    /// and starts them on the next line. SIMD code can therefore rely on aligned 64-byte loads
+    /// over `as_raw_words`; checking this once is a cheap guard against future layouts.
+    pub fn block_alignment(&self) -> usize {
+        let addr = self.blocks.as_ptr() as usize;
+        1 << addr.trailing_zeros().min(6)
+    }
+
+    /// Returns each block's eight words, in block order and native byte order.
+    ///
+    /// The blocks are contiguous in memory, so the first array's address is the start of the
+    /// whole bit array, aligned to `block_alignment()`, for code that reads the bits directly.
+    pub fn as_raw_words(&self) -> impl ExactSizeIterator<Item = &[u32; 8]> {
+        self.blocks.iter().map(|block| &block.words)
+    }
+
+    /// Wraps existing blocks, e.g. ones decoded from another format, with `Direct` mapping.
+    fn from_blocks(blocks: Vec<CacheLineBlock>, seed: u64) -> Self {
+        let num_blocks = blocks.len() as u32;
+        let blocks = aligned::AlignedBlocks::new(blocks);
+        debug_assert!(
+            num_blocks == 0
+                || (blocks.as_ptr() as usize).is_multiple_of(Self::GUARANTEED_BLOCK_ALIGNMENT),
+            "block storage is misaligned"
+        );
         Self {
+            #[cfg(feature = "cow")]
+            blocks: std::sync::Arc::new(blocks),
+            #[cfg(not(feature = "cow"))]
             blocks,
             num_blocks,
             seed,
+            mapping: BlockMapping::Direct,
+            hash: HashAlgorithm::Xxh64,
         }
     }
 
+    /// Sets the block mapping of blocks built (or decoded) under it.
+    fn with_mapping(mut self, mapping: BlockMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Sets the algorithm the `*_key` methods hash keys with.
+    fn with_hash(mut self, hash: HashAlgorithm) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// Returns the blocks for writing, first unsharing them with the `cow` feature.
     #[inline(always)]
-    fn fast_map(&self, hash: u32) -> usize {
-        ((hash as u64 * self.num_blocks as u64) >> 32) as usize
+    fn blocks_mut(&mut self) -> &mut [CacheLineBlock] {
+        #[cfg(feature = "cow")]
+        return &mut *std::sync::Arc::make_mut(&mut self.blocks);
+        #[cfg(not(feature = "cow"))]
+        return &mut self.blocks;
+    }
+
+    #[inline(always)]
+    fn fast_map(&self, h: u64) -> usize {
+        self.mapping.block_index(h, self.num_blocks)
     }
 
     /// Inserts a hash into the filter.
     #[inline(always)]
     pub fn insert_hash(&mut self, h: u64) {
-        let block_idx = self.fast_map((h >> 32) as u32);
-        let block = &mut self.blocks[block_idx];
+        self.insert_in_block(self.fast_map(h), h as u32);
+    }
+
+    /// Inserts a hash and returns whether it was new, i.e. did not already match.
+    ///
+    /// A `false` answer may be a false positive rather than a real duplicate.
+    #[inline]
+    pub fn insert_hash_checked(&mut self, h: u64) -> bool {
+        let block_idx = self.fast_map(h);
+        let new = !self.check_block(block_idx, h as u32);
+        if new {
+            self.insert_in_block(block_idx, h as u32);
+        }
+        new
+    }
+
+    /// Checks if the filter might contain the hash.
+    #[inline(always)]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.check_block(self.fast_map(h), h as u32)
+    }
+
+    /// Inserts pre-split hash material: `block_selector` picks the block the way the upper half
+    /// of a hash does under `BlockMapping::Direct`, and `bit_selector` picks one bit per word
+    /// the way the lower half does.
+    ///
+    /// For systems that already split their hashes and must reproduce an existing layout (such
+    /// as Parquet's split block Bloom filters) exactly. The filter's block mapping is bypassed,
+    /// so on `Direct` filters this equals inserting `(block_selector << 32) | bit_selector`.
+    #[inline(always)]
+    pub fn insert_split(&mut self, block_selector: u32, bit_selector: u32) {
+        let block_idx =
+            BlockMapping::Direct.block_index((block_selector as u64) << 32, self.num_blocks);
+        self.insert_in_block(block_idx, bit_selector);
+    }
+
+    /// Checks pre-split hash material inserted with `insert_split`.
+    #[inline(always)]
+    pub fn may_match_split(&self, block_selector: u32, bit_selector: u32) -> bool {
+        let block_idx =
+            BlockMapping::Direct.block_index((block_selector as u64) << 32, self.num_blocks);
+        self.check_block(block_idx, bit_selector)
+    }
+
+    #[inline(always)]
+    fn insert_in_block(&mut self, block_idx: usize, bits: u32) {
+        #[cfg(not(feature = "safe-index"))]
+        let block = &mut self.blocks_mut()[block_idx];
+        #[cfg(feature = "safe-index")]
+        let Some(block) = self.blocks_mut().get_mut(block_idx) else {
+            debug_assert!(false, "block index {block_idx} out of range");
+            return;
+        };
 
         block
             .words
             .iter_mut()
             .zip(Self::SALT.iter())
             .for_each(|(w, &salt)| {
-                let idx = (h as u32).wrapping_mul(salt) >> 27;
+                let idx = bits.wrapping_mul(salt) >> 27;
                 *w |= 1 << idx;
             });
     }
 
-    /// Checks if the filter might contain the hash.
     #[inline(always)]
-    pub fn may_match_hash(&self, h: u64) -> bool {
-        let block_idx = self.fast_map((h >> 32) as u32);
+    fn check_block(&self, block_idx: usize, bits: u32) -> bool {
+        #[cfg(not(feature = "safe-index"))]
         let block = &self.blocks[block_idx];
+        // A missing block answers "maybe", so corruption never causes false negatives.
+        #[cfg(feature = "safe-index")]
+        let Some(block) = self.blocks.get(block_idx) else {
+            debug_assert!(false, "block index {block_idx} out of range");
+            return true;
+        };
 
         let check = block
             .words
             .iter()
             .zip(Self::SALT.iter())
             .fold(0u32, |acc, (&w, &salt)| {
-                let idx = (h as u32).wrapping_mul(salt) >> 27;
+                let idx = bits.wrapping_mul(salt) >> 27;
                 acc | ((1 << idx) & !w)
             });
 
         check == 0
     }
 
+    /// Loads the first word of the block a hash maps to, so a later probe finds it in cache.
+    ///
+    /// This is an ordinary demand load, not a prefetch instruction, which safe Rust cannot
+    /// issue: the load must complete before it retires, so a miss blocks retirement, unlike a
+    /// prefetch. Its result is unused, so an out-of-order CPU can still run the following
+    /// independent probes while it is outstanding, and misses overlap as far as the reorder
+    /// window allows.
+    #[inline(always)]
+    pub(crate) fn prefetch_hash(&self, h: u64) {
+        if let Some(block) = self.blocks.get(self.fast_map(h)) {
+            std::hint::black_box(block.words[0]);
+        }
+    }
+
+    /// Checks if any of several filters might contain the hash.
+    ///
+    /// Every filter is probed without short-circuiting, so the cache misses for their blocks
+    /// overlap instead of being paid one after another. Meant for tiered setups that always
+    /// query a handful of filters per key.
+    #[inline]
+    pub fn check_any(filters: &[&BlockedBloomFilter], h: u64) -> bool {
+        filters
+            .iter()
+            .fold(false, |acc, filter| acc | filter.may_match_hash(h))
+    }
+
+    /// Probes a batch of hashes and writes the positions of possible matches to `selection`.
+    ///
+    /// `selection` is overwritten with ascending indices into `hashes`, the compact selection
+    /// vector vectorized engines consume. Returns the number of selected rows.
+    pub fn select_hashes(&self, hashes: &[u64], selection: &mut Vec<u32>) -> usize {
+        selection.clear();
+        selection.resize(hashes.len(), 0);
+        // Write every index and only advance past matches, so the loop has no data-dependent
+        // branches to mispredict.
+        let mut selected = 0;
+        for (i, &h) in hashes.iter().enumerate() {
+            selection[selected] = i as u32;
+            selected += self.may_match_hash(h) as usize;
+        }
+        selection.truncate(selected);
+        selected
+    }
+
+    /// Probes a batch of hashes and adds the row ids of possible matches, `first_row` plus
+    /// their index in `hashes`, to `rows` in ascending order.
+    ///
+    /// Fills any `Extend<u32>` selection directly, such as a `RoaringBitmap` or `BTreeSet`,
+    /// with no intermediate `Vec<bool>`. `first_row` places a row group's ids within a larger
+    /// table. Returns the number of rows added.
+    pub fn extend_matching_rows(
+        &self,
+        hashes: &[u64],
+        first_row: u32,
+        rows: &mut impl Extend<u32>,
+    ) -> usize {
+        let mut selected = 0;
+        rows.extend(
+            (first_row..)
+                .zip(hashes)
+                .filter(|&(_, &h)| self.may_match_hash(h))
+                .map(|(row, _)| {
+                    selected += 1;
+                    row
+                }),
+        );
+        selected
+    }
+
+    /// Resets every bit, keeping the geometry and seed.
+    pub fn clear(&mut self) {
+        self.blocks_mut().fill(CacheLineBlock::default());
+    }
+
     /// Hashes the key and inserts it.
     #[inline]
     pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
-        let mut hasher = Xxh64::new(self.seed);
-        key.hash(&mut hasher);
-        self.insert_hash(hasher.finish());
+        self.insert_hash(self.hash_key(key));
     }
 
     /// Hashes the key and checks if it might be present.
     #[inline]
     pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
-        let mut hasher = Xxh64::new(self.seed);
-        key.hash(&mut hasher);
-        self.may_match_hash(hasher.finish())
+        self.may_match_hash(self.hash_key(key))
     }
 
-    fn bloom_bits_per_key(fpr: f64) -> usize {
-        if fpr <= 0.0 || fpr >= 1.0 {
-            return 10;
+    /// Hashes and inserts a batch of keys.
+    ///
+    /// One hasher is reset between keys instead of built per key, and keys are hashed
+    /// `PREFETCH_DISTANCE` at a time with their blocks prefetched before any is written, so the
+    /// cache misses overlap with hashing the rest of the batch.
+    pub fn insert_keys<I>(&mut self, keys: I)
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        let mut hasher = KeyHasher::new(self.hash, self.seed);
+        let mut keys = keys.into_iter();
+        let mut batch = [0u64; PREFETCH_DISTANCE];
+        loop {
+            let len = self.hash_batch(&mut hasher, &mut keys, &mut batch);
+            batch[..len].iter().for_each(|&h| self.insert_hash(h));
+            if len < PREFETCH_DISTANCE {
+                break;
+            }
         }
-        ((-1.0 * fpr.ln()) / (LN_2 * LN_2)).ceil() as usize
     }
+
+    /// Hashes a batch of keys and checks each, returning one answer per key in order.
+    ///
+    /// Hashes like `insert_keys`, prefetching each group's blocks before probing them.
+    pub fn may_match_keys<I>(&self, keys: I) -> Vec<bool>
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        let mut hasher = KeyHasher::new(self.hash, self.seed);
+        let mut keys = keys.into_iter();
+        let mut batch = [0u64; PREFETCH_DISTANCE];
+        let mut results = Vec::with_capacity(keys.size_hint().0);
+        loop {
+            let len = self.hash_batch(&mut hasher, &mut keys, &mut batch);
+            results.extend(batch[..len].iter().map(|&h| self.may_match_hash(h)));
+            if len < PREFETCH_DISTANCE {
+                break;
+            }
+        }
+        results
+    }
+
+    /// Fills `batch` with the hashes of the next keys, prefetching their blocks, and returns
+    /// how many it hashed.
+    #[inline]
+    fn hash_batch<I>(
+        &self,
+        hasher: &mut KeyHasher,
+        keys: &mut I,
+        batch: &mut [u64; PREFETCH_DISTANCE],
+    ) -> usize
+    where
+        I: Iterator,
+        I::Item: Hash,
+    {
+        let mut len = 0;
+        for (slot, key) in batch.iter_mut().zip(keys) {
+            *slot = hasher.hash_key(&key);
+            self.prefetch_hash(*slot);
+            len += 1;
+        }
+        len
+    }
+
+    /// Inserts `n` independent probe sets for the key, the `i`-th hashed under a seed derived
+    /// from the filter's seed and `i`.
+    ///
+    /// The first set is the one `insert_key` uses, so `may_match_key` still finds the key. The
+    /// extra sets replicate a heavily weighted key into other blocks, so it survives when the
+    /// filter is later split or downsized and only part of its blocks are kept; check them with
+    /// `may_match_key_n`. Each set costs as much filter capacity as an ordinary insert.
+    pub fn insert_key_n<T: Hash + ?Sized>(&mut self, key: &T, n: u32) {
+        for i in 0..n {
+            self.insert_hash(self.hash.hash_key(Self::replica_seed(self.seed, i), key));
+        }
+    }
+
+    /// Checks if any of the key's first `n` probe sets, as inserted by `insert_key_n`, might be
+    /// present.
+    ///
+    /// Each additional set adds its own chance of a false positive.
+    pub fn may_match_key_n<T: Hash + ?Sized>(&self, key: &T, n: u32) -> bool {
+        (0..n)
+            .any(|i| self.may_match_hash(self.hash.hash_key(Self::replica_seed(self.seed, i), key)))
+    }
+
+    /// Seed for the `i`-th probe set of `insert_key_n`; set 0 uses the filter's own seed.
+    fn replica_seed(seed: u64, i: u32) -> u64 {
+        seed ^ (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+
+    /// Hashes a key with the filter's seed and hash algorithm.
+    #[inline]
+    pub(crate) fn hash_key<T: Hash + ?Sized>(&self, key: &T) -> u64 {
+        self.hash.hash_key(self.seed, key)
+    }
+
+    /// Hashes raw bytes exactly as `insert_key` would hash the equivalent `str`.
+    #[inline]
+    pub(crate) fn hash_str_bytes(&self, bytes: &[u8]) -> u64 {
+        self.hash.hash_str_bytes(self.seed, bytes)
+    }
+}
+
+/// Hashes a key the way every filter's `*_key` methods do.
+#[inline]
+pub(crate) fn key_hash<T: Hash + ?Sized>(seed: u64, key: &T) -> u64 {
+    let mut hasher = Xxh64::new(seed);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the bit indices, counted across the whole block array, that a hash sets in the
+/// blocked layout with direct mapping. Variants that keep more than a bit per position use it
+/// to index their own arrays.
+#[inline]
+pub(crate) fn probe_positions(num_blocks: u32, h: u64) -> [usize; 8] {
+    let base = BlockMapping::Direct.block_index(h, num_blocks) * CacheLineBlock::BITS;
+    std::array::from_fn(|word| {
+        base + word * 32 + ((h as u32).wrapping_mul(BlockedBloomFilter::SALT[word]) >> 27) as usize
+    })
 }
 
+/// Wipes the blocks and seed so membership of confidential keys does not outlive the filter.
+#[cfg(feature = "zeroize")]
+impl Drop for BlockedBloomFilter {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+        // With shared storage only the last owner wipes the blocks.
+        #[cfg(feature = "cow")]
+        if let Some(blocks) = std::sync::Arc::get_mut(&mut self.blocks) {
+            blocks.zeroize();
+        }
+        #[cfg(not(feature = "cow"))]
+        self.blocks.zeroize();
+        self.seed.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for BlockedBloomFilter {}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::DefaultIsZeroes for CacheLineBlock {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::Rng;
 
+    #[test]
+    fn test_raw_words_and_alignment() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_hash(0x0000_0003_dead_beef);
+        let blocks: Vec<&[u32; 8]> = bf.as_raw_words().collect();
+        assert_eq!(blocks.len(), bf.num_blocks as usize);
+        assert_eq!(*blocks[0], bf.blocks[0].words);
+        assert_eq!(bf.block_alignment(), 64);
+        assert_eq!(blocks[0].as_ptr() as usize % bf.block_alignment(), 0);
+        assert_eq!(
+            blocks[1].as_ptr() as usize - blocks[0].as_ptr() as usize,
+            32
+        );
+    }
+
+    #[test]
+    fn test_batched_keys_match_single_keys() {
+        for hash in [HashAlgorithm::Xxh64, HashAlgorithm::Xxh3] {
+            let mut batched = BlockedBloomFilter::new_with_hash(1000, 0.01, 3, hash);
+            let mut single = batched.clone();
+            batched.insert_keys(0..1001u64);
+            (0..1001u64).for_each(|i| single.insert_key(&i));
+            assert_eq!(batched.to_bytes(), single.to_bytes());
+
+            let answers = batched.may_match_keys(["a", "b"].iter().chain(&["c"; 20]));
+            assert_eq!(answers.len(), 22);
+            assert_eq!(answers[2], single.may_match_key(&"c"));
+            assert!(batched.may_match_keys(990..1001u64).into_iter().all(|m| m));
+        }
+    }
+
     #[test]
     fn test_key_api() {
         let mut bf = BlockedBloomFilter::new(1000, 0.01);
@@ -156,6 +732,36 @@ mod tests {
         assert!(actual_fpr < fpr * 2.5);
     }
 
+    #[test]
+    fn test_split_api_matches_hash_layout() {
+        let mut split = BlockedBloomFilter::new(1000, 0.01);
+        let mut hashed = BlockedBloomFilter::new(1000, 0.01);
+        split.insert_split(0xdead_beef, 7);
+        hashed.insert_hash(0xdead_beef_0000_0007);
+        assert_eq!(split.blocks[..], hashed.blocks[..]);
+        assert!(split.may_match_split(0xdead_beef, 7));
+        assert!(!split.may_match_split(0xdead_beef, 8));
+
+        // Mixed filters still place split material like a direct one.
+        let mut mixed = BlockedBloomFilter::new_with_mapping(1000, 0.01, 0, BlockMapping::Mixed);
+        mixed.insert_split(0xdead_beef, 7);
+        assert_eq!(mixed.blocks[..], hashed.blocks[..]);
+    }
+
+    #[test]
+    fn test_replicated_keys_survive_losing_blocks() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key_n("Leopold Bloom", 4);
+        assert!(bf.may_match_key("Leopold Bloom"));
+        assert!(!bf.may_match_key_n("Molly Bloom", 4));
+
+        // Losing the block of the primary set leaves the replicas.
+        let primary = bf.fast_map(bf.hash_key("Leopold Bloom"));
+        bf.blocks_mut()[primary] = CacheLineBlock::default();
+        assert!(!bf.may_match_key("Leopold Bloom"));
+        assert!(bf.may_match_key_n("Leopold Bloom", 4));
+    }
+
     #[test]
     fn test_zero_entries_init() {
         let mut bf = BlockedBloomFilter::new(0, 0.01);
@@ -164,6 +770,20 @@ mod tests {
         assert!(bf.may_match_hash(123));
     }
 
+    #[test]
+    fn test_check_any() {
+        let mut memtable = BlockedBloomFilter::new(1000, 0.01);
+        let mut l0 = BlockedBloomFilter::new(1000, 0.01);
+        memtable.insert_hash(1);
+        l0.insert_hash(2);
+
+        let tiers = [&memtable, &l0];
+        assert!(BlockedBloomFilter::check_any(&tiers, 1));
+        assert!(BlockedBloomFilter::check_any(&tiers, 2));
+        assert!(!BlockedBloomFilter::check_any(&tiers, 3));
+        assert!(!BlockedBloomFilter::check_any(&[], 1));
+    }
+
     #[test]
     fn test_different_seeds() {
         let mut bf1 = BlockedBloomFilter::new_with_seed(1000, 0.01, 123);
@@ -174,6 +794,20 @@ mod tests {
         assert!(bf1.may_match_key(key));
         assert!(!bf2.may_match_key(key));
     }
+
+    #[test]
+    fn test_extend_matching_rows() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        let hashes: Vec<u64> = (0..100u64).map(|i| bf.hash_key(&i)).collect();
+        for &h in hashes.iter().step_by(10) {
+            bf.insert_hash(h);
+        }
+        let mut rows = std::collections::BTreeSet::new();
+        let added = bf.extend_matching_rows(&hashes, 500, &mut rows);
+        assert_eq!(added, rows.len());
+        assert!((500..600).step_by(10).all(|row| rows.contains(&row)));
+        assert!(added < 13, "{rows:?}");
+    }
 }
 
 #[cfg(all(test, feature = "serde"))]
@@ -192,3 +826,56 @@ mod serde_tests {
         assert!(!deserialized.may_match_hash(43));
     }
 }
+
+#[cfg(all(test, feature = "safe-index", not(debug_assertions)))]
+mod safe_index_tests {
+    use super::*;
+
+    #[test]
+    fn test_inconsistent_geometry_does_not_panic() {
+        let mut bf = BlockedBloomFilter::new(0, 0.01);
+        bf.num_blocks = 1000;
+
+        let h = u64::MAX;
+        bf.insert_hash(h);
+        assert!(bf.may_match_hash(h));
+    }
+}
+
+#[cfg(all(test, feature = "zeroize"))]
+mod zeroize_tests {
+    use super::*;
+    use zeroize::Zeroize;
+
+    #[test]
+    fn test_blocks_zeroize() {
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+        bf.insert_hash(42);
+
+        let mut blocks = bf.blocks.to_vec();
+        blocks[..].zeroize();
+        assert!(blocks.iter().all(|b| b.words == [0; 8]));
+
+        fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<BlockedBloomFilter>();
+    }
+}
+
+#[cfg(all(test, feature = "cow"))]
+mod cow_tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_shares_until_mutation() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key("Leopold Bloom");
+
+        let mut clone = bf.clone();
+        assert_eq!(clone.blocks.as_ptr(), bf.blocks.as_ptr());
+
+        clone.insert_key("Molly Bloom");
+        assert_ne!(clone.blocks.as_ptr(), bf.blocks.as_ptr());
+        assert!(clone.may_match_key("Leopold Bloom"));
+        assert!(!bf.may_match_key("Molly Bloom"));
+    }
+}
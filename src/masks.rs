@@ -0,0 +1,102 @@
+use std::hash::Hash;
+
+use crate::BlockedBloomFilter;
+
+/// A small direct-mapped cache of per-word probe masks, keyed by the low half of a hash.
+///
+/// Computing a probe's masks takes eight multiplies; when a few hot hashes account for most
+/// lookups, reusing their masks skips that work. Keep one cache per thread and pass it to
+/// `may_match_hash_cached`; it can be shared across filters since masks do not depend on the
+/// filter.
+#[derive(Clone, Debug)]
+pub struct MaskCache {
+    entries: Vec<Option<(u32, [u32; 8])>>,
+    shift: u32,
+    hits: u64,
+}
+
+impl MaskCache {
+    /// Creates a cache with at least `capacity` slots, rounded up to a power of two.
+    pub fn new(capacity: usize) -> Self {
+        let slots = capacity.max(1).next_power_of_two();
+        Self {
+            entries: vec![None; slots],
+            shift: 32 - slots.trailing_zeros(),
+            hits: 0,
+        }
+    }
+
+    /// Returns the number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    fn masks(&mut self, key: u32) -> [u32; 8] {
+        // Fibonacci hashing spreads nearby keys over the slots; a one-slot cache uses slot 0.
+        let slot = (key.wrapping_mul(0x9e37_79b9) as u64 >> self.shift) as usize;
+        match self.entries[slot] {
+            Some((cached, masks)) if cached == key => {
+                self.hits += 1;
+                masks
+            }
+            _ => {
+                let masks =
+                    BlockedBloomFilter::SALT.map(|salt| 1 << (key.wrapping_mul(salt) >> 27));
+                self.entries[slot] = Some((key, masks));
+                masks
+            }
+        }
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Like `may_match_hash`, taking the probe masks from `cache` when it has them.
+    #[inline]
+    pub fn may_match_hash_cached(&self, h: u64, cache: &mut MaskCache) -> bool {
+        let masks = cache.masks(h as u32);
+        let block_idx = self.fast_map(h);
+        #[cfg(not(feature = "safe-index"))]
+        let block = &self.blocks[block_idx];
+        #[cfg(feature = "safe-index")]
+        let Some(block) = self.blocks.get(block_idx) else {
+            debug_assert!(false, "block index {block_idx} out of range");
+            return true;
+        };
+        block
+            .words
+            .iter()
+            .zip(masks)
+            .fold(0u32, |acc, (&w, mask)| acc | (mask & !w))
+            == 0
+    }
+
+    /// Hashes the key and checks it like `may_match_hash_cached`.
+    #[inline]
+    pub fn may_match_key_cached<T: Hash + ?Sized>(&self, key: &T, cache: &mut MaskCache) -> bool {
+        self.may_match_hash_cached(self.hash_key(key), cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_masks_match_uncached_probes() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        for i in 0..1000u64 {
+            bf.insert_key(&i);
+        }
+        let mut cache = MaskCache::new(1024);
+        for round in 0..2 {
+            for i in 0..2000u64 {
+                let key = i % 50 + round * 1000;
+                assert_eq!(
+                    bf.may_match_key_cached(&key, &mut cache),
+                    bf.may_match_key(&key)
+                );
+            }
+        }
+        assert!(cache.hits() > 3500);
+    }
+}
@@ -0,0 +1,166 @@
+//! Sizing formulas used by the filters, exposed for capacity planning.
+//!
+//! The classic formulas assume an unblocked Bloom filter. The `blocked_*` variants model
+//! `BlockedBloomFilter` exactly: keys land in blocks following a Poisson distribution and
+//! each key sets one bit in each of the block's eight 32-bit words.
+
+use std::f64::consts::LN_2;
+
+use crate::{BlockedBloomFilter, CacheLineBlock};
+
+/// Returns the bits per key the filters allocate for a target false positive rate.
+///
+/// This is the classic optimum `-ln(fpr) / ln(2)^2`, rounded up. Rates outside `(0, 1)` fall
+/// back to 10 bits per key.
+pub fn bits_per_key(fpr: f64) -> usize {
+    if fpr <= 0.0 || fpr >= 1.0 {
+        return 10;
+    }
+    (-fpr.ln() / (LN_2 * LN_2)).ceil() as usize
+}
+
+/// Returns the total bits a classic Bloom filter needs for `entries` at `fpr`.
+pub fn bits_for(entries: usize, fpr: f64) -> usize {
+    entries * bits_per_key(fpr)
+}
+
+/// Returns the false positive rate of a classic Bloom filter with `bits` bits, `entries`
+/// keys and `k` hash functions.
+pub fn fpr_for(bits: usize, entries: usize, k: u32) -> f64 {
+    if bits == 0 {
+        return 1.0;
+    }
+    let k = k as f64;
+    (1.0 - (-k * entries as f64 / bits as f64).exp()).powf(k)
+}
+
+/// Returns the number of hash functions minimizing a classic filter's false positive rate.
+pub fn optimal_k(bits_per_key: f64) -> u32 {
+    ((bits_per_key * LN_2).round() as u32).max(1)
+}
+
+/// Returns the number of blocks `BlockedBloomFilter::new` allocates.
+pub fn num_blocks_for(entries: usize, fpr: f64) -> u32 {
+    let num_blocks = bits_for(entries, fpr).div_ceil(CacheLineBlock::BITS) as u32;
+    num_blocks.max(1)
+}
+
+/// Returns the expected false positive rate of a blocked filter with `num_blocks` blocks
+/// holding `entries` keys.
+pub fn blocked_fpr_for(num_blocks: u32, entries: usize) -> f64 {
+    blocked_fpr_with_probes(num_blocks, entries, BlockedBloomFilter::PROBES)
+}
+
+/// Returns the expected false positive rate of blocks probed `probes` times per key, each
+/// probe setting one bit in its own `256 / probes`-bit lane of the block, as in
+/// `ReducedProbeFilter`. With 8 probes this is `blocked_fpr_for`.
+pub fn blocked_fpr_with_probes(num_blocks: u32, entries: usize, probes: u32) -> f64 {
+    let lambda = entries as f64 / num_blocks.max(1) as f64;
+    let lane_miss: f64 = 1.0 - probes as f64 / CacheLineBlock::BITS as f64;
+
+    // Sum over the Poisson-distributed number of keys j in the probed block.
+    let max_j = (lambda + 12.0 * lambda.sqrt() + 32.0) as u32;
+    // Track ln(P(j)) so heavily loaded blocks do not underflow exp(-lambda).
+    let mut ln_p_j = -lambda;
+    let mut fpr = 0.0;
+    for j in 0..=max_j {
+        if j > 0 {
+            ln_p_j += (lambda / j as f64).ln();
+        }
+        fpr += ln_p_j.exp() * (1.0 - lane_miss.powi(j as i32)).powi(probes as i32);
+    }
+    fpr.min(1.0)
+}
+
+/// Returns the smallest block count whose blocked false positive rate is at most `fpr`.
+pub fn blocked_num_blocks_for(entries: usize, fpr: f64) -> u32 {
+    let mut lo = 1u32;
+    let mut hi = num_blocks_for(entries, fpr).max(1);
+    while blocked_fpr_for(hi, entries) > fpr {
+        lo = hi;
+        hi = hi.saturating_mul(2);
+        if hi == u32::MAX {
+            return hi;
+        }
+    }
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if blocked_fpr_for(mid, entries) > fpr {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    hi
+}
+
+/// Returns the total bits a blocked filter needs for `entries` at `fpr`.
+pub fn blocked_bits_for(entries: usize, fpr: f64) -> usize {
+    blocked_num_blocks_for(entries, fpr) as usize * CacheLineBlock::BITS
+}
+
+/// Returns the probability that an inserted key no longer matches after
+/// `BlockedBloomFilter::randomized_response` with flip probability `p`, i.e. that any of its
+/// 8 bits was cleared.
+pub fn randomized_response_fnr(p: f64) -> f64 {
+    1.0 - (1.0 - p).powi(BlockedBloomFilter::PROBES as i32)
+}
+
+/// Returns the false positive rate of a query that checks several independent filters and
+/// matches if any of them does.
+pub fn composite_fpr(rates: &[f64]) -> f64 {
+    1.0 - rates.iter().map(|f| 1.0 - f).product::<f64>()
+}
+
+/// Splits a target false positive rate across filters that are all checked per query.
+///
+/// Each filter gets a share proportional to its weight, and the shares combine to exactly
+/// `target` under `composite_fpr`. Weighting by expected entries minimizes total size, since
+/// a filter's bits grow with `entries * ln(1 / fpr)`.
+pub fn split_fpr(target: f64, weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    weights
+        .iter()
+        .map(|w| 1.0 - (1.0 - target).powf(w / total))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_formulas() {
+        assert_eq!(bits_per_key(0.01), 10);
+        assert_eq!(bits_for(1000, 0.01), 10_000);
+        assert_eq!(optimal_k(10.0), 7);
+        assert!((fpr_for(10_000, 1000, 7) - 0.0082).abs() < 0.0005);
+    }
+
+    #[test]
+    fn test_blocked_formulas_match_filter() {
+        let entries = 100_000;
+        let num_blocks = num_blocks_for(entries, 0.01);
+        assert_eq!(
+            num_blocks,
+            BlockedBloomFilter::new(entries, 0.01).params().num_blocks
+        );
+
+        // Measured on the filter: ~1.27% at 10 bits per key.
+        let predicted = blocked_fpr_for(num_blocks, entries);
+        assert!(predicted > 0.011 && predicted < 0.014, "{predicted}");
+
+        let blocks = blocked_num_blocks_for(entries, 0.01);
+        assert!(blocks > num_blocks);
+        assert!(blocked_fpr_for(blocks, entries) <= 0.01);
+        assert!(blocked_fpr_for(blocks - 1, entries) > 0.01);
+        assert!(blocked_fpr_for(1, 10_000) > 0.99);
+    }
+
+    #[test]
+    fn test_split_fpr_meets_target() {
+        let rates = split_fpr(0.01, &[1.0, 3.0]);
+        assert!((composite_fpr(&rates) - 0.01).abs() < 1e-12);
+        assert!(rates[1] > rates[0]);
+    }
+}
@@ -0,0 +1,84 @@
+use std::convert::Infallible;
+use std::future::{Future, ready};
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, HashAlgorithm};
+
+/// Batched approximate membership queries, answered locally or by a remote service.
+///
+/// Application code written against this trait works unchanged whether the filter lives in
+/// process or behind a server. Queries are async and batched because remote ones need to be;
+/// local implementations answer immediately.
+pub trait ApproxMembership: Sync {
+    /// Error produced when a query cannot be answered.
+    type Error;
+
+    /// Returns the seed keys are hashed with.
+    fn seed(&self) -> u64;
+
+    /// Returns the algorithm keys are hashed with.
+    fn hash_algorithm(&self) -> HashAlgorithm;
+
+    /// Checks a batch of hashes, answering in order.
+    fn may_match_hashes(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Result<Vec<bool>, Self::Error>> + Send;
+
+    /// Hashes the keys with `seed` and `hash_algorithm` and checks them like `may_match_hashes`.
+    fn may_match_keys<T: Hash>(
+        &self,
+        keys: &[T],
+    ) -> impl Future<Output = Result<Vec<bool>, Self::Error>> + Send {
+        let hashes: Vec<u64> = keys
+            .iter()
+            .map(|key| self.hash_algorithm().hash_key(self.seed(), key))
+            .collect();
+        async move { self.may_match_hashes(&hashes).await }
+    }
+}
+
+impl ApproxMembership for BlockedBloomFilter {
+    type Error = Infallible;
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
+
+    fn may_match_hashes(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Result<Vec<bool>, Self::Error>> + Send {
+        ready(Ok(hashes.iter().map(|&h| self.may_match_hash(h)).collect()))
+    }
+
+    /// Hashes with the filter's own algorithm through `BlockedBloomFilter::may_match_keys`.
+    fn may_match_keys<T: Hash>(
+        &self,
+        keys: &[T],
+    ) -> impl Future<Output = Result<Vec<bool>, Self::Error>> + Send {
+        ready(Ok(BlockedBloomFilter::may_match_keys(self, keys)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_filter_answers_batches() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 3);
+        bf.insert_key("Leopold Bloom");
+        bf.insert_hash(42);
+        let keys = ["Molly Bloom", "Leopold Bloom"];
+        assert_eq!(
+            ApproxMembership::may_match_keys(&bf, &keys).await.unwrap(),
+            [false, true]
+        );
+        assert_eq!(bf.may_match_hashes(&[42, 43]).await.unwrap(), [true, false]);
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+
+use crate::{BlockMapping, BlockedBloomFilter, ErrorKind, FilterParams, HashAlgorithm};
+
+/// Why two filters cannot be combined by `union` or `intersect`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    /// The filters have different block counts, so were sized for different entries or rates.
+    BlockCount { left: u32, right: u32 },
+    /// The filters hash with different seeds.
+    Seed { left: u64, right: u64 },
+    /// The filters map hashes to blocks differently.
+    Mapping {
+        left: BlockMapping,
+        right: BlockMapping,
+    },
+    /// The filters hash keys with different algorithms.
+    Hash {
+        left: HashAlgorithm,
+        right: HashAlgorithm,
+    },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockCount { left, right } => {
+                write!(f, "cannot combine filters of {left} and {right} blocks")
+            }
+            Self::Seed { left, right } => {
+                write!(f, "cannot combine filters with seeds {left} and {right}")
+            }
+            Self::Mapping { left, right } => {
+                write!(
+                    f,
+                    "cannot combine filters with {left:?} and {right:?} block mapping"
+                )
+            }
+            Self::Hash { left, right } => {
+                write!(
+                    f,
+                    "cannot combine filters hashing keys with {left:?} and {right:?}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl MergeError {
+    /// Returns the category of the error, always `IncompatibleGeometry`.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::IncompatibleGeometry
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Checks that `other` was built with the same geometry and hashing, so a key inserted into
+    /// either sets the same bits in both.
+    pub(crate) fn check_compatible(&self, other: &Self) -> Result<(), MergeError> {
+        self.check_params(&other.params())
+    }
+
+    /// Checks that a filter with parameters `other` sets the same bits for a key as this one.
+    pub(crate) fn check_params(&self, other: &FilterParams) -> Result<(), MergeError> {
+        if self.num_blocks != other.num_blocks {
+            return Err(MergeError::BlockCount {
+                left: self.num_blocks,
+                right: other.num_blocks,
+            });
+        }
+        if self.seed != other.seed {
+            return Err(MergeError::Seed {
+                left: self.seed,
+                right: other.seed,
+            });
+        }
+        if self.mapping != other.mapping {
+            return Err(MergeError::Mapping {
+                left: self.mapping,
+                right: other.mapping,
+            });
+        }
+        if self.hash != other.hash {
+            return Err(MergeError::Hash {
+                left: self.hash,
+                right: other.hash,
+            });
+        }
+        Ok(())
+    }
+
+    /// Merges `other` into this filter, which then matches every key either filter matched.
+    ///
+    /// For combining filters built in parallel, such as one per shard, without reinserting
+    /// their keys. Both must come from the same entries, false positive rate, seed, mapping
+    /// and hash algorithm; the result is exactly the filter a single build over all keys would
+    /// give. Unlike `join`, incompatible filters are reported rather than panicking.
+    pub fn union(&mut self, other: &Self) -> Result<(), MergeError> {
+        self.check_compatible(other)?;
+        self.join(other);
+        Ok(())
+    }
+
+    /// Returns the union of this filter and `other`, leaving both unchanged.
+    pub fn union_with(&self, other: &Self) -> Result<Self, MergeError> {
+        self.check_compatible(other)?;
+        let mut union = self.clone();
+        union.join(other);
+        Ok(union)
+    }
+
+    /// ANDs `other` into this filter, approximating the keys both filters hold.
+    ///
+    /// Every key inserted into both still matches, so the result has no false negatives for
+    /// the intersection, for example the keys seen on two replicas. Its false positive rate is
+    /// higher than that of a filter built from the common keys alone, since bits set by
+    /// different keys in each filter can line up; `export_diagnostics` on the result estimates
+    /// it. Both filters must be compatible as for `union`.
+    pub fn intersect(&mut self, other: &Self) -> Result<(), MergeError> {
+        self.check_compatible(other)?;
+        for (dst, src) in self.blocks_mut().iter_mut().zip(other.blocks.iter()) {
+            for (d, s) in dst.words.iter_mut().zip(src.words) {
+                *d &= s;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the intersection of this filter and `other`, leaving both unchanged.
+    pub fn intersect_with(&self, other: &Self) -> Result<Self, MergeError> {
+        let mut intersection = self.clone();
+        intersection.intersect(other)?;
+        Ok(intersection)
+    }
+}
+
+/// `a |= &b` is `a.union(&b)`.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible; use `union` to handle that as an error.
+impl BitOrAssign<&BlockedBloomFilter> for BlockedBloomFilter {
+    fn bitor_assign(&mut self, other: &BlockedBloomFilter) {
+        if let Err(e) = self.union(other) {
+            panic!("{e}");
+        }
+    }
+}
+
+/// `a &= &b` is `a.intersect(&b)`.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible; use `intersect` to handle that as an error.
+impl BitAndAssign<&BlockedBloomFilter> for BlockedBloomFilter {
+    fn bitand_assign(&mut self, other: &BlockedBloomFilter) {
+        if let Err(e) = self.intersect(other) {
+            panic!("{e}");
+        }
+    }
+}
+
+/// `a | &b` reuses `a`'s storage for the union.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible.
+impl BitOr<&BlockedBloomFilter> for BlockedBloomFilter {
+    type Output = BlockedBloomFilter;
+
+    fn bitor(mut self, other: &BlockedBloomFilter) -> BlockedBloomFilter {
+        self |= other;
+        self
+    }
+}
+
+/// `&a | &b` is a new filter, the union of both.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible.
+impl BitOr for &BlockedBloomFilter {
+    type Output = BlockedBloomFilter;
+
+    fn bitor(self, other: &BlockedBloomFilter) -> BlockedBloomFilter {
+        self.clone() | other
+    }
+}
+
+/// `a & &b` reuses `a`'s storage for the intersection.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible.
+impl BitAnd<&BlockedBloomFilter> for BlockedBloomFilter {
+    type Output = BlockedBloomFilter;
+
+    fn bitand(mut self, other: &BlockedBloomFilter) -> BlockedBloomFilter {
+        self &= other;
+        self
+    }
+}
+
+/// `&a & &b` is a new filter, the intersection of both.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible.
+impl BitAnd for &BlockedBloomFilter {
+    type Output = BlockedBloomFilter;
+
+    fn bitand(self, other: &BlockedBloomFilter) -> BlockedBloomFilter {
+        self.clone() & other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_union_of_shards_matches_single_build() {
+        let mut shards: Vec<BlockedBloomFilter> = (0..4)
+            .map(|_| BlockedBloomFilter::new_with_seed(10_000, 0.01, 9))
+            .collect();
+        let mut single = BlockedBloomFilter::new_with_seed(10_000, 0.01, 9);
+        for i in 0..10_000u64 {
+            shards[i as usize % 4].insert_key(&i);
+            single.insert_key(&i);
+        }
+        let pair = shards[0].union_with(&shards[1]).unwrap();
+        assert!(!shards[0].may_match_key(&1u64));
+        let mut merged = shards.pop().unwrap();
+        merged.union(&pair).unwrap();
+        merged.union(&shards[2]).unwrap();
+        assert_eq!(merged.blocks[..], single.blocks[..]);
+
+        let other = BlockedBloomFilter::new_with_seed(10_000, 0.01, 10);
+        assert_eq!(
+            merged.union(&other),
+            Err(MergeError::Seed { left: 9, right: 10 })
+        );
+        let small = BlockedBloomFilter::new_with_seed(100, 0.01, 9);
+        assert!(matches!(
+            merged.union_with(&small),
+            Err(MergeError::BlockCount { .. })
+        ));
+    }
+
+    #[test]
+    fn test_intersection_keeps_common_keys() {
+        let mut a = BlockedBloomFilter::new_with_seed(10_000, 0.01, 2);
+        let mut b = a.clone();
+        (0..10_000u64).for_each(|i| a.insert_key(&i));
+        (5_000..15_000u64).for_each(|i| b.insert_key(&i));
+        let common = a.intersect_with(&b).unwrap();
+        assert!((5_000..10_000u64).all(|i| common.may_match_key(&i)));
+        let only_one = (0..5_000u64)
+            .chain(10_000..15_000)
+            .filter(|i| common.may_match_key(i))
+            .count();
+        assert!(only_one < 500, "{only_one}");
+
+        a.intersect(&b).unwrap();
+        assert_eq!(a.blocks[..], common.blocks[..]);
+        let other = BlockedBloomFilter::new_with_seed(10_000, 0.01, 3);
+        assert!(a.intersect(&other).is_err());
+    }
+
+    #[test]
+    fn test_operators_match_methods() {
+        let mut a = BlockedBloomFilter::new(1000, 0.01);
+        let mut b = a.clone();
+        (0..600u64).for_each(|i| a.insert_key(&i));
+        (400..1000u64).for_each(|i| b.insert_key(&i));
+
+        let or = &a | &b;
+        let and = &a & &b;
+        assert_eq!(or.blocks[..], a.union_with(&b).unwrap().blocks[..]);
+        assert_eq!(and.blocks[..], a.intersect_with(&b).unwrap().blocks[..]);
+        assert_eq!((a.clone() | &b).blocks[..], or.blocks[..]);
+        a &= &b;
+        assert_eq!(a.blocks[..], and.blocks[..]);
+        a |= &or;
+        assert_eq!(a.blocks[..], or.blocks[..]);
+
+        let other_seed = BlockedBloomFilter::new_with_seed(1000, 0.01, 1);
+        let panicked = std::panic::catch_unwind(|| &a | &other_seed);
+        assert!(panicked.is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::hash::{Hash, Hasher};
+
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, Wiped};
+
+impl BlockedBloomFilter {
+    /// Rebuilds the filter under `new_seed` from a stream of its keys.
+    ///
+    /// The result has the same geometry. Keys missing from the stream are lost, since a filter
+    /// cannot be re-seeded from its bits alone.
+    pub fn migrate<K: Hash>(&self, new_seed: u64, keys: impl Iterator<Item = K>) -> Self {
+        let mut migrated = Self::from_blocks(
+            vec![CacheLineBlock::default(); self.num_blocks as usize],
+            new_seed,
+        )
+        .with_mapping(self.mapping)
+        .with_hash(self.hash);
+        for key in keys {
+            migrated.insert_key(&key);
+        }
+        migrated
+    }
+}
+
+/// Captures the bytes a key feeds to its hasher.
+struct ByteRecorder<'a>(&'a mut Vec<u8>);
+
+impl Hasher for ByteRecorder<'_> {
+    fn finish(&self) -> u64 {
+        unreachable!("the recorder only captures input")
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+/// Builds a filter while retaining what it needs to migrate to another seed later.
+///
+/// For each key it keeps the exact bytes the key's `Hash` impl writes, which is what the
+/// filter's hasher consumes, so `migrate` can re-hash every key under a new seed without the
+/// original data. Memory grows with the total size of the keys. With the `zeroize` feature
+/// the recorded bytes are wiped when the builder is dropped.
+#[derive(Clone, Debug)]
+pub struct MigratableBuilder {
+    filter: BlockedBloomFilter,
+    bytes: Wiped<Vec<u8>>,
+    ends: Wiped<Vec<usize>>,
+}
+
+impl MigratableBuilder {
+    /// Creates a builder for a filter sized like `BlockedBloomFilter::new_with_seed`.
+    pub fn new(entries: usize, fpr: f64, seed: u64) -> Self {
+        Self {
+            filter: BlockedBloomFilter::new_with_seed(entries, fpr, seed),
+            bytes: Wiped::default(),
+            ends: Wiped::default(),
+        }
+    }
+
+    /// Records the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        let start = self.bytes.len();
+        key.hash(&mut ByteRecorder(&mut self.bytes));
+        self.ends.push(self.bytes.len());
+
+        let mut hasher = Xxh64::new(self.filter.seed);
+        hasher.write(&self.bytes[start..]);
+        self.filter.insert_hash(hasher.finish());
+    }
+
+    /// Returns the filter built so far.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Returns the number of recorded keys.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Returns `true` if no key has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Rebuilds the filter and its records under `new_seed`.
+    pub fn migrate(&self, new_seed: u64) -> Self {
+        let mut filter = BlockedBloomFilter::from_blocks(
+            vec![CacheLineBlock::default(); self.filter.num_blocks as usize],
+            new_seed,
+        );
+        let mut start = 0;
+        for &end in self.ends.iter() {
+            let mut hasher = Xxh64::new(new_seed);
+            hasher.write(&self.bytes[start..end]);
+            filter.insert_hash(hasher.finish());
+            start = end;
+        }
+        Self {
+            filter,
+            bytes: self.bytes.clone(),
+            ends: self.ends.clone(),
+        }
+    }
+
+    /// Drops the records and returns the filter.
+    pub fn finish(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for MigratableBuilder {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migration_matches_fresh_build() {
+        let keys = ["Leopold Bloom", "Molly Bloom", "Stephen Dedalus"];
+        let mut builder = MigratableBuilder::new(1000, 0.01, 1);
+        let mut original = BlockedBloomFilter::new_with_seed(1000, 0.01, 1);
+        for key in keys {
+            builder.insert_key(key);
+            original.insert_key(key);
+        }
+        builder.insert_key(&1904u64);
+        original.insert_key(&1904u64);
+        assert_eq!(builder.filter().blocks[..], original.blocks[..]);
+
+        let mut fresh = BlockedBloomFilter::new_with_seed(1000, 0.01, 2);
+        for key in keys {
+            fresh.insert_key(key);
+        }
+        fresh.insert_key(&1904u64);
+
+        let migrated = builder.migrate(2);
+        assert_eq!(migrated.len(), 4);
+        assert_eq!(migrated.filter().blocks[..], fresh.blocks[..]);
+        assert_eq!(migrated.filter().params(), fresh.params());
+
+        let streamed = original.migrate(2, keys.iter());
+        assert!(keys.iter().all(|k| streamed.may_match_key(k)));
+        assert_eq!(streamed.params(), fresh.params());
+    }
+}
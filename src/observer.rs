@@ -0,0 +1,161 @@
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::BlockedBloomFilter;
+
+/// Coarse counters for one sampled query operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProbeSample {
+    /// Hashes probed by the operation: 1 for single queries, the batch size for batches.
+    pub probes: usize,
+    /// Probes that answered "may match".
+    pub hits: usize,
+    /// Operations this sample stands for, the sampling interval. Multiply counters by it to
+    /// estimate totals.
+    pub weight: u32,
+}
+
+/// Receives samples of the queries made through an `ObservedFilter`.
+///
+/// Called inline on the query path, so implementations should only bump counters or push to a
+/// channel. Closures taking a `&ProbeSample` implement it.
+pub trait ProbeObserver {
+    /// Records one sampled operation.
+    fn observe(&self, sample: &ProbeSample);
+}
+
+impl<F: Fn(&ProbeSample)> ProbeObserver for F {
+    fn observe(&self, sample: &ProbeSample) {
+        self(sample)
+    }
+}
+
+/// A filter that reports a sample of its queries to a `ProbeObserver`.
+///
+/// Every `sample_every`-th query operation (a single query or a batch) is reported; the rest
+/// cost one relaxed atomic increment. This feeds an embedder's own telemetry without a metrics
+/// dependency.
+#[derive(Debug)]
+pub struct ObservedFilter<O> {
+    filter: BlockedBloomFilter,
+    observer: O,
+    sample_every: u32,
+    operations: AtomicU64,
+}
+
+impl<O: ProbeObserver> ObservedFilter<O> {
+    /// Wraps `filter`, reporting one in `sample_every` operations to `observer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_every` is zero.
+    pub fn new(filter: BlockedBloomFilter, observer: O, sample_every: u32) -> Self {
+        assert!(sample_every > 0, "sampling interval must be positive");
+        Self {
+            filter,
+            observer,
+            sample_every,
+            operations: AtomicU64::new(0),
+        }
+    }
+
+    fn sampled(&self) -> bool {
+        let n = self.operations.fetch_add(1, Ordering::Relaxed);
+        n.is_multiple_of(self.sample_every as u64)
+    }
+
+    fn report(&self, probes: usize, hits: usize) {
+        self.observer.observe(&ProbeSample {
+            probes,
+            hits,
+            weight: self.sample_every,
+        });
+    }
+
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let hit = self.filter.may_match_hash(h);
+        if self.sampled() {
+            self.report(1, hit as usize);
+        }
+        hit
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.filter.hash_key(key))
+    }
+
+    /// Probes a batch of hashes like `BlockedBloomFilter::select_hashes`.
+    pub fn select_hashes(&self, hashes: &[u64], selection: &mut Vec<u32>) -> usize {
+        let selected = self.filter.select_hashes(hashes, selection);
+        if self.sampled() {
+            self.report(hashes.len(), selected);
+        }
+        selected
+    }
+
+    /// Inserts a hash into the filter. Inserts are not observed.
+    pub fn insert_hash(&mut self, h: u64) {
+        self.filter.insert_hash(h);
+    }
+
+    /// Hashes the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.filter.insert_key(key);
+    }
+
+    /// Returns the number of query operations so far, sampled or not.
+    pub fn operations(&self) -> u64 {
+        self.operations.load(Ordering::Relaxed)
+    }
+
+    /// Returns the wrapped filter.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Drops the observer and returns the filter.
+    pub fn into_inner(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_observer_sees_sampled_operations() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key("Leopold Bloom");
+        let samples = Mutex::new(Vec::new());
+        let observed =
+            ObservedFilter::new(bf, |s: &ProbeSample| samples.lock().unwrap().push(*s), 2);
+
+        assert!(observed.may_match_key("Leopold Bloom"));
+        assert!(!observed.may_match_key("Molly Bloom"));
+        let leopold = observed.filter().hash_key("Leopold Bloom");
+        let mut selection = Vec::new();
+        assert_eq!(
+            observed.select_hashes(&[leopold, 1, leopold], &mut selection),
+            2
+        );
+        assert_eq!(observed.operations(), 3);
+
+        let expected = [
+            ProbeSample {
+                probes: 1,
+                hits: 1,
+                weight: 2,
+            },
+            ProbeSample {
+                probes: 3,
+                hits: 2,
+                weight: 2,
+            },
+        ];
+        assert_eq!(*samples.lock().unwrap(), expected);
+    }
+}
@@ -0,0 +1,122 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::{BlockedBloomFilter, CacheLineBlock};
+
+/// Builds one filter from several threads without sharing it during the build.
+///
+/// Each thread (or rayon task) takes a private `LocalFilter` with `local`, inserts into it
+/// without synchronization, and drops it when done; dropped filters are OR-ed into the
+/// result. All local filters share the builder's geometry and seed, so the merge is exact.
+/// Each live local filter is a full-size copy, so keep one per thread rather than per item.
+#[derive(Debug)]
+pub struct ParallelBuilder {
+    num_blocks: u32,
+    seed: u64,
+    merged: Mutex<Option<BlockedBloomFilter>>,
+}
+
+impl ParallelBuilder {
+    /// Creates a builder for a filter sized like `BlockedBloomFilter::new_with_seed`.
+    pub fn new(entries: usize, fpr: f64, seed: u64) -> Self {
+        let template = BlockedBloomFilter::new_with_seed(entries, fpr, seed);
+        Self {
+            num_blocks: template.num_blocks,
+            seed,
+            merged: Mutex::new(None),
+        }
+    }
+
+    fn empty(&self) -> BlockedBloomFilter {
+        BlockedBloomFilter::from_blocks(
+            vec![CacheLineBlock::default(); self.num_blocks as usize],
+            self.seed,
+        )
+    }
+
+    /// Hands out an empty private filter that is merged into the result when dropped.
+    pub fn local(&self) -> LocalFilter<'_> {
+        LocalFilter {
+            builder: self,
+            filter: Some(self.empty()),
+        }
+    }
+
+    fn merge(&self, filter: BlockedBloomFilter) {
+        let mut merged = self.merged.lock().unwrap();
+        match merged.as_mut() {
+            Some(merged) => {
+                for (dst, src) in merged.blocks_mut().iter_mut().zip(filter.blocks.iter()) {
+                    for (d, s) in dst.words.iter_mut().zip(src.words) {
+                        *d |= s;
+                    }
+                }
+            }
+            None => *merged = Some(filter),
+        }
+    }
+
+    /// Returns the union of all local filters dropped so far.
+    pub fn finish(self) -> BlockedBloomFilter {
+        let merged = self.merged.lock().unwrap().take();
+        merged.unwrap_or_else(|| self.empty())
+    }
+}
+
+/// A private filter handed out by `ParallelBuilder::local`.
+#[derive(Debug)]
+pub struct LocalFilter<'a> {
+    builder: &'a ParallelBuilder,
+    filter: Option<BlockedBloomFilter>,
+}
+
+impl Deref for LocalFilter<'_> {
+    type Target = BlockedBloomFilter;
+
+    fn deref(&self) -> &BlockedBloomFilter {
+        self.filter.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for LocalFilter<'_> {
+    fn deref_mut(&mut self) -> &mut BlockedBloomFilter {
+        self.filter.as_mut().unwrap()
+    }
+}
+
+impl Drop for LocalFilter<'_> {
+    fn drop(&mut self) {
+        if let Some(filter) = self.filter.take() {
+            self.builder.merge(filter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_build_matches_sequential() {
+        let builder = ParallelBuilder::new(10_000, 0.01, 7);
+        std::thread::scope(|s| {
+            for t in 0..4u64 {
+                let builder = &builder;
+                s.spawn(move || {
+                    let mut local = builder.local();
+                    for i in (t..10_000).step_by(4) {
+                        local.insert_key(&i);
+                    }
+                });
+            }
+        });
+        let merged = builder.finish();
+
+        let mut sequential = BlockedBloomFilter::new_with_seed(10_000, 0.01, 7);
+        for i in 0..10_000u64 {
+            sequential.insert_key(&i);
+        }
+        assert_eq!(merged.blocks[..], sequential.blocks[..]);
+        assert_eq!(merged.params(), sequential.params());
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use xxhash_rust::xxh3::Xxh3;
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, ErrorKind, key_hash};
+
+/// Key hashing algorithm used by the `*_key` methods.
+///
+/// Keys are fed through `Hash` either way; the algorithm only changes the hasher. It is
+/// recorded in the binary format, so decoded filters hash keys the way they were built.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// xxHash64 seeded with the filter's seed.
+    #[default]
+    Xxh64,
+    /// XXH3 (64-bit) seeded with the filter's seed. Faster than xxHash64 on long keys.
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Hashes a key with `seed`.
+    #[inline]
+    pub(crate) fn hash_key<T: Hash + ?Sized>(self, seed: u64, key: &T) -> u64 {
+        match self {
+            Self::Xxh64 => key_hash(seed, key),
+            Self::Xxh3 => {
+                let mut hasher = Xxh3::with_seed(seed);
+                key.hash(&mut hasher);
+                hasher.finish()
+            }
+        }
+    }
+
+    /// Hashes raw bytes with `seed` exactly as `hash_key` would hash the equivalent `str`.
+    #[inline]
+    pub(crate) fn hash_str_bytes(self, seed: u64, bytes: &[u8]) -> u64 {
+        fn finish(mut hasher: impl Hasher, bytes: &[u8]) -> u64 {
+            hasher.write(bytes);
+            hasher.write_u8(0xff);
+            hasher.finish()
+        }
+        match self {
+            Self::Xxh64 => finish(Xxh64::new(seed), bytes),
+            Self::Xxh3 => finish(Xxh3::with_seed(seed), bytes),
+        }
+    }
+
+    /// Encodes the algorithm for the binary format's header.
+    pub(crate) fn to_u32(self) -> u32 {
+        match self {
+            Self::Xxh64 => 0,
+            Self::Xxh3 => 1,
+        }
+    }
+
+    /// Decodes an algorithm written by `to_u32`.
+    pub(crate) fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Xxh64),
+            1 => Some(Self::Xxh3),
+            _ => None,
+        }
+    }
+}
+
+/// A reusable hasher for hashing many keys with one algorithm and seed.
+///
+/// `HashAlgorithm::hash_key` builds a fresh hasher per key, and an `Xxh3` carries a few hundred
+/// bytes of buffer and secret. Batch APIs build one `KeyHasher` and reset it between keys.
+pub(crate) enum KeyHasher {
+    Xxh64(Xxh64, u64),
+    Xxh3(Box<Xxh3>),
+}
+
+impl KeyHasher {
+    pub(crate) fn new(algorithm: HashAlgorithm, seed: u64) -> Self {
+        match algorithm {
+            HashAlgorithm::Xxh64 => Self::Xxh64(Xxh64::new(seed), seed),
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::new(Xxh3::with_seed(seed))),
+        }
+    }
+
+    /// Hashes a key exactly as `HashAlgorithm::hash_key` would.
+    #[inline]
+    pub(crate) fn hash_key<T: Hash + ?Sized>(&mut self, key: &T) -> u64 {
+        match self {
+            Self::Xxh64(hasher, seed) => {
+                hasher.reset(*seed);
+                key.hash(hasher);
+                hasher.finish()
+            }
+            Self::Xxh3(hasher) => {
+                hasher.reset();
+                key.hash(hasher.as_mut());
+                hasher.finish()
+            }
+        }
+    }
+}
+
+/// How a hash picks its block.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlockMapping {
+    /// The upper 32 bits pick the block directly. The fastest option, and the one Parquet's
+    /// split block Bloom filters use.
+    #[default]
+    Direct,
+    /// Both halves of the hash are mixed with a 32-bit finalizer before picking the block.
+    ///
+    /// For externally produced hashes whose upper bits carry little entropy (e.g. widened
+    /// 32-bit hashes), which `Direct` would funnel into a few blocks. Costs two multiplies
+    /// per operation.
+    Mixed,
+}
+
+impl BlockMapping {
+    /// Returns the block among `num_blocks` that `h` maps to.
+    #[inline(always)]
+    pub(crate) fn block_index(self, h: u64, num_blocks: u32) -> usize {
+        let hi = match self {
+            Self::Direct => (h >> 32) as u32,
+            Self::Mixed => fmix32((h >> 32) as u32 ^ (h as u32).wrapping_mul(0x9e37_79b9)),
+        };
+        ((hi as u64 * num_blocks as u64) >> 32) as usize
+    }
+
+    /// Encodes the mapping for the binary format's header.
+    pub(crate) fn to_u32(self) -> u32 {
+        match self {
+            Self::Direct => 0,
+            Self::Mixed => 1,
+        }
+    }
+
+    /// Decodes a mapping written by `to_u32`.
+    pub(crate) fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Direct),
+            1 => Some(Self::Mixed),
+            _ => None,
+        }
+    }
+}
+
+/// MurmurHash3's 32-bit finalizer.
+#[inline(always)]
+fn fmix32(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+/// Everything needed to build a filter that is bit-compatible with another one.
+///
+/// Distributed builders can exchange and compare these before exchanging any block data.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FilterParams {
+    /// Seed for key hashing.
+    pub seed: u64,
+    /// Number of cache-line blocks.
+    pub num_blocks: u32,
+    /// Bits set per inserted hash.
+    pub probes: u32,
+    /// Size of one block in bytes.
+    pub block_bytes: u32,
+    /// Key hashing algorithm.
+    pub hash: HashAlgorithm,
+    /// How hashes pick their block.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mapping: BlockMapping,
+}
+
+/// Errors produced when building a filter from `FilterParams`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParamsError {
+    /// The parameters describe a filter without blocks.
+    NoBlocks,
+    /// The probe count differs from the one this build implements.
+    UnsupportedProbes(u32),
+    /// The block size differs from the one this build implements.
+    UnsupportedBlockBytes(u32),
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBlocks => write!(f, "filter parameters have zero blocks"),
+            Self::UnsupportedProbes(k) => write!(
+                f,
+                "unsupported probe count {k} (expected {})",
+                BlockedBloomFilter::PROBES
+            ),
+            Self::UnsupportedBlockBytes(b) => write!(
+                f,
+                "unsupported block size of {b} bytes (expected {})",
+                size_of::<CacheLineBlock>()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+impl ParamsError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NoBlocks => ErrorKind::InvalidParameters,
+            Self::UnsupportedProbes(_) | Self::UnsupportedBlockBytes(_) => {
+                ErrorKind::IncompatibleGeometry
+            }
+        }
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Returns the parameters describing this filter's geometry and hashing.
+    pub fn params(&self) -> FilterParams {
+        FilterParams {
+            seed: self.seed,
+            num_blocks: self.num_blocks,
+            probes: Self::PROBES,
+            block_bytes: size_of::<CacheLineBlock>() as u32,
+            hash: self.hash,
+            mapping: self.mapping,
+        }
+    }
+
+    /// Creates an empty filter compatible with any filter that has the same parameters.
+    pub fn with_params(params: FilterParams) -> Result<Self, ParamsError> {
+        if params.num_blocks == 0 {
+            return Err(ParamsError::NoBlocks);
+        }
+        if params.probes != Self::PROBES {
+            return Err(ParamsError::UnsupportedProbes(params.probes));
+        }
+        if params.block_bytes as usize != size_of::<CacheLineBlock>() {
+            return Err(ParamsError::UnsupportedBlockBytes(params.block_bytes));
+        }
+        let blocks = vec![CacheLineBlock::default(); params.num_blocks as usize];
+        Ok(Self::from_blocks(blocks, params.seed)
+            .with_mapping(params.mapping)
+            .with_hash(params.hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_params_round_trip() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 42);
+        bf.insert_key("Leopold Bloom");
+
+        let params = bf.params();
+        let mut other = BlockedBloomFilter::with_params(params).unwrap();
+        assert_eq!(other.params(), params);
+        assert!(!other.may_match_key("Leopold Bloom"));
+        other.insert_key("Leopold Bloom");
+        assert_eq!(other.blocks[..], bf.blocks[..]);
+
+        let bad = FilterParams {
+            probes: 4,
+            ..params
+        };
+        assert_eq!(
+            BlockedBloomFilter::with_params(bad).unwrap_err(),
+            ParamsError::UnsupportedProbes(4)
+        );
+    }
+
+    #[test]
+    fn test_mixed_mapping_survives_encoding() {
+        let mut bf = BlockedBloomFilter::new_with_mapping(1000, 0.01, 7, BlockMapping::Mixed);
+        bf.insert_hash(42);
+        assert_eq!(bf.params().mapping, BlockMapping::Mixed);
+
+        let decoded = BlockedBloomFilter::from_bytes(&bf.to_bytes()).unwrap();
+        assert_eq!(decoded.block_mapping(), BlockMapping::Mixed);
+        assert!(decoded.may_match_hash(42));
+        let archived = crate::ArchivedFilter::from_bytes(&bf.to_bytes())
+            .unwrap()
+            .to_filter();
+        assert_eq!(archived.params(), bf.params());
+
+        let direct = BlockedBloomFilter::new_with_seed(1000, 0.01, 7);
+        assert_ne!(
+            direct.fast_map(42 << 32 | 1),
+            bf.fast_map(42 << 32 | 1),
+            "mixing should move at least this hash"
+        );
+    }
+}
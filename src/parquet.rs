@@ -0,0 +1,133 @@
+use parquet::arrow::arrow_writer::ArrowColumnChunk;
+use parquet::bloom_filter::Sbbf;
+use parquet::data_type::AsBytes;
+use parquet::errors::ParquetError;
+use parquet::file::metadata::ColumnChunkMetaData;
+use parquet::file::reader::ChunkReader;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::binary::blocks_from_le_bytes;
+use crate::{BlockMapping, BlockedBloomFilter};
+
+impl BlockedBloomFilter {
+    /// Inserts a value hashed the way Parquet readers hash it.
+    ///
+    /// Parquet hashes the plain-encoded bytes with xxh64 and seed 0, independent of the
+    /// filter's own seed. Use this instead of `insert_key` for filters written to Parquet.
+    #[inline]
+    pub fn insert_parquet_value<T: AsBytes + ?Sized>(&mut self, value: &T) {
+        self.insert_hash(xxh64(value.as_bytes(), 0));
+    }
+
+    /// Checks a value hashed the way Parquet readers hash it.
+    #[inline]
+    pub fn may_match_parquet_value<T: AsBytes + ?Sized>(&self, value: &T) -> bool {
+        self.may_match_hash(xxh64(value.as_bytes(), 0))
+    }
+
+    /// Converts the filter into a Parquet `Sbbf` with identical bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter uses `BlockMapping::Mixed`, which Parquet readers do not implement.
+    pub fn to_parquet_sbbf(&self) -> Sbbf {
+        assert_eq!(
+            self.mapping,
+            BlockMapping::Direct,
+            "Parquet filters use direct block mapping"
+        );
+        let mut bitset = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut bitset);
+        Sbbf::new(&bitset)
+    }
+
+    /// Converts a Parquet `Sbbf` into a filter with identical bits and seed 0.
+    pub fn from_parquet_sbbf(sbbf: &Sbbf) -> Result<Self, ParquetError> {
+        let mut bitset = Vec::new();
+        sbbf.write_bitset(&mut bitset)?;
+
+        Ok(Self::from_blocks(blocks_from_le_bytes(&bitset), 0))
+    }
+
+    /// Attaches the filter to a column chunk before it is appended to a row group.
+    ///
+    /// Any filter the Parquet writer built for the chunk is replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter uses `BlockMapping::Mixed`, like `to_parquet_sbbf`.
+    pub fn attach_to_column_chunk(&self, chunk: &mut ArrowColumnChunk) {
+        chunk.close_mut().bloom_filter = Some(self.to_parquet_sbbf());
+    }
+
+    /// Reads the filter stored for a column chunk, if it has one.
+    pub fn read_from_column_chunk<R: ChunkReader>(
+        metadata: &ColumnChunkMetaData,
+        reader: &R,
+    ) -> Result<Option<Self>, ParquetError> {
+        Sbbf::read_from_column_chunk(metadata, reader)?
+            .map(|sbbf| Self::from_parquet_sbbf(&sbbf))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{ArrayRef, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use bytes::Bytes;
+    use parquet::arrow::ArrowSchemaConverter;
+    use parquet::arrow::arrow_writer::{ArrowRowGroupWriterFactory, compute_leaves};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::file::writer::SerializedFileWriter;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let values = ["Leopold Bloom", "Molly Bloom"];
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+        for v in values {
+            bf.insert_parquet_value(v);
+        }
+
+        let field = Field::new("name", DataType::Utf8, false);
+        let schema = Arc::new(Schema::new(vec![field.clone()]));
+        let props = Arc::new(WriterProperties::default());
+        let parquet_schema = ArrowSchemaConverter::new().convert(&schema).unwrap();
+
+        let mut out = Vec::new();
+        let mut writer =
+            SerializedFileWriter::new(&mut out, parquet_schema.root_schema_ptr(), props).unwrap();
+        let factory = ArrowRowGroupWriterFactory::new(&writer, Arc::clone(&schema));
+        let mut col_writer = factory.create_column_writers(0).unwrap().remove(0);
+
+        let array: ArrayRef = Arc::new(StringArray::from(values.to_vec()));
+        for leaf in compute_leaves(&field, &array).unwrap() {
+            col_writer.write(&leaf).unwrap();
+        }
+        let mut chunk = col_writer.close().unwrap();
+        bf.attach_to_column_chunk(&mut chunk);
+
+        let mut row_group = writer.next_row_group().unwrap();
+        chunk.append_to_row_group(&mut row_group).unwrap();
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let data = Bytes::from(out);
+        let reader = SerializedFileReader::new(data.clone()).unwrap();
+        let column = reader.metadata().row_group(0).column(0);
+
+        let sbbf = Sbbf::read_from_column_chunk(column, &data)
+            .unwrap()
+            .unwrap();
+        assert!(sbbf.check("Molly Bloom"));
+
+        let loaded = BlockedBloomFilter::read_from_column_chunk(column, &data)
+            .unwrap()
+            .unwrap();
+        assert!(loaded.may_match_parquet_value("Leopold Bloom"));
+        assert!(!loaded.may_match_parquet_value("Stephen Dedalus"));
+    }
+}
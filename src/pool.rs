@@ -0,0 +1,146 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::{BlockedBloomFilter, FilterParams, HashAlgorithm};
+
+/// A pool of filters with one fixed geometry, reused to avoid repeated allocation.
+///
+/// Returned filters are zeroed lazily, when they are next handed out, so returning a filter
+/// is cheap on the request path.
+#[derive(Debug)]
+pub struct FilterPool {
+    params: FilterParams,
+    max_idle: usize,
+    idle: Mutex<Vec<BlockedBloomFilter>>,
+}
+
+impl FilterPool {
+    /// Creates a pool of filters sized like `BlockedBloomFilter::new_with_seed`.
+    ///
+    /// At most `max_idle` returned filters are kept; extra ones are dropped.
+    pub fn new(entries: usize, fpr: f64, seed: u64, max_idle: usize) -> Self {
+        Self::with_hash(entries, fpr, seed, HashAlgorithm::Xxh64, max_idle)
+    }
+
+    /// Creates a pool of filters sized like `BlockedBloomFilter::new_with_hash`.
+    pub fn with_hash(
+        entries: usize,
+        fpr: f64,
+        seed: u64,
+        hash: HashAlgorithm,
+        max_idle: usize,
+    ) -> Self {
+        let template = BlockedBloomFilter::new_with_hash(entries, fpr, seed, hash);
+        Self {
+            params: template.params(),
+            max_idle,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Takes an empty filter from the pool, allocating one if none is idle.
+    ///
+    /// The filter goes back to the pool when the guard is dropped.
+    pub fn get(&self) -> PooledFilter<'_> {
+        let filter = match self.idle.lock().unwrap().pop() {
+            Some(mut filter) => {
+                filter.clear();
+                filter
+            }
+            None => BlockedBloomFilter::with_params(self.params)
+                .expect("pool parameters come from a built filter"),
+        };
+        PooledFilter {
+            pool: self,
+            filter: Some(filter),
+        }
+    }
+
+    /// Returns a filter to the pool.
+    ///
+    /// Filters with a different geometry, seed, mapping or hash algorithm are dropped.
+    pub fn put(&self, filter: BlockedBloomFilter) {
+        if filter.params() != self.params {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_idle {
+            idle.push(filter);
+        }
+    }
+
+    /// Returns the number of idle filters held by the pool.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+/// A filter borrowed from a `FilterPool`.
+#[derive(Debug)]
+pub struct PooledFilter<'a> {
+    pool: &'a FilterPool,
+    filter: Option<BlockedBloomFilter>,
+}
+
+impl PooledFilter<'_> {
+    /// Detaches the filter so it is not returned to the pool.
+    pub fn into_inner(mut self) -> BlockedBloomFilter {
+        self.filter.take().unwrap()
+    }
+}
+
+impl Deref for PooledFilter<'_> {
+    type Target = BlockedBloomFilter;
+
+    fn deref(&self) -> &BlockedBloomFilter {
+        self.filter.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledFilter<'_> {
+    fn deref_mut(&mut self) -> &mut BlockedBloomFilter {
+        self.filter.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledFilter<'_> {
+    fn drop(&mut self) {
+        if let Some(filter) = self.filter.take() {
+            self.pool.put(filter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_reuses_cleared_filters() {
+        let pool = FilterPool::new(1000, 0.01, 0, 4);
+
+        let mut filter = pool.get();
+        filter.insert_key("Leopold Bloom");
+        let ptr = filter.blocks.as_ptr();
+        drop(filter);
+        assert_eq!(pool.idle_count(), 1);
+
+        let filter = pool.get();
+        assert_eq!(filter.blocks.as_ptr(), ptr);
+        assert!(!filter.may_match_key("Leopold Bloom"));
+
+        let detached = filter.into_inner();
+        assert_eq!(pool.idle_count(), 0);
+
+        pool.put(BlockedBloomFilter::new(10, 0.01));
+        pool.put(BlockedBloomFilter::new_with_hash(
+            1000,
+            0.01,
+            0,
+            HashAlgorithm::Xxh3,
+        ));
+        assert_eq!(pool.idle_count(), 0);
+        pool.put(detached);
+        assert_eq!(pool.idle_count(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+use std::hint::black_box;
+
+use crate::{ArchivedFilter, BlockedBloomFilter, CacheLineBlock};
+
+/// Stride of the page touches. Systems with larger pages are touched more often than needed,
+/// which costs little.
+const PAGE_BYTES: usize = 4096;
+
+impl BlockedBloomFilter {
+    /// Touches every page of the block array so its page faults happen now rather than on the
+    /// first queries or inserts that reach each page.
+    ///
+    /// Fresh filters are zeroed lazily by the OS, and even reading a zero page leaves the write
+    /// fault for the first insert, so each page is written (with its current contents). With
+    /// the `cow` feature this also unshares the blocks from any clones.
+    pub fn prefault(&mut self) {
+        let stride = PAGE_BYTES / size_of::<CacheLineBlock>();
+        for block in self.blocks_mut().iter_mut().step_by(stride) {
+            block.words[0] = black_box(block.words[0]);
+        }
+    }
+}
+
+impl ArchivedFilter<'_> {
+    /// Reads one byte of every page of the encoded filter, so a memory-mapped file is paged in
+    /// now rather than on the first queries that reach each page.
+    ///
+    /// This is the portable equivalent of mapping with `MAP_POPULATE`, for buffers mapped
+    /// without it.
+    pub fn prefault(&self) {
+        for &byte in self.as_bytes().iter().step_by(PAGE_BYTES) {
+            black_box(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefault_keeps_contents() {
+        let mut bf = BlockedBloomFilter::new(100_000, 0.01);
+        for i in 0..1000u64 {
+            bf.insert_key(&i);
+        }
+        let before = bf.to_bytes();
+        bf.prefault();
+        assert_eq!(bf.to_bytes(), before);
+        ArchivedFilter::from_bytes(&before).unwrap().prefault();
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{BlockedBloomFilter, CacheLineBlock};
+
+impl BlockedBloomFilter {
+    /// Returns a copy with every bit flipped independently with probability `p` (randomized
+    /// response), for sharing filters built from user identifiers.
+    ///
+    /// Each bit of the copy is `p`-plausibly deniable: whatever its true value, the shared
+    /// value was produced with probability at least `p`, which gives `ln((1 - p) / p)`-local
+    /// differential privacy per bit. The noise costs accuracy in both directions:
+    ///
+    /// - Inserted keys lose each of their 8 bits with probability `p`, so they go missing with
+    ///   probability `math::randomized_response_fnr(p)`.
+    /// - Absent keys match more often, at `randomized_response_fpr(p)`.
+    ///
+    /// `next_u64` supplies uniformly random words; use a cryptographically secure generator
+    /// when the guarantee matters, since predictable noise can be subtracted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is outside `[0, 0.5]`.
+    pub fn randomized_response(&self, p: f64, mut next_u64: impl FnMut() -> u64) -> Self {
+        assert!(
+            (0.0..=0.5).contains(&p),
+            "flip probability must be between 0 and 0.5"
+        );
+        let mut noisy = self.clone();
+        if p == 0.0 {
+            return noisy;
+        }
+        let total_bits = self.num_blocks as u64 * CacheLineBlock::BITS as u64;
+        let ln_keep = (1.0 - p).ln();
+        // Jump straight to the next flipped bit: gaps between flips are geometric.
+        let mut gap = || {
+            let u = ((next_u64() >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+            (u.ln() / ln_keep) as u64
+        };
+        let blocks = noisy.blocks_mut();
+        let mut bit = gap();
+        while bit < total_bits {
+            let block = (bit / CacheLineBlock::BITS as u64) as usize;
+            let word = (bit % CacheLineBlock::BITS as u64 / 32) as usize;
+            blocks[block].words[word] ^= 1 << (bit % 32);
+            bit = bit.saturating_add(1 + gap());
+        }
+        noisy
+    }
+
+    /// Returns the false positive rate a copy made by `randomized_response` with flip
+    /// probability `p` is expected to have.
+    ///
+    /// Noise sets a bit of a word with `c` of 32 bits set with probability
+    /// `(c * (1 - p) + (32 - c) * p) / 32`, and a probe checks one bit per word.
+    pub fn randomized_response_fpr(&self, p: f64) -> f64 {
+        let fpr_sum: f64 = self
+            .blocks
+            .iter()
+            .map(|block| {
+                block
+                    .words
+                    .iter()
+                    .map(|w| {
+                        let ones = w.count_ones() as f64;
+                        (ones * (1.0 - p) + (32.0 - ones) * p) / 32.0
+                    })
+                    .product::<f64>()
+            })
+            .sum();
+        fpr_sum / self.num_blocks as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+    use rand::Rng;
+
+    #[test]
+    fn test_randomized_response_matches_predicted_rates() {
+        let mut bf = BlockedBloomFilter::new(10_000, 0.01);
+        for i in 0..10_000u64 {
+            bf.insert_key(&i);
+        }
+        let mut rng = rand::rng();
+        assert_eq!(
+            bf.randomized_response(0.0, || rng.random()).blocks,
+            bf.blocks
+        );
+
+        let p = 0.02;
+        let noisy = bf.randomized_response(p, || rng.random());
+        let missing = (0..10_000u64).filter(|i| !noisy.may_match_key(i)).count() as f64;
+        let fnr = math::randomized_response_fnr(p);
+        assert!((missing / 10_000.0 - fnr).abs() < 0.03, "{missing}");
+
+        let fpr = bf.randomized_response_fpr(p);
+        assert_eq!(
+            bf.randomized_response_fpr(0.0),
+            bf.export_diagnostics().estimated_fpr
+        );
+        let matched = (10_000..110_000u64)
+            .filter(|i| noisy.may_match_key(i))
+            .count() as f64;
+        assert!(
+            (matched / 100_000.0 - fpr).abs() < fpr * 0.2,
+            "{matched} vs {fpr}"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Protobuf messages for exchanging filters, matching `proto/bloomsday.proto`.
+//!
+//! The message types are written out by hand so no `protoc` or build script is needed; keep
+//! them in sync with the schema.
+
+use std::fmt;
+
+use prost::Message;
+
+use crate::binary::blocks_from_le_bytes;
+use crate::{BlockedBloomFilter, ErrorKind, ParamsError};
+
+/// `bloomsday.v1.HashAlgorithm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum HashAlgorithm {
+    Xxh64 = 0,
+    Xxh3 = 1,
+}
+
+/// `bloomsday.v1.BlockMapping`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum BlockMapping {
+    Direct = 0,
+    Mixed = 1,
+}
+
+/// `bloomsday.v1.FilterParams`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct FilterParams {
+    #[prost(uint64, tag = "1")]
+    pub seed: u64,
+    #[prost(uint32, tag = "2")]
+    pub num_blocks: u32,
+    #[prost(uint32, tag = "3")]
+    pub probes: u32,
+    #[prost(uint32, tag = "4")]
+    pub block_bytes: u32,
+    #[prost(enumeration = "HashAlgorithm", tag = "5")]
+    pub hash: i32,
+    #[prost(enumeration = "BlockMapping", tag = "6")]
+    pub mapping: i32,
+}
+
+/// `bloomsday.v1.BloomFilter`.
+#[derive(Clone, PartialEq, Message)]
+pub struct BloomFilter {
+    #[prost(message, optional, tag = "1")]
+    pub params: Option<FilterParams>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub payload: Vec<u8>,
+}
+
+/// `bloomsday.v1.InsertRequest`.
+#[derive(Clone, PartialEq, Message)]
+pub struct InsertRequest {
+    #[prost(uint64, tag = "1")]
+    pub filter_id: u64,
+    #[prost(uint64, repeated, tag = "2")]
+    pub hashes: Vec<u64>,
+}
+
+/// `bloomsday.v1.InsertResponse`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct InsertResponse {
+    #[prost(uint64, tag = "1")]
+    pub inserted: u64,
+}
+
+/// `bloomsday.v1.MayMatchRequest`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct MayMatchRequest {
+    #[prost(uint64, tag = "1")]
+    pub filter_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub hash: u64,
+}
+
+/// `bloomsday.v1.MayMatchResponse`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct MayMatchResponse {
+    #[prost(bool, tag = "1")]
+    pub may_match: bool,
+}
+
+/// `bloomsday.v1.Probe`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct Probe {
+    #[prost(uint64, tag = "1")]
+    pub filter_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub hash: u64,
+}
+
+/// `bloomsday.v1.BulkProbeRequest`.
+#[derive(Clone, PartialEq, Message)]
+pub struct BulkProbeRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub probes: Vec<Probe>,
+}
+
+/// `bloomsday.v1.BulkProbeResponse`.
+#[derive(Clone, PartialEq, Message)]
+pub struct BulkProbeResponse {
+    #[prost(bool, repeated, tag = "1")]
+    pub results: Vec<bool>,
+}
+
+/// `bloomsday.v1.SnapshotRequest`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct SnapshotRequest {
+    #[prost(uint64, tag = "1")]
+    pub filter_id: u64,
+}
+
+/// `bloomsday.v1.SubscribeRequest`.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct SubscribeRequest {
+    #[prost(uint64, tag = "1")]
+    pub filter_id: u64,
+}
+
+/// Errors produced when decoding a filter from protobuf.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtoError {
+    /// The bytes are not a valid `BloomFilter` message.
+    Decode(prost::DecodeError),
+    /// The message has no `params`.
+    MissingParams,
+    /// The hash algorithm is unknown to this build.
+    UnknownHash(i32),
+    /// The block mapping is unknown to this build.
+    UnknownMapping(i32),
+    /// The parameters do not describe a filter this build can use.
+    Params(ParamsError),
+    /// The payload length does not match the parameters.
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(e) => write!(f, "invalid protobuf message: {e}"),
+            Self::MissingParams => write!(f, "filter message has no params"),
+            Self::UnknownHash(h) => write!(f, "unknown hash algorithm {h}"),
+            Self::UnknownMapping(m) => write!(f, "unknown block mapping {m}"),
+            Self::Params(e) => e.fmt(f),
+            Self::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes of filter data, got {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(e) => Some(e),
+            Self::Params(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ProtoError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Decode(_) | Self::MissingParams | Self::LengthMismatch { .. } => {
+                ErrorKind::Corrupt
+            }
+            Self::UnknownHash(_) | Self::UnknownMapping(_) => ErrorKind::VersionMismatch,
+            Self::Params(e) => e.kind(),
+        }
+    }
+}
+
+impl From<prost::DecodeError> for ProtoError {
+    fn from(e: prost::DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl From<ParamsError> for ProtoError {
+    fn from(e: ParamsError) -> Self {
+        Self::Params(e)
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Converts the filter to its protobuf message.
+    pub fn to_proto(&self) -> BloomFilter {
+        let params = self.params();
+        let mut payload = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut payload);
+        BloomFilter {
+            params: Some(FilterParams {
+                seed: params.seed,
+                num_blocks: params.num_blocks,
+                probes: params.probes,
+                block_bytes: params.block_bytes,
+                hash: match params.hash {
+                    crate::HashAlgorithm::Xxh64 => HashAlgorithm::Xxh64,
+                    crate::HashAlgorithm::Xxh3 => HashAlgorithm::Xxh3,
+                } as i32,
+                mapping: match params.mapping {
+                    crate::BlockMapping::Direct => BlockMapping::Direct,
+                    crate::BlockMapping::Mixed => BlockMapping::Mixed,
+                } as i32,
+            }),
+            payload,
+        }
+    }
+
+    /// Rebuilds a filter from its protobuf message.
+    pub fn from_proto(message: &BloomFilter) -> Result<Self, ProtoError> {
+        let params = message.params.ok_or(ProtoError::MissingParams)?;
+        let hash = match HashAlgorithm::try_from(params.hash) {
+            Ok(HashAlgorithm::Xxh64) => crate::HashAlgorithm::Xxh64,
+            Ok(HashAlgorithm::Xxh3) => crate::HashAlgorithm::Xxh3,
+            Err(_) => return Err(ProtoError::UnknownHash(params.hash)),
+        };
+        let mapping = match BlockMapping::try_from(params.mapping) {
+            Ok(BlockMapping::Direct) => crate::BlockMapping::Direct,
+            Ok(BlockMapping::Mixed) => crate::BlockMapping::Mixed,
+            Err(_) => return Err(ProtoError::UnknownMapping(params.mapping)),
+        };
+        let empty = Self::with_params(crate::FilterParams {
+            seed: params.seed,
+            num_blocks: params.num_blocks,
+            probes: params.probes,
+            block_bytes: params.block_bytes,
+            hash,
+            mapping,
+        })?;
+        let expected = empty.header().payload_len();
+        if message.payload.len() != expected {
+            return Err(ProtoError::LengthMismatch {
+                expected,
+                actual: message.payload.len(),
+            });
+        }
+        Ok(
+            Self::from_blocks(blocks_from_le_bytes(&message.payload), params.seed)
+                .with_mapping(mapping)
+                .with_hash(hash),
+        )
+    }
+
+    /// Encodes the filter as a protobuf `BloomFilter` message.
+    pub fn encode_proto(&self) -> Vec<u8> {
+        self.to_proto().encode_to_vec()
+    }
+
+    /// Decodes a filter from a protobuf `BloomFilter` message.
+    pub fn decode_proto(bytes: &[u8]) -> Result<Self, ProtoError> {
+        Self::from_proto(&BloomFilter::decode(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proto_round_trip() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 11);
+        bf.insert_key("Leopold Bloom");
+
+        let decoded = BlockedBloomFilter::decode_proto(&bf.encode_proto()).unwrap();
+        assert_eq!(decoded.params(), bf.params());
+        assert!(decoded.may_match_key("Leopold Bloom"));
+
+        let mut message = bf.to_proto();
+        message.payload.pop();
+        assert!(matches!(
+            BlockedBloomFilter::from_proto(&message),
+            Err(ProtoError::LengthMismatch { .. })
+        ));
+        message.params = None;
+        assert_eq!(
+            BlockedBloomFilter::from_proto(&message).unwrap_err(),
+            ProtoError::MissingParams
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{BlockedBloomFilter, CacheLineBlock, DecodeError, Error, FilterParams};
+
+/// Byte order of the block words in a raw export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    /// Least significant byte first, as in the binary format.
+    #[default]
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+impl BlockedBloomFilter {
+    /// Exports the block words without a header, in the given byte order.
+    pub fn to_raw_bytes(&self, endianness: Endianness) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.blocks.len() * size_of::<CacheLineBlock>());
+        for block in self.blocks.iter() {
+            for w in block.words {
+                out.extend_from_slice(&match endianness {
+                    Endianness::Little => w.to_le_bytes(),
+                    Endianness::Big => w.to_be_bytes(),
+                });
+            }
+        }
+        out
+    }
+
+    /// Rebuilds a filter from words exported by `to_raw_bytes` and the seed it used.
+    ///
+    /// The result uses direct mapping and xxHash64; use `from_raw_bytes_with_params` for
+    /// filters built with another mapping or hash algorithm.
+    pub fn from_raw_bytes(
+        bytes: &[u8],
+        seed: u64,
+        endianness: Endianness,
+    ) -> Result<Self, DecodeError> {
+        Ok(Self::from_blocks(
+            Self::raw_blocks_from(bytes, endianness)?,
+            seed,
+        ))
+    }
+
+    /// Rebuilds a filter from words exported by `to_raw_bytes` and the `params()` of the
+    /// filter that exported them.
+    ///
+    /// Fails with a `ParamsError` if the parameters describe no filter this build can make, or
+    /// a `DecodeError` if the words do not fill exactly `params.num_blocks` blocks.
+    pub fn from_raw_bytes_with_params(
+        bytes: &[u8],
+        params: FilterParams,
+        endianness: Endianness,
+    ) -> Result<Self, Error> {
+        let empty = Self::with_params(params)?;
+        let expected = empty.header().payload_len();
+        if bytes.len() != expected {
+            return Err(DecodeError::LengthMismatch {
+                expected,
+                actual: bytes.len(),
+            }
+            .into());
+        }
+        let blocks = Self::raw_blocks_from(bytes, endianness)?;
+        Ok(Self::from_blocks(blocks, params.seed)
+            .with_mapping(params.mapping)
+            .with_hash(params.hash))
+    }
+
+    fn raw_blocks_from(
+        bytes: &[u8],
+        endianness: Endianness,
+    ) -> Result<Vec<CacheLineBlock>, DecodeError> {
+        let block_bytes = size_of::<CacheLineBlock>();
+        if bytes.is_empty() {
+            return Err(DecodeError::NoBlocks);
+        }
+        if !bytes.len().is_multiple_of(block_bytes) {
+            return Err(DecodeError::LengthMismatch {
+                expected: bytes.len().next_multiple_of(block_bytes),
+                actual: bytes.len(),
+            });
+        }
+
+        let blocks = bytes
+            .chunks_exact(block_bytes)
+            .map(|chunk| {
+                let mut block = CacheLineBlock::default();
+                for (w, bytes) in block.words.iter_mut().zip(chunk.chunks_exact(4)) {
+                    let bytes = bytes.try_into().unwrap();
+                    *w = match endianness {
+                        Endianness::Little => u32::from_le_bytes(bytes),
+                        Endianness::Big => u32::from_be_bytes(bytes),
+                    };
+                }
+                block
+            })
+            .collect();
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashAlgorithm;
+
+    #[test]
+    fn test_raw_round_trip_in_both_orders() {
+        let mut bf = BlockedBloomFilter::new_with_seed(1000, 0.01, 3);
+        bf.insert_key("Leopold Bloom");
+
+        let le = bf.to_raw_bytes(Endianness::Little);
+        let be = bf.to_raw_bytes(Endianness::Big);
+        assert_eq!(&le[..], &bf.to_bytes()[crate::FilterHeader::LEN..]);
+        assert_eq!(be[..4], [le[3], le[2], le[1], le[0]]);
+
+        let decoded = BlockedBloomFilter::from_raw_bytes(&be, 3, Endianness::Big).unwrap();
+        assert_eq!(decoded.to_raw_bytes(Endianness::Little), le);
+        assert!(decoded.may_match_key("Leopold Bloom"));
+        assert!(BlockedBloomFilter::from_raw_bytes(&be[1..], 3, Endianness::Big).is_err());
+
+        let mut xxh3 = BlockedBloomFilter::new_with_hash(1000, 0.01, 3, HashAlgorithm::Xxh3);
+        xxh3.insert_key("Molly Bloom");
+        let raw = xxh3.to_raw_bytes(Endianness::Little);
+        let decoded =
+            BlockedBloomFilter::from_raw_bytes_with_params(&raw, xxh3.params(), Endianness::Little)
+                .unwrap();
+        assert_eq!(decoded.params(), xxh3.params());
+        assert!(decoded.may_match_key("Molly Bloom"));
+        assert!(
+            BlockedBloomFilter::from_raw_bytes_with_params(
+                &raw[32..],
+                xxh3.params(),
+                Endianness::Little
+            )
+            .is_err()
+        );
+    }
+}
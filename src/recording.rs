@@ -0,0 +1,170 @@
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{BlockedBloomFilter, Wiped};
+
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+struct Spill {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Builds a filter while recording the 64-bit hash of every insert.
+///
+/// The recorded hashes allow rebuilding the filter exactly at another size or false positive
+/// rate, or feeding them to another filter type. Hashes stay in memory up to `memory_cap`
+/// bytes; beyond that they are spilled to a file in `std::env::temp_dir()`, removed when the
+/// builder is dropped.
+///
+/// With the `zeroize` feature the in-memory hashes are wiped when spilled or dropped. Spilled
+/// hashes are only deleted, so keep `memory_cap` above the expected total when the hashes
+/// must not reach disk.
+#[derive(Debug)]
+pub struct RecordingBuilder {
+    filter: BlockedBloomFilter,
+    hashes: Wiped<Vec<u64>>,
+    memory_cap: usize,
+    spill: Option<Spill>,
+    spilled: usize,
+}
+
+impl RecordingBuilder {
+    /// Creates a builder for a filter sized like `BlockedBloomFilter::new_with_seed`.
+    pub fn new(entries: usize, fpr: f64, seed: u64, memory_cap: usize) -> Self {
+        Self {
+            filter: BlockedBloomFilter::new_with_seed(entries, fpr, seed),
+            hashes: Wiped::default(),
+            memory_cap,
+            spill: None,
+            spilled: 0,
+        }
+    }
+
+    /// Records the hash and inserts it.
+    pub fn insert_hash(&mut self, h: u64) -> io::Result<()> {
+        self.filter.insert_hash(h);
+        self.hashes.push(h);
+        if self.hashes.len() * size_of::<u64>() >= self.memory_cap {
+            self.spill_hashes()?;
+        }
+        Ok(())
+    }
+
+    /// Hashes the key, records the hash and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) -> io::Result<()> {
+        self.insert_hash(self.filter.hash_key(key))
+    }
+
+    fn spill_hashes(&mut self) -> io::Result<()> {
+        let spill = match &mut self.spill {
+            Some(spill) => spill,
+            None => {
+                let path = std::env::temp_dir().join(format!(
+                    "bloomsday-{}-{}.hashes",
+                    std::process::id(),
+                    SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+                ));
+                let file = File::options()
+                    .read(true)
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)?;
+                self.spill.insert(Spill {
+                    path,
+                    writer: BufWriter::new(file),
+                })
+            }
+        };
+        for h in self.hashes.iter() {
+            spill.writer.write_all(&h.to_le_bytes())?;
+        }
+        self.spilled += self.hashes.len();
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut *self.hashes);
+        #[cfg(not(feature = "zeroize"))]
+        self.hashes.clear();
+        Ok(())
+    }
+
+    /// Returns the filter built so far.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Returns the number of recorded hashes.
+    pub fn len(&self) -> usize {
+        self.spilled + self.hashes.len()
+    }
+
+    /// Returns `true` if nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `f` with every recorded hash, spilled ones first, in insertion order.
+    pub fn for_each_hash(&mut self, mut f: impl FnMut(u64)) -> io::Result<()> {
+        if let Some(spill) = &mut self.spill {
+            spill.writer.flush()?;
+            let mut reader = BufReader::new(File::open(&spill.path)?);
+            let mut buf = [0; size_of::<u64>()];
+            for _ in 0..self.spilled {
+                reader.read_exact(&mut buf)?;
+                f(u64::from_le_bytes(buf));
+            }
+        }
+        self.hashes.iter().copied().for_each(f);
+        Ok(())
+    }
+
+    /// Builds a new filter with the same seed for `entries` at `fpr` from the recorded hashes.
+    pub fn rebuild(&mut self, entries: usize, fpr: f64) -> io::Result<BlockedBloomFilter> {
+        let mut filter = BlockedBloomFilter::new_with_seed(entries, fpr, self.filter.seed);
+        self.for_each_hash(|h| filter.insert_hash(h))?;
+        Ok(filter)
+    }
+
+    /// Discards the recorded hashes and returns the filter.
+    pub fn finish(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for RecordingBuilder {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuild_from_spilled_hashes() {
+        let mut builder = RecordingBuilder::new(1000, 0.01, 5, 1024);
+        for i in 0..5000u64 {
+            builder.insert_key(&i).unwrap();
+        }
+        assert_eq!(builder.len(), 5000);
+        let path = builder.spill.as_ref().unwrap().path.clone();
+        assert!(path.exists());
+
+        let rebuilt = builder.rebuild(5000, 0.001).unwrap();
+        let mut expected = BlockedBloomFilter::new_with_seed(5000, 0.001, 5);
+        for i in 0..5000u64 {
+            expected.insert_key(&i);
+        }
+        assert_eq!(rebuilt.blocks[..], expected.blocks[..]);
+
+        drop(builder);
+        assert!(!path.exists());
+    }
+}
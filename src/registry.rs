@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+use crate::{BlockedBloomFilter, ErrorKind, PREFETCH_DISTANCE};
+
+/// Identifies a filter in a `FilterRegistry`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FilterId(pub u64);
+
+/// Identifies a tenant owning filters in a `FilterRegistry`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TenantId(pub u64);
+
+/// What to do when registering a tenant's filter would exceed its memory quota.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// Refuse the new filter.
+    #[default]
+    Reject,
+    /// Evict the tenant's least recently registered filters until the new one fits.
+    EvictOldest,
+}
+
+/// Per-tenant limits enforced by `FilterRegistry`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TenantQuota {
+    /// Total filter payload bytes the tenant may hold.
+    pub max_bytes: usize,
+    /// Sustained inserts per second, with bursts of up to one second's worth.
+    pub max_inserts_per_sec: Option<f64>,
+    /// What to do when a new filter does not fit in `max_bytes`.
+    pub policy: QuotaPolicy,
+}
+
+/// A tenant's current resource use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TenantUsage {
+    /// Payload bytes of the tenant's filters.
+    pub bytes: usize,
+    /// Number of filters the tenant owns.
+    pub filters: usize,
+    /// Inserts accepted through `insert_hash`.
+    pub inserts: u64,
+    /// Inserts rejected by the rate limit.
+    pub rejected_inserts: u64,
+}
+
+/// Errors produced when a registry operation is refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuotaError {
+    /// No filter is registered under the ID.
+    UnknownFilter(FilterId),
+    /// The filter does not fit in the tenant's memory quota.
+    MemoryExceeded {
+        tenant: TenantId,
+        needed: usize,
+        limit: usize,
+    },
+    /// The tenant is inserting faster than its quota allows.
+    RateExceeded(TenantId),
+    /// The ID is registered to another tenant, or outside any tenant, so the tenant may not
+    /// replace it.
+    NotOwned { tenant: TenantId, id: FilterId },
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFilter(id) => write!(f, "no filter registered under {}", id.0),
+            Self::MemoryExceeded {
+                tenant,
+                needed,
+                limit,
+            } => write!(
+                f,
+                "tenant {} needs {needed} bytes but its quota is {limit}",
+                tenant.0
+            ),
+            Self::RateExceeded(tenant) => write!(f, "tenant {} exceeded its insert rate", tenant.0),
+            Self::NotOwned { tenant, id } => {
+                write!(f, "filter {} is not owned by tenant {}", id.0, tenant.0)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuotaError {}
+
+impl QuotaError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnknownFilter(_) => ErrorKind::NotFound,
+            Self::MemoryExceeded { .. } | Self::RateExceeded(_) => ErrorKind::QuotaExceeded,
+            Self::NotOwned { .. } => ErrorKind::PermissionDenied,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TenantState {
+    quota: Option<TenantQuota>,
+    usage: TenantUsage,
+    /// The tenant's filters, least recently registered first.
+    order: Vec<FilterId>,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TenantState {
+    fn new() -> Self {
+        Self {
+            quota: None,
+            usage: TenantUsage::default(),
+            order: Vec::new(),
+            tokens: 0.0,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Takes one insert from the token bucket, refilling it for the time since the last call.
+    fn take_insert(&mut self) -> bool {
+        let Some(rate) = self.quota.and_then(|q| q.max_inserts_per_sec) else {
+            return true;
+        };
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.refilled = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// A set of filters addressed by ID, such as one filter per partition.
+///
+/// Filters registered with `insert_for_tenant` count against their tenant's `TenantQuota`,
+/// so one noisy tenant cannot take memory or insert throughput from the others.
+#[derive(Clone, Debug, Default)]
+pub struct FilterRegistry {
+    filters: HashMap<FilterId, BlockedBloomFilter>,
+    owners: HashMap<FilterId, TenantId>,
+    tenants: HashMap<TenantId, TenantState>,
+}
+
+impl FilterRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a filter outside any tenant's quota, returning the one it replaces.
+    pub fn insert(
+        &mut self,
+        id: FilterId,
+        filter: BlockedBloomFilter,
+    ) -> Option<BlockedBloomFilter> {
+        self.release(id);
+        self.filters.insert(id, filter)
+    }
+
+    /// Sets a tenant's quota. Filters it already owns are kept even if they exceed it.
+    pub fn set_quota(&mut self, tenant: TenantId, quota: TenantQuota) {
+        let state = self.tenants.entry(tenant).or_insert_with(TenantState::new);
+        state.quota = Some(quota);
+        state.tokens = quota.max_inserts_per_sec.unwrap_or(0.0);
+        state.refilled = Instant::now();
+    }
+
+    /// Returns a tenant's resource use.
+    pub fn tenant_usage(&self, tenant: TenantId) -> TenantUsage {
+        self.tenants
+            .get(&tenant)
+            .map_or_else(TenantUsage::default, |state| state.usage)
+    }
+
+    /// Registers a filter owned by `tenant`, enforcing its memory quota.
+    ///
+    /// Returns the filters removed to make room: the one previously registered under `id`, if
+    /// any, and with `QuotaPolicy::EvictOldest` the tenant's evicted filters. A tenant may
+    /// only replace its own filters; an `id` registered to another tenant, or with `insert`,
+    /// is refused with `QuotaError::NotOwned`.
+    pub fn insert_for_tenant(
+        &mut self,
+        tenant: TenantId,
+        id: FilterId,
+        filter: BlockedBloomFilter,
+    ) -> Result<Vec<(FilterId, BlockedBloomFilter)>, QuotaError> {
+        if self.filters.contains_key(&id) && self.owners.get(&id) != Some(&tenant) {
+            return Err(QuotaError::NotOwned { tenant, id });
+        }
+        let needed = filter_bytes(&filter);
+        let state = self.tenants.entry(tenant).or_insert_with(TenantState::new);
+        if let Some(quota) = state.quota {
+            // A filter being replaced, necessarily the tenant's own, frees its bytes first.
+            let replaced = self.filters.get(&id).map_or(0, filter_bytes);
+            let used = state.usage.bytes - replaced;
+            if needed > quota.max_bytes
+                || (quota.policy == QuotaPolicy::Reject && used + needed > quota.max_bytes)
+            {
+                return Err(QuotaError::MemoryExceeded {
+                    tenant,
+                    needed: used + needed,
+                    limit: quota.max_bytes,
+                });
+            }
+        }
+
+        let mut removed: Vec<(FilterId, BlockedBloomFilter)> =
+            self.remove(id).map(|old| (id, old)).into_iter().collect();
+        if let Some(quota) = self.tenants[&tenant].quota {
+            while self.tenants[&tenant].usage.bytes + needed > quota.max_bytes {
+                let oldest = self.tenants[&tenant].order[0];
+                let evicted = self.remove(oldest).expect("owned filters are registered");
+                removed.push((oldest, evicted));
+            }
+        }
+
+        let state = self
+            .tenants
+            .get_mut(&tenant)
+            .expect("tenant was just created");
+        state.usage.bytes += needed;
+        state.usage.filters += 1;
+        state.order.push(id);
+        self.owners.insert(id, tenant);
+        self.filters.insert(id, filter);
+        Ok(removed)
+    }
+
+    /// Inserts a hash into a filter, charging the insert to the filter's tenant.
+    ///
+    /// Inserts beyond the tenant's rate are rejected and counted. Inserts made through
+    /// `get_mut` bypass this accounting.
+    pub fn insert_hash(&mut self, id: FilterId, h: u64) -> Result<(), QuotaError> {
+        let filter = self
+            .filters
+            .get_mut(&id)
+            .ok_or(QuotaError::UnknownFilter(id))?;
+        if let Some(state) = self.owners.get(&id).and_then(|t| self.tenants.get_mut(t)) {
+            if !state.take_insert() {
+                state.usage.rejected_inserts += 1;
+                return Err(QuotaError::RateExceeded(self.owners[&id]));
+            }
+            state.usage.inserts += 1;
+        }
+        filter.insert_hash(h);
+        Ok(())
+    }
+
+    /// Removes a filter from its tenant's accounting.
+    fn release(&mut self, id: FilterId) {
+        let Some(tenant) = self.owners.remove(&id) else {
+            return;
+        };
+        let bytes = self.filters.get(&id).map_or(0, filter_bytes);
+        if let Some(state) = self.tenants.get_mut(&tenant) {
+            state.usage.bytes -= bytes;
+            state.usage.filters -= 1;
+            state.order.retain(|&owned| owned != id);
+        }
+    }
+
+    /// Returns the filter registered under `id`.
+    pub fn get(&self, id: FilterId) -> Option<&BlockedBloomFilter> {
+        self.filters.get(&id)
+    }
+
+    /// Returns the filter registered under `id` for inserting.
+    pub fn get_mut(&mut self, id: FilterId) -> Option<&mut BlockedBloomFilter> {
+        self.filters.get_mut(&id)
+    }
+
+    /// Unregisters a filter and returns it.
+    pub fn remove(&mut self, id: FilterId) -> Option<BlockedBloomFilter> {
+        self.release(id);
+        self.filters.remove(&id)
+    }
+
+    /// Returns the number of registered filters.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Returns true if no filters are registered.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Answers a batch of `(filter, hash)` queries, in the order given.
+    ///
+    /// Queries are grouped by filter so each filter is looked up once, and each block is
+    /// touched a few probes ahead so the cache misses overlap. The touch is a demand load of
+    /// the block's first word, not a prefetch instruction: it blocks retirement until the
+    /// line arrives, so the overlap is limited to what the CPU's reorder window can hold.
+    /// Unknown filters contain nothing.
+    pub fn multi_query(&self, queries: &[(FilterId, u64)]) -> Vec<bool> {
+        let mut order: Vec<usize> = (0..queries.len()).collect();
+        order.sort_unstable_by_key(|&i| queries[i].0);
+
+        let mut results = vec![false; queries.len()];
+        for group in order.chunk_by(|&a, &b| queries[a].0 == queries[b].0) {
+            let Some(filter) = self.filters.get(&queries[group[0]].0) else {
+                continue;
+            };
+            for &i in group.iter().take(PREFETCH_DISTANCE) {
+                filter.prefetch_hash(queries[i].1);
+            }
+            for (j, &i) in group.iter().enumerate() {
+                if let Some(&ahead) = group.get(j + PREFETCH_DISTANCE) {
+                    filter.prefetch_hash(queries[ahead].1);
+                }
+                results[i] = filter.may_match_hash(queries[i].1);
+            }
+        }
+        results
+    }
+}
+
+fn filter_bytes(filter: &BlockedBloomFilter) -> usize {
+    filter.header().payload_len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_query_matches_single_queries() {
+        let mut registry = FilterRegistry::new();
+        for id in 0..4u64 {
+            let mut bf = BlockedBloomFilter::new(1000, 0.01);
+            for i in 0..1000u64 {
+                bf.insert_hash(id * 1_000_000 + i);
+            }
+            registry.insert(FilterId(id), bf);
+        }
+
+        let queries: Vec<(FilterId, u64)> = (0..5000u64)
+            .map(|i| (FilterId(i % 5), (i % 4) * 1_000_000 + i % 1500))
+            .collect();
+        let results = registry.multi_query(&queries);
+        for (&(id, h), &hit) in queries.iter().zip(&results) {
+            assert_eq!(hit, registry.get(id).is_some_and(|f| f.may_match_hash(h)));
+        }
+        assert!(results.iter().any(|&hit| hit));
+    }
+
+    #[test]
+    fn test_tenant_quotas() {
+        let mut registry = FilterRegistry::new();
+        let filter = BlockedBloomFilter::new(1000, 0.01);
+        let size = filter.header().payload_len();
+        let (noisy, quiet) = (TenantId(1), TenantId(2));
+        registry.set_quota(
+            noisy,
+            TenantQuota {
+                max_bytes: 2 * size,
+                max_inserts_per_sec: Some(10.0),
+                policy: QuotaPolicy::EvictOldest,
+            },
+        );
+        registry.set_quota(
+            quiet,
+            TenantQuota {
+                max_bytes: size,
+                max_inserts_per_sec: None,
+                policy: QuotaPolicy::Reject,
+            },
+        );
+
+        for id in 0..3 {
+            registry
+                .insert_for_tenant(noisy, FilterId(id), filter.clone())
+                .unwrap();
+        }
+        assert!(registry.get(FilterId(0)).is_none());
+        assert_eq!(registry.tenant_usage(noisy).bytes, 2 * size);
+
+        registry
+            .insert_for_tenant(quiet, FilterId(10), filter.clone())
+            .unwrap();
+        assert!(matches!(
+            registry.insert_for_tenant(quiet, FilterId(11), filter.clone()),
+            Err(QuotaError::MemoryExceeded { .. })
+        ));
+        // Neither tenant can take over the other's filters.
+        assert_eq!(
+            registry
+                .insert_for_tenant(quiet, FilterId(1), filter.clone())
+                .unwrap_err(),
+            QuotaError::NotOwned {
+                tenant: quiet,
+                id: FilterId(1)
+            }
+        );
+        assert!(
+            registry
+                .insert_for_tenant(noisy, FilterId(10), filter.clone())
+                .is_err()
+        );
+        assert_eq!(registry.tenant_usage(quiet).filters, 1);
+        assert_eq!(registry.tenant_usage(noisy).filters, 2);
+
+        let accepted = (0..20)
+            .filter(|&h| registry.insert_hash(FilterId(1), h).is_ok())
+            .count();
+        assert_eq!(accepted, 10);
+        assert_eq!(registry.tenant_usage(noisy).rejected_inserts, 10);
+        assert!((0..20).all(|h| registry.insert_hash(FilterId(10), h).is_ok()));
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::ops::{Deref, Range};
+
+use object_store::path::Path;
+use object_store::{GetOptions, ObjectMeta, ObjectStore, ObjectStoreExt};
+
+use crate::{BlockedBloomFilter, DecodeError, ErrorKind, FilterHeader};
+
+/// Errors produced when loading a filter from an object store.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The object store request failed.
+    Store(object_store::Error),
+    /// The object is not a valid filter.
+    Decode(DecodeError),
+    /// The object was replaced while it was being read. Loading again reads the new
+    /// generation.
+    Changed(object_store::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(e) => write!(f, "failed to fetch filter: {e}"),
+            Self::Decode(e) => write!(f, "failed to decode filter: {e}"),
+            Self::Changed(e) => write!(f, "filter changed while loading: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(e) | Self::Changed(e) => Some(e),
+            Self::Decode(e) => Some(e),
+        }
+    }
+}
+
+impl LoadError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Store(object_store::Error::NotFound { .. }) => ErrorKind::NotFound,
+            Self::Store(_) | Self::Changed(_) => ErrorKind::Io,
+            Self::Decode(e) => e.kind(),
+        }
+    }
+
+    /// Returns whether loading again may succeed, because the object changed mid-read.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Changed(_))
+    }
+}
+
+impl From<object_store::Error> for LoadError {
+    fn from(e: object_store::Error) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<DecodeError> for LoadError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Loads a filter written with `to_bytes` from an object store.
+    ///
+    /// The header is fetched with a ranged read and validated against the object size before
+    /// the payload is downloaded, so a wrong or truncated object fails without a full download.
+    /// Both reads are pinned to the generation the initial `HEAD` saw, by ETag and version, or
+    /// by modification time when the store reports neither; if the object is replaced in
+    /// between, the load fails with `LoadError::Changed` rather than mixing generations.
+    pub async fn load_from_object_store(
+        store: &dyn ObjectStore,
+        path: &Path,
+    ) -> Result<Self, LoadError> {
+        let meta = store.head(path).await?;
+        Self::load_object(store, &meta).await
+    }
+
+    /// Loads the object described by `meta`, see `load_from_object_store`.
+    pub(crate) async fn load_object(
+        store: &dyn ObjectStore,
+        meta: &ObjectMeta,
+    ) -> Result<Self, LoadError> {
+        let header_len = FilterHeader::LEN as u64;
+        if meta.size < header_len {
+            return Err(DecodeError::LengthMismatch {
+                expected: FilterHeader::LEN,
+                actual: meta.size as usize,
+            }
+            .into());
+        }
+
+        let header = FilterHeader::parse(&Self::get_pinned(store, meta, 0..header_len).await?)?;
+        let expected = header_len + header.payload_len() as u64;
+        if meta.size != expected {
+            return Err(DecodeError::LengthMismatch {
+                expected: expected as usize,
+                actual: meta.size as usize,
+            }
+            .into());
+        }
+
+        let payload = Self::get_pinned(store, meta, header_len..expected).await?;
+        Ok(Self::from_header_and_payload(&header, &payload)?)
+    }
+
+    /// Reads `range` of the object, failing with `LoadError::Changed` unless it is still the
+    /// generation described by `meta`.
+    async fn get_pinned(
+        store: &dyn ObjectStore,
+        meta: &ObjectMeta,
+        range: Range<u64>,
+    ) -> Result<impl Deref<Target = [u8]>, LoadError> {
+        let unpinned = meta.e_tag.is_none() && meta.version.is_none();
+        let options = GetOptions::new()
+            .with_if_match(meta.e_tag.clone())
+            .with_version(meta.version.clone())
+            .with_if_unmodified_since(unpinned.then_some(meta.last_modified))
+            .with_range(Some(range));
+        let result = async { store.get_opts(&meta.location, options).await?.bytes().await };
+        result.await.map_err(|e| match e {
+            object_store::Error::Precondition { .. } => LoadError::Changed(e),
+            e => LoadError::Store(e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use object_store::memory::InMemory;
+
+    #[test]
+    fn test_load_from_object_store() {
+        let store = InMemory::new();
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key("Leopold Bloom");
+
+        let path = Path::from("filters/bloom.bin");
+        let bytes = bf.to_bytes();
+        block_on(store.put(&path, bytes.clone().into())).unwrap();
+        let loaded = block_on(BlockedBloomFilter::load_from_object_store(&store, &path)).unwrap();
+        assert!(loaded.may_match_key("Leopold Bloom"));
+
+        let truncated = Path::from("filters/truncated.bin");
+        block_on(store.put(&truncated, bytes[..bytes.len() - 32].to_vec().into())).unwrap();
+        let err = block_on(BlockedBloomFilter::load_from_object_store(
+            &store, &truncated,
+        ));
+        assert!(matches!(
+            err,
+            Err(LoadError::Decode(DecodeError::LengthMismatch { .. }))
+        ));
+
+        // A read pinned to a replaced generation fails instead of mixing the two.
+        let stale = block_on(store.head(&path)).unwrap();
+        block_on(store.put(&path, bytes.into())).unwrap();
+        let err = block_on(BlockedBloomFilter::load_object(&store, &stale)).unwrap_err();
+        assert!(err.is_retryable(), "{err}");
+        assert!(block_on(BlockedBloomFilter::load_from_object_store(&store, &path)).is_ok());
+    }
+}
@@ -0,0 +1,198 @@
+use std::hash::Hash;
+
+use crate::xor::mix;
+use crate::{XorFingerprint, key_hash};
+
+/// Columns each key's equation spans, the bits of its coefficient row.
+const RIBBON_WIDTH: usize = 64;
+
+/// Construction attempts with new hashes before the slot array is grown.
+const ATTEMPTS_PER_SIZE: u32 = 4;
+
+/// An immutable standard ribbon filter over a set fixed at construction.
+///
+/// Each hash picks a start slot and a random 64-bit coefficient row; its fingerprint is the
+/// XOR of the slots under the row's set bits, starting there. Construction solves the
+/// resulting banded linear system by Gaussian elimination as keys arrive, then back
+/// substitution. It needs about 1.07 fingerprints per key for ten thousand keys and 1.11 for
+/// a million, against 1.25 and 1.13 for `BinaryFuseFilter`, so it is the smallest filter in
+/// this crate below a few million keys: about 8.7 bits per key at 0.39% false positives with
+/// `u8` fingerprints at ten thousand keys, or 17 at 0.0015% with `u16`. In exchange a query
+/// XORs about 32 slots rather than three, though all within 64 consecutive ones. Keys are
+/// hashed with xxh64 like `BlockedBloomFilter`, so the same seed gives the same hashes in
+/// both.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RibbonFilter<F = u8> {
+    solution: Vec<F>,
+    len: usize,
+    seed: u64,
+    mix_seed: u64,
+}
+
+/// Returns the number of slots construction starts with for `size` distinct hashes.
+///
+/// With 64-column rows the slots needed per key grow slowly with the set, by about 0.02 for
+/// every tenfold increase.
+pub(crate) fn num_slots_for(size: usize) -> usize {
+    let factor = 0.99 + 0.02 * (size.max(1000) as f64).log10();
+    ((size as f64 * factor).ceil() as usize + 32).max(RIBBON_WIDTH)
+}
+
+impl<F: XorFingerprint> RibbonFilter<F> {
+    /// Builds a filter from key hashes made with `seed`, the seed `may_match_key` hashes with.
+    ///
+    /// Duplicate hashes are ignored. Construction rehashes and retries in the rare case the
+    /// system cannot be solved, adding slots if that keeps happening.
+    pub fn from_hashes<I: IntoIterator<Item = u64>>(hashes: I, seed: u64) -> Self {
+        let mut hashes: Vec<u64> = hashes.into_iter().collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let size = hashes.len();
+        let mut num_slots = num_slots_for(size);
+        let mut coefficients = Vec::new();
+        let mut results = Vec::new();
+        let mut mix_seed = seed;
+        let mut attempts = 0;
+
+        'build: loop {
+            coefficients.clear();
+            coefficients.resize(num_slots, 0u64);
+            results.clear();
+            results.resize(num_slots, F::default());
+            for &h in &hashes {
+                let hm = mix(h, mix_seed);
+                let (mut slot, mut row) = Self::row(hm, num_slots);
+                let mut result = F::from_hash(hm);
+                // Eliminate against the rows already placed until this one has a pivot.
+                while coefficients[slot] != 0 {
+                    row ^= coefficients[slot];
+                    result = result ^ results[slot];
+                    if row == 0 {
+                        if result == F::default() {
+                            break;
+                        }
+                        attempts += 1;
+                        if attempts % ATTEMPTS_PER_SIZE == 0 {
+                            num_slots += num_slots / 32;
+                        }
+                        mix_seed = mix(mix_seed, 0x9e37_79b9_7f4a_7c15);
+                        continue 'build;
+                    }
+                    let shift = row.trailing_zeros();
+                    slot += shift as usize;
+                    row >>= shift;
+                }
+                if row != 0 {
+                    coefficients[slot] = row;
+                    results[slot] = result;
+                }
+            }
+            break;
+        }
+
+        // Solve from the last slot back, so every slot a row reaches is already known. Slots
+        // without a row are free and left zero.
+        let mut solution = vec![F::default(); num_slots];
+        for slot in (0..num_slots).rev() {
+            let mut value = results[slot];
+            let mut rest = coefficients[slot] >> 1;
+            while rest != 0 {
+                value = value ^ solution[slot + 1 + rest.trailing_zeros() as usize];
+                rest &= rest - 1;
+            }
+            solution[slot] = value;
+        }
+        Self {
+            solution,
+            len: size,
+            seed,
+            mix_seed,
+        }
+    }
+
+    /// Hashes the keys with `seed` and builds a filter from them.
+    pub fn from_keys<I>(keys: I, seed: u64) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        Self::from_hashes(keys.into_iter().map(|key| key_hash(seed, &key)), seed)
+    }
+
+    /// Picks the start slot from the high bits of the product, so the row's 64 columns fit,
+    /// and a coefficient row with its first column set.
+    #[inline]
+    fn row(hm: u64, num_slots: usize) -> (usize, u64) {
+        let starts = (num_slots - RIBBON_WIDTH + 1) as u128;
+        let start = ((hm as u128 * starts) >> 64) as usize;
+        (start, mix(hm, 0xc2b2_ae3d_27d4_eb4f) | 1)
+    }
+
+    /// Checks if the set might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let hm = mix(h, self.mix_seed);
+        let (start, mut row) = Self::row(hm, self.solution.len());
+        let mut value = F::default();
+        while row != 0 {
+            value = value ^ self.solution[start + row.trailing_zeros() as usize];
+            row &= row - 1;
+        }
+        value == F::from_hash(hm)
+    }
+
+    /// Hashes the key and checks if the set might contain it.
+    #[inline]
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(key_hash(self.seed, key))
+    }
+
+    /// Returns the number of distinct hashes the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the filter was built from no hashes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the seed keys are hashed with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the size of the solution array in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.solution.len() * size_of::<F>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinaryFuseFilter;
+
+    #[test]
+    fn test_ribbon_filter() {
+        let keys = (0..100_000u64).chain(0..1000);
+        let ribbon: RibbonFilter = RibbonFilter::from_keys(keys.clone(), 5);
+        let ribbon16 = RibbonFilter::<u16>::from_keys(keys.clone(), 5);
+        assert_eq!(ribbon.len(), 100_000);
+        assert!((0..100_000u64).all(|i| ribbon.may_match_key(&i) && ribbon16.may_match_key(&i)));
+
+        let fp = (100_000..1_100_000u64)
+            .filter(|i| ribbon.may_match_key(i))
+            .count();
+        assert!((3400..4400).contains(&fp), "{fp}");
+        let fuse: BinaryFuseFilter = BinaryFuseFilter::from_keys(keys, 5);
+        assert!(ribbon.size_in_bytes() < fuse.size_in_bytes());
+
+        for n in 0..100u64 {
+            let small: RibbonFilter = RibbonFilter::from_hashes(0..n, 0);
+            assert!((0..n).all(|h| small.may_match_hash(h)));
+        }
+    }
+}
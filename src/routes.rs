@@ -0,0 +1,203 @@
+use std::sync::{Arc, RwLock};
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{Path, RawQuery, State};
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures_util::StreamExt;
+
+use crate::{BlockedBloomFilter, FilterId, FilterRegistry};
+
+/// Response header carrying the number of probed items, since the bitmap is padded to bytes.
+pub const PROBE_COUNT_HEADER: &str = "x-probe-count";
+
+type SharedRegistry = Arc<RwLock<FilterRegistry>>;
+
+/// Returns a router serving `POST /filters/{id}/probe` over the registry's filters.
+///
+/// The request body holds one base64 item per line and is processed as it streams in, so
+/// probes of any size use constant memory besides the result. The `kind` query parameter says
+/// how items are hashed:
+///
+/// - `hash` (the default): 8 little-endian bytes of a precomputed hash.
+/// - `str`: key bytes hashed like `insert_key` hashes a `str`.
+/// - `bytes`: key bytes hashed like `insert_bytes`.
+///
+/// The response is a bitmap with bit `i % 8` of byte `i / 8` set if item `i` may be present,
+/// plus the item count in the `x-probe-count` header.
+pub fn probe_router(registry: SharedRegistry) -> Router {
+    Router::new()
+        .route("/filters/{id}/probe", post(probe))
+        .with_state(registry)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ItemKind {
+    Hash,
+    Str,
+    Bytes,
+}
+
+impl ItemKind {
+    fn from_query(query: Option<&str>) -> Option<Self> {
+        let kind = query
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("kind="));
+        match kind {
+            None | Some("hash") => Some(Self::Hash),
+            Some("str") => Some(Self::Str),
+            Some("bytes") => Some(Self::Bytes),
+            Some(_) => None,
+        }
+    }
+}
+
+/// A rejected probe, answered with a status and a plain-text message.
+struct ProbeError(StatusCode, String);
+
+impl IntoResponse for ProbeError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+/// Accumulates the result bitmap across body chunks.
+struct Probe {
+    id: FilterId,
+    kind: ItemKind,
+    bitmap: Vec<u8>,
+    count: usize,
+}
+
+impl Probe {
+    /// Probes every complete line in `lines`.
+    fn lines(&mut self, registry: &SharedRegistry, lines: &[u8]) -> Result<(), ProbeError> {
+        let registry = registry.read().unwrap();
+        let filter = registry.get(self.id).ok_or_else(|| not_found(self.id))?;
+        for line in lines.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if !line.is_empty() {
+                let hit = self.item(filter, line)?;
+                self.push(hit);
+            }
+        }
+        Ok(())
+    }
+
+    fn item(&self, filter: &BlockedBloomFilter, line: &[u8]) -> Result<bool, ProbeError> {
+        let bad = |what: &str| {
+            ProbeError(
+                StatusCode::BAD_REQUEST,
+                format!("item {}: {what}", self.count),
+            )
+        };
+        let bytes = STANDARD.decode(line).map_err(|_| bad("invalid base64"))?;
+        let h = match self.kind {
+            ItemKind::Hash => {
+                let bytes: [u8; 8] = bytes.try_into().map_err(|_| bad("hash is not 8 bytes"))?;
+                u64::from_le_bytes(bytes)
+            }
+            ItemKind::Str => filter.hash_str_bytes(&bytes),
+            ItemKind::Bytes => filter.hash_bytes(&bytes),
+        };
+        Ok(filter.may_match_hash(h))
+    }
+
+    fn push(&mut self, hit: bool) {
+        if self.count.is_multiple_of(8) {
+            self.bitmap.push(0);
+        }
+        *self.bitmap.last_mut().unwrap() |= (hit as u8) << (self.count % 8);
+        self.count += 1;
+    }
+}
+
+async fn probe(
+    State(registry): State<SharedRegistry>,
+    Path(id): Path<u64>,
+    RawQuery(query): RawQuery,
+    body: Body,
+) -> Result<Response, ProbeError> {
+    let kind = ItemKind::from_query(query.as_deref())
+        .ok_or_else(|| ProbeError(StatusCode::BAD_REQUEST, "unknown item kind".to_owned()))?;
+    let mut probe = Probe {
+        id: FilterId(id),
+        kind,
+        bitmap: Vec::new(),
+        count: 0,
+    };
+    if registry.read().unwrap().get(probe.id).is_none() {
+        return Err(not_found(probe.id));
+    }
+
+    let mut pending = Vec::new();
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| ProbeError(StatusCode::BAD_REQUEST, e.to_string()))?;
+        pending.extend_from_slice(&chunk);
+        // Keep a trailing partial line for the next chunk.
+        if let Some(end) = pending.iter().rposition(|&b| b == b'\n') {
+            probe.lines(&registry, &pending[..end])?;
+            pending.drain(..=end);
+        }
+    }
+    probe.lines(&registry, &pending)?;
+
+    let mut response = probe.bitmap.into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    response
+        .headers_mut()
+        .insert(PROBE_COUNT_HEADER, HeaderValue::from(probe.count));
+    Ok(response)
+}
+
+fn not_found(id: FilterId) -> ProbeError {
+    let message = format!("no filter registered under {}", id.0);
+    ProbeError(StatusCode::NOT_FOUND, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_probe_endpoint_returns_bitmap() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        bf.insert_key("Leopold Bloom");
+        bf.insert_hash(42);
+        let mut registry = FilterRegistry::new();
+        registry.insert(FilterId(7), bf);
+        let router = probe_router(Arc::new(RwLock::new(registry)));
+
+        let keys = ["Molly Bloom", "Leopold Bloom"]
+            .map(|k| STANDARD.encode(k))
+            .join("\n");
+        let request = Request::post("/filters/7/probe?kind=str")
+            .body(Body::from(keys))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[PROBE_COUNT_HEADER], "2");
+        let body = axum::body::to_bytes(response.into_body(), 16)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], [0b10]);
+
+        let hash = STANDARD.encode(42u64.to_le_bytes());
+        let request = Request::post("/filters/8/probe")
+            .body(Body::from(hash))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,98 @@
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::ExpiringBloomFilter;
+
+/// Owns an `ExpiringBloomFilter` and rotates its segments on a Tokio timer.
+///
+/// Callers interact with the filter through cloneable `ExpiringHandle`s. Dropping the
+/// scheduler stops rotation; outstanding handles keep the filter alive.
+#[derive(Debug)]
+pub struct RotationScheduler {
+    handle: ExpiringHandle,
+    task: JoinHandle<()>,
+}
+
+impl RotationScheduler {
+    /// Starts rotating `filter` every `period` on the current Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a Tokio runtime.
+    pub fn start(filter: ExpiringBloomFilter, period: Duration) -> Self {
+        let handle = ExpiringHandle {
+            inner: Arc::new(RwLock::new(filter)),
+        };
+        let inner = Arc::clone(&handle.inner);
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                inner.write().unwrap().rotate();
+            }
+        });
+        Self { handle, task }
+    }
+
+    /// Returns a cheap cloneable handle for inserting and querying.
+    pub fn handle(&self) -> ExpiringHandle {
+        self.handle.clone()
+    }
+}
+
+impl Drop for RotationScheduler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Shared access to a scheduled `ExpiringBloomFilter`.
+#[derive(Clone, Debug)]
+pub struct ExpiringHandle {
+    inner: Arc<RwLock<ExpiringBloomFilter>>,
+}
+
+impl ExpiringHandle {
+    /// Inserts a hash into the newest segment.
+    pub fn insert_hash(&self, h: u64) {
+        self.inner.write().unwrap().insert_hash(h);
+    }
+
+    /// Checks if any segment might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.inner.read().unwrap().may_match_hash(h)
+    }
+
+    /// Hashes the key and inserts it into the newest segment.
+    pub fn insert_key<T: Hash + ?Sized>(&self, key: &T) {
+        self.inner.write().unwrap().insert_key(key);
+    }
+
+    /// Hashes the key and checks if any segment might contain it.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.inner.read().unwrap().may_match_key(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scheduler_rotates_on_timer() {
+        let period = Duration::from_secs(60);
+        let scheduler = RotationScheduler::start(ExpiringBloomFilter::new(100, 0.01, 2), period);
+        let handle = scheduler.handle();
+        handle.insert_key("Leopold Bloom");
+
+        tokio::time::sleep(period + Duration::from_millis(1)).await;
+        assert!(handle.may_match_key("Leopold Bloom"));
+
+        tokio::time::sleep(period).await;
+        assert!(!handle.may_match_key("Leopold Bloom"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use crate::hash_compat::xxh64_bytes;
+use crate::{ArchivedFilter, BlockedBloomFilter, CacheLineBlock, DecodeError};
+
+/// Default number of blocks covered by one checksum.
+pub const DEFAULT_BLOCKS_PER_CHECKSUM: u32 = 1024;
+
+/// Checksums over consecutive ranges of a filter's blocks, taken when the filter is saved.
+///
+/// Store them next to the filter and call `verify` later to find ranges hit by bit rot in
+/// long-lived or memory-mapped copies. Each checksum is XXH64 of the range's little-endian
+/// payload bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockChecksums {
+    blocks_per_checksum: u32,
+    num_blocks: u32,
+    sums: Vec<u64>,
+}
+
+impl BlockChecksums {
+    /// Returns the number of blocks covered by each checksum (the last range may be shorter).
+    pub fn blocks_per_checksum(&self) -> u32 {
+        self.blocks_per_checksum
+    }
+
+    /// Encodes the checksums: blocks per checksum and block count (u32 each), then the sums,
+    /// all little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.sums.len() * 8);
+        out.extend_from_slice(&self.blocks_per_checksum.to_le_bytes());
+        out.extend_from_slice(&self.num_blocks.to_le_bytes());
+        for sum in &self.sums {
+            out.extend_from_slice(&sum.to_le_bytes());
+        }
+        out
+    }
+
+    /// Decodes checksums produced by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < 8 {
+            return Err(DecodeError::LengthMismatch {
+                expected: 8,
+                actual: bytes.len(),
+            });
+        }
+        let blocks_per_checksum = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let num_blocks = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if blocks_per_checksum == 0 || num_blocks == 0 {
+            return Err(DecodeError::NoBlocks);
+        }
+        let expected = 8 + num_blocks.div_ceil(blocks_per_checksum) as usize * 8;
+        if bytes.len() != expected {
+            return Err(DecodeError::LengthMismatch {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let sums = bytes[8..]
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            blocks_per_checksum,
+            num_blocks,
+            sums,
+        })
+    }
+
+    fn compute(payload: &[u8], num_blocks: u32, blocks_per_checksum: u32) -> Self {
+        let range_bytes = blocks_per_checksum as usize * size_of::<CacheLineBlock>();
+        Self {
+            blocks_per_checksum,
+            num_blocks,
+            sums: payload
+                .chunks(range_bytes)
+                .map(|range| xxh64_bytes(0, range))
+                .collect(),
+        }
+    }
+
+    /// Returns the block ranges whose checksum does not match `payload`.
+    fn corrupt_ranges(&self, payload: &[u8], num_blocks: u32) -> Vec<Range<usize>> {
+        let mut corrupt: Vec<Range<usize>> = Vec::new();
+        if num_blocks != self.num_blocks {
+            corrupt.push(0..num_blocks as usize);
+            return corrupt;
+        }
+        let per = self.blocks_per_checksum as usize;
+        let actual = Self::compute(payload, num_blocks, self.blocks_per_checksum);
+        for (i, (a, b)) in actual.sums.iter().zip(&self.sums).enumerate() {
+            if a == b {
+                continue;
+            }
+            let range = i * per..((i + 1) * per).min(num_blocks as usize);
+            match corrupt.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => corrupt.push(range),
+            }
+        }
+        corrupt
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Computes checksums over ranges of `blocks_per_checksum` blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocks_per_checksum` is zero.
+    pub fn checksums(&self, blocks_per_checksum: u32) -> BlockChecksums {
+        assert!(
+            blocks_per_checksum > 0,
+            "ranges must cover at least one block"
+        );
+        let mut payload = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut payload);
+        BlockChecksums::compute(&payload, self.num_blocks, blocks_per_checksum)
+    }
+
+    /// Returns the block ranges that no longer match checksums taken when the filter was saved.
+    ///
+    /// Adjacent corrupt ranges are merged. A filter with a different block count is reported as
+    /// corrupt throughout.
+    pub fn verify(&self, checksums: &BlockChecksums) -> Vec<Range<usize>> {
+        let mut payload = Vec::with_capacity(self.header().payload_len());
+        self.write_payload(&mut payload);
+        checksums.corrupt_ranges(&payload, self.num_blocks)
+    }
+}
+
+impl ArchivedFilter<'_> {
+    /// Like `BlockedBloomFilter::verify`, reading the archived bytes in place.
+    pub fn verify(&self, checksums: &BlockChecksums) -> Vec<Range<usize>> {
+        checksums.corrupt_ranges(self.payload(), self.num_blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_reports_corrupt_ranges() {
+        let mut bf = BlockedBloomFilter::new(10_000, 0.01);
+        for i in 0..10_000u64 {
+            bf.insert_key(&i);
+        }
+        let checksums = BlockChecksums::from_bytes(&bf.checksums(16).to_bytes()).unwrap();
+        assert!(bf.verify(&checksums).is_empty());
+
+        let mut bytes = bf.to_bytes();
+        let block_bytes = size_of::<CacheLineBlock>();
+        bytes[crate::FilterHeader::LEN + 40 * block_bytes] ^= 1;
+        bytes[crate::FilterHeader::LEN + 50 * block_bytes] ^= 1;
+        let archived = ArchivedFilter::from_bytes(&bytes).unwrap();
+        let corrupt = archived.verify(&checksums);
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(corrupt[0], 32..64);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, key_hash};
+
+/// Identifies a segment of an append-only log, ordered oldest to newest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SegmentId(pub u64);
+
+/// An existence index over the segments of an append-only log: one small filter per segment
+/// answering which segment files might contain a key.
+///
+/// Log-structured stores consult it on lookups to skip segments that certainly lack the key,
+/// and prune it as compaction deletes segments. Segments are searched newest first, the order
+/// in which a lookup wants to read them.
+#[derive(Clone, Debug)]
+pub struct SegmentedLogFilter {
+    segments: BTreeMap<SegmentId, BlockedBloomFilter>,
+    entries_per_segment: usize,
+    fpr: f64,
+    seed: u64,
+}
+
+impl SegmentedLogFilter {
+    /// Creates an empty index whose segment filters are sized for `entries_per_segment` keys
+    /// at `fpr` each.
+    pub fn new(entries_per_segment: usize, fpr: f64, seed: u64) -> Self {
+        Self {
+            segments: BTreeMap::new(),
+            entries_per_segment,
+            fpr,
+            seed,
+        }
+    }
+
+    /// Inserts a hash into `segment`'s filter, creating the filter on first use.
+    pub fn insert_hash(&mut self, segment: SegmentId, h: u64) {
+        self.segments
+            .entry(segment)
+            .or_insert_with(|| {
+                BlockedBloomFilter::new_with_seed(self.entries_per_segment, self.fpr, self.seed)
+            })
+            .insert_hash(h);
+    }
+
+    /// Hashes the key and inserts it into `segment`'s filter.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, segment: SegmentId, key: &T) {
+        self.insert_hash(segment, key_hash(self.seed, key));
+    }
+
+    /// Adds the filter of a sealed segment, for example one loaded with the segment file, and
+    /// returns the filter it replaces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter's seed differs from the index's.
+    pub fn add_segment(
+        &mut self,
+        segment: SegmentId,
+        filter: BlockedBloomFilter,
+    ) -> Option<BlockedBloomFilter> {
+        assert_eq!(filter.seed, self.seed, "segment filter uses another seed");
+        self.segments.insert(segment, filter)
+    }
+
+    /// Removes a deleted segment's filter.
+    pub fn remove_segment(&mut self, segment: SegmentId) -> Option<BlockedBloomFilter> {
+        self.segments.remove(&segment)
+    }
+
+    /// Drops the filters of all segments older than `oldest_live`, as after a compaction that
+    /// deleted them. Returns how many were dropped.
+    pub fn prune_before(&mut self, oldest_live: SegmentId) -> usize {
+        let live = self.segments.split_off(&oldest_live);
+        let pruned = self.segments.len();
+        self.segments = live;
+        pruned
+    }
+
+    /// Returns the segments whose filters might contain the hash, newest first.
+    pub fn segments_possibly_containing(&self, h: u64) -> Vec<SegmentId> {
+        self.segments
+            .iter()
+            .rev()
+            .filter(|(_, filter)| filter.may_match_hash(h))
+            .map(|(&segment, _)| segment)
+            .collect()
+    }
+
+    /// Hashes the key and finds the segments that might contain it, newest first.
+    pub fn segments_possibly_containing_key<T: Hash + ?Sized>(&self, key: &T) -> Vec<SegmentId> {
+        self.segments_possibly_containing(key_hash(self.seed, key))
+    }
+
+    /// Returns a segment's filter.
+    pub fn segment(&self, segment: SegmentId) -> Option<&BlockedBloomFilter> {
+        self.segments.get(&segment)
+    }
+
+    /// Returns the indexed segments, oldest first.
+    pub fn segment_ids(&self) -> impl Iterator<Item = SegmentId> + '_ {
+        self.segments.keys().copied()
+    }
+
+    /// Returns the number of indexed segments.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns true if no segment is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_possibly_containing() {
+        let mut index = SegmentedLogFilter::new(1000, 0.001, 4);
+        for i in 0..5000u64 {
+            index.insert_key(SegmentId(i / 1000), &i);
+        }
+        index.insert_key(SegmentId(4), &1500u64);
+        assert_eq!(
+            index.segments_possibly_containing_key(&1500u64),
+            [SegmentId(4), SegmentId(1)]
+        );
+        assert!(index.segments_possibly_containing_key(&9999u64).is_empty());
+
+        assert_eq!(index.prune_before(SegmentId(2)), 2);
+        assert!(index.remove_segment(SegmentId(4)).is_some());
+        assert_eq!(
+            index.segment_ids().collect::<Vec<_>>(),
+            [SegmentId(2), SegmentId(3)]
+        );
+        assert!(index.segments_possibly_containing_key(&1500u64).is_empty());
+    }
+}
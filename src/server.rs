@@ -0,0 +1,317 @@
+//! A gRPC service over a `FilterRegistry`, implementing `bloomsday.v1.FilterService` from
+//! `proto/bloomsday.proto`.
+//!
+//! The routing below is written out by hand in the shape `tonic-build` generates, so no
+//! `protoc` is needed. Serve `FilterServiceServer` with any tonic transport, e.g.
+//! `tonic::transport::Server::builder().add_service(server)`.
+
+use std::convert::Infallible;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+
+use tokio::sync::broadcast;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
+use tonic::body::Body;
+use tonic::codegen::{BoxFuture, BoxStream, Service, StdError, http};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::{Request, Response, Status};
+
+use crate::proto::{
+    BloomFilter, BulkProbeRequest, BulkProbeResponse, InsertRequest, InsertResponse,
+    MayMatchRequest, MayMatchResponse, SnapshotRequest, SubscribeRequest,
+};
+use crate::{FilterId, FilterRegistry, QuotaError};
+
+/// Update notifications buffered per subscriber before older ones are skipped.
+const UPDATE_BUFFER: usize = 64;
+
+/// Handles `FilterService` calls against a shared registry.
+#[derive(Clone, Debug)]
+pub struct FilterServer {
+    registry: Arc<RwLock<FilterRegistry>>,
+    updates: broadcast::Sender<FilterId>,
+}
+
+impl FilterServer {
+    /// Serves the filters in `registry`.
+    pub fn new(registry: FilterRegistry) -> Self {
+        Self {
+            registry: Arc::new(RwLock::new(registry)),
+            updates: broadcast::channel(UPDATE_BUFFER).0,
+        }
+    }
+
+    /// Returns the registry, for registering filters while serving.
+    pub fn registry(&self) -> &Arc<RwLock<FilterRegistry>> {
+        &self.registry
+    }
+
+    /// Inserts the request's hashes, charging them to the filter's tenant.
+    ///
+    /// Hashes before a rejected one stay inserted.
+    pub async fn insert(
+        &self,
+        request: Request<InsertRequest>,
+    ) -> Result<Response<InsertResponse>, Status> {
+        let request = request.into_inner();
+        let id = FilterId(request.filter_id);
+        let mut inserted = 0;
+        let result = {
+            let mut registry = self.registry.write().unwrap();
+            request.hashes.iter().try_for_each(|&h| {
+                registry.insert_hash(id, h)?;
+                inserted += 1;
+                Ok::<_, QuotaError>(())
+            })
+        };
+        if inserted > 0 {
+            // Nobody listening is fine.
+            let _ = self.updates.send(id);
+        }
+        result.map_err(quota_status)?;
+        Ok(Response::new(InsertResponse { inserted }))
+    }
+
+    /// Checks one hash against one filter.
+    pub async fn may_match(
+        &self,
+        request: Request<MayMatchRequest>,
+    ) -> Result<Response<MayMatchResponse>, Status> {
+        let request = request.into_inner();
+        let registry = self.registry.read().unwrap();
+        let filter = registry
+            .get(FilterId(request.filter_id))
+            .ok_or_else(|| not_found(request.filter_id))?;
+        Ok(Response::new(MayMatchResponse {
+            may_match: filter.may_match_hash(request.hash),
+        }))
+    }
+
+    /// Checks many (filter, hash) pairs with `FilterRegistry::multi_query`.
+    pub async fn bulk_probe(
+        &self,
+        request: Request<BulkProbeRequest>,
+    ) -> Result<Response<BulkProbeResponse>, Status> {
+        let queries: Vec<(FilterId, u64)> = request
+            .into_inner()
+            .probes
+            .iter()
+            .map(|p| (FilterId(p.filter_id), p.hash))
+            .collect();
+        let results = self.registry.read().unwrap().multi_query(&queries);
+        Ok(Response::new(BulkProbeResponse { results }))
+    }
+
+    /// Returns a copy of one filter.
+    pub async fn snapshot(
+        &self,
+        request: Request<SnapshotRequest>,
+    ) -> Result<Response<BloomFilter>, Status> {
+        snapshot(&self.registry, FilterId(request.into_inner().filter_id)).map(Response::new)
+    }
+
+    /// Streams a copy of the filter now and after every insert into it.
+    ///
+    /// Slow subscribers skip intermediate copies and always catch up to the latest one.
+    pub async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<BoxStream<BloomFilter>>, Status> {
+        let id = FilterId(request.into_inner().filter_id);
+        let updates = BroadcastStream::new(self.updates.subscribe());
+        let first = snapshot(&self.registry, id)?;
+        let registry = Arc::clone(&self.registry);
+        let later = updates.filter_map(move |update| match update {
+            Ok(updated) if updated != id => None,
+            // A lagged receiver still gets the current state.
+            _ => Some(snapshot(&registry, id)),
+        });
+        let stream = tokio_stream::once(Ok(first)).chain(later);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn snapshot(registry: &RwLock<FilterRegistry>, id: FilterId) -> Result<BloomFilter, Status> {
+    let registry = registry.read().unwrap();
+    let filter = registry.get(id).ok_or_else(|| not_found(id.0))?;
+    Ok(filter.to_proto())
+}
+
+fn not_found(id: u64) -> Status {
+    Status::not_found(format!("no filter registered under {id}"))
+}
+
+fn quota_status(e: QuotaError) -> Status {
+    match e {
+        QuotaError::UnknownFilter(id) => not_found(id.0),
+        QuotaError::MemoryExceeded { .. } | QuotaError::RateExceeded(_) => {
+            Status::resource_exhausted(e.to_string())
+        }
+        QuotaError::NotOwned { .. } => Status::permission_denied(e.to_string()),
+    }
+}
+
+/// The `bloomsday.v1.FilterService` gRPC service, routing requests to a `FilterServer`.
+#[derive(Clone, Debug)]
+pub struct FilterServiceServer {
+    inner: Arc<FilterServer>,
+}
+
+impl FilterServiceServer {
+    /// Wraps a server for use with a tonic transport.
+    pub fn new(server: FilterServer) -> Self {
+        Self {
+            inner: Arc::new(server),
+        }
+    }
+}
+
+impl NamedService for FilterServiceServer {
+    const NAME: &'static str = "bloomsday.v1.FilterService";
+}
+
+/// Adapts one `FilterServer` method to tonic's unary service trait.
+macro_rules! unary_method {
+    ($svc:ident, $method:ident, $req:ty, $resp:ty) => {
+        struct $svc(Arc<FilterServer>);
+
+        impl UnaryService<$req> for $svc {
+            type Response = $resp;
+            type Future = BoxFuture<Response<$resp>, Status>;
+
+            fn call(&mut self, request: Request<$req>) -> Self::Future {
+                let inner = Arc::clone(&self.0);
+                Box::pin(async move { inner.$method(request).await })
+            }
+        }
+    };
+}
+
+unary_method!(InsertSvc, insert, InsertRequest, InsertResponse);
+unary_method!(MayMatchSvc, may_match, MayMatchRequest, MayMatchResponse);
+unary_method!(
+    BulkProbeSvc,
+    bulk_probe,
+    BulkProbeRequest,
+    BulkProbeResponse
+);
+unary_method!(SnapshotSvc, snapshot, SnapshotRequest, BloomFilter);
+
+struct SubscribeSvc(Arc<FilterServer>);
+
+impl ServerStreamingService<SubscribeRequest> for SubscribeSvc {
+    type Response = BloomFilter;
+    type ResponseStream = BoxStream<BloomFilter>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<SubscribeRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { inner.subscribe(request).await })
+    }
+}
+
+impl<B> Service<http::Request<B>> for FilterServiceServer
+where
+    B: tonic::codegen::Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let inner = Arc::clone(&self.inner);
+        match req.uri().path() {
+            "/bloomsday.v1.FilterService/Insert" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic_prost::ProstCodec::default());
+                Ok(grpc.unary(InsertSvc(inner), req).await)
+            }),
+            "/bloomsday.v1.FilterService/MayMatch" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic_prost::ProstCodec::default());
+                Ok(grpc.unary(MayMatchSvc(inner), req).await)
+            }),
+            "/bloomsday.v1.FilterService/BulkProbe" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic_prost::ProstCodec::default());
+                Ok(grpc.unary(BulkProbeSvc(inner), req).await)
+            }),
+            "/bloomsday.v1.FilterService/Snapshot" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic_prost::ProstCodec::default());
+                Ok(grpc.unary(SnapshotSvc(inner), req).await)
+            }),
+            "/bloomsday.v1.FilterService/Subscribe" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic_prost::ProstCodec::default());
+                Ok(grpc.server_streaming(SubscribeSvc(inner), req).await)
+            }),
+            _ => Box::pin(async move { Ok(Status::unimplemented("").into_http()) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockedBloomFilter;
+
+    #[tokio::test]
+    async fn test_server_inserts_probes_and_streams_snapshots() {
+        let mut registry = FilterRegistry::new();
+        registry.insert(FilterId(1), BlockedBloomFilter::new(1000, 0.01));
+        let server = FilterServer::new(registry);
+
+        let mut updates = server
+            .subscribe(Request::new(SubscribeRequest { filter_id: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        let first = updates.next().await.unwrap().unwrap();
+        assert!(
+            !BlockedBloomFilter::from_proto(&first)
+                .unwrap()
+                .may_match_hash(42)
+        );
+
+        let inserted = server
+            .insert(Request::new(InsertRequest {
+                filter_id: 1,
+                hashes: vec![42, 43],
+            }))
+            .await
+            .unwrap();
+        assert_eq!(inserted.into_inner().inserted, 2);
+        let next = updates.next().await.unwrap().unwrap();
+        assert!(
+            BlockedBloomFilter::from_proto(&next)
+                .unwrap()
+                .may_match_hash(42)
+        );
+
+        let probes = BulkProbeRequest {
+            probes: vec![
+                crate::proto::Probe {
+                    filter_id: 1,
+                    hash: 43,
+                },
+                crate::proto::Probe {
+                    filter_id: 2,
+                    hash: 43,
+                },
+            ],
+        };
+        let results = server.bulk_probe(Request::new(probes)).await.unwrap();
+        assert_eq!(results.into_inner().results, [true, false]);
+
+        let missing = server
+            .may_match(Request::new(MayMatchRequest {
+                filter_id: 2,
+                hash: 42,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+    }
+}
@@ -0,0 +1,214 @@
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::BlockedBloomFilter;
+
+/// Disagreement and latency counters collected by a `ShadowedFilter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShadowStats {
+    /// Queries answered.
+    pub queries: u64,
+    /// Queries only the primary matched: false positives the candidate avoided, or, if the
+    /// candidate missed inserts, keys it would lose.
+    pub primary_only: u64,
+    /// Queries only the candidate matched: false positives the candidate added.
+    pub candidate_only: u64,
+    /// Queries whose latency was measured.
+    pub timed_queries: u64,
+    /// Total time the primary spent on the timed queries.
+    pub primary_nanos: u64,
+    /// Total time the candidate spent on the timed queries.
+    pub candidate_nanos: u64,
+}
+
+impl ShadowStats {
+    /// Returns the fraction of queries the two filters answered differently.
+    pub fn disagreement_rate(&self) -> f64 {
+        if self.queries == 0 {
+            return 0.0;
+        }
+        (self.primary_only + self.candidate_only) as f64 / self.queries as f64
+    }
+
+    /// Returns the primary's mean latency over the timed queries.
+    pub fn primary_latency(&self) -> Option<Duration> {
+        self.mean(self.primary_nanos)
+    }
+
+    /// Returns the candidate's mean latency over the timed queries.
+    pub fn candidate_latency(&self) -> Option<Duration> {
+        self.mean(self.candidate_nanos)
+    }
+
+    fn mean(&self, nanos: u64) -> Option<Duration> {
+        (self.timed_queries > 0).then(|| Duration::from_nanos(nanos / self.timed_queries))
+    }
+}
+
+/// A filter that mirrors its traffic to a candidate configuration, for trying a new geometry,
+/// rate, mapping or hash algorithm in production before switching to it.
+///
+/// Inserts go to both filters. Queries are answered by the primary alone, so callers see no
+/// change in behavior, and the candidate's answer is only compared with it. Every
+/// `time_every`-th query is also timed on both filters; timing costs two clock reads per
+/// filter, so keep the interval large on hot paths. When the stats look right, `promote`
+/// returns the candidate, already holding every key inserted since it was attached.
+///
+/// Keys are hashed separately for each filter, so the two may use different seeds or hash
+/// algorithms. The `_hash` methods pass the same hash to both and need them to agree.
+#[derive(Debug)]
+pub struct ShadowedFilter {
+    primary: BlockedBloomFilter,
+    candidate: BlockedBloomFilter,
+    time_every: u64,
+    queries: AtomicU64,
+    primary_only: AtomicU64,
+    candidate_only: AtomicU64,
+    timed_queries: AtomicU64,
+    primary_nanos: AtomicU64,
+    candidate_nanos: AtomicU64,
+}
+
+impl ShadowedFilter {
+    /// Mirrors `primary`'s traffic to `candidate`, timing one in `time_every` queries.
+    ///
+    /// The candidate should start with the primary's keys, for instance by being built from
+    /// the same source, or its disagreements will include keys it never saw.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `time_every` is zero.
+    pub fn new(
+        primary: BlockedBloomFilter,
+        candidate: BlockedBloomFilter,
+        time_every: u32,
+    ) -> Self {
+        assert!(time_every > 0, "timing interval must be positive");
+        Self {
+            primary,
+            candidate,
+            time_every: time_every as u64,
+            queries: AtomicU64::new(0),
+            primary_only: AtomicU64::new(0),
+            candidate_only: AtomicU64::new(0),
+            timed_queries: AtomicU64::new(0),
+            primary_nanos: AtomicU64::new(0),
+            candidate_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn compare(
+        &self,
+        primary: impl FnOnce(&BlockedBloomFilter) -> bool,
+        candidate: impl FnOnce(&BlockedBloomFilter) -> bool,
+    ) -> bool {
+        let n = self.queries.fetch_add(1, Ordering::Relaxed);
+        let (hit, shadow_hit) = if n.is_multiple_of(self.time_every) {
+            let start = Instant::now();
+            let hit = primary(&self.primary);
+            let split = Instant::now();
+            let shadow_hit = candidate(&self.candidate);
+            let end = Instant::now();
+            self.timed_queries.fetch_add(1, Ordering::Relaxed);
+            self.primary_nanos
+                .fetch_add((split - start).as_nanos() as u64, Ordering::Relaxed);
+            self.candidate_nanos
+                .fetch_add((end - split).as_nanos() as u64, Ordering::Relaxed);
+            (hit, shadow_hit)
+        } else {
+            (primary(&self.primary), candidate(&self.candidate))
+        };
+        match (hit, shadow_hit) {
+            (true, false) => self.primary_only.fetch_add(1, Ordering::Relaxed),
+            (false, true) => self.candidate_only.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+        hit
+    }
+
+    /// Checks if the primary might contain the hash, comparing with the candidate.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.compare(|f| f.may_match_hash(h), |f| f.may_match_hash(h))
+    }
+
+    /// Hashes the key for each filter and checks if the primary might contain it, comparing
+    /// with the candidate.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.compare(|f| f.may_match_key(key), |f| f.may_match_key(key))
+    }
+
+    /// Inserts a hash into both filters.
+    pub fn insert_hash(&mut self, h: u64) {
+        self.primary.insert_hash(h);
+        self.candidate.insert_hash(h);
+    }
+
+    /// Hashes the key for each filter and inserts it into both.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.primary.insert_key(key);
+        self.candidate.insert_key(key);
+    }
+
+    /// Returns the counters collected so far.
+    pub fn stats(&self) -> ShadowStats {
+        ShadowStats {
+            queries: self.queries.load(Ordering::Relaxed),
+            primary_only: self.primary_only.load(Ordering::Relaxed),
+            candidate_only: self.candidate_only.load(Ordering::Relaxed),
+            timed_queries: self.timed_queries.load(Ordering::Relaxed),
+            primary_nanos: self.primary_nanos.load(Ordering::Relaxed),
+            candidate_nanos: self.candidate_nanos.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the filter answering queries.
+    pub fn primary(&self) -> &BlockedBloomFilter {
+        &self.primary
+    }
+
+    /// Returns the filter being evaluated.
+    pub fn candidate(&self) -> &BlockedBloomFilter {
+        &self.candidate
+    }
+
+    /// Stops shadowing and returns the candidate, to answer queries from now on.
+    pub fn promote(self) -> BlockedBloomFilter {
+        self.candidate
+    }
+
+    /// Stops shadowing and returns the primary, dropping the candidate.
+    pub fn into_inner(self) -> BlockedBloomFilter {
+        self.primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashAlgorithm;
+
+    #[test]
+    fn test_shadow_compares_configurations() {
+        let primary = BlockedBloomFilter::new(10_000, 0.05);
+        let candidate = BlockedBloomFilter::new_with_hash(10_000, 0.001, 7, HashAlgorithm::Xxh3);
+        let mut shadowed = ShadowedFilter::new(primary, candidate, 100);
+        (0..10_000u64).for_each(|i| shadowed.insert_key(&i));
+
+        assert!((0..10_000u64).all(|i| shadowed.may_match_key(&i)));
+        let fp = (10_000..110_000u64)
+            .filter(|i| shadowed.may_match_key(i))
+            .count() as u64;
+
+        let stats = shadowed.stats();
+        assert_eq!(stats.queries, 110_000);
+        assert_eq!(stats.timed_queries, 1100);
+        assert!(stats.primary_latency().is_some());
+        // The primary answers, so its false positives are what callers saw.
+        assert!(stats.primary_only > fp * 9 / 10, "{stats:?}");
+        assert!(stats.candidate_only < 200, "{stats:?}");
+
+        let promoted = shadowed.promote();
+        assert!((0..10_000u64).all(|i| promoted.may_match_key(&i)));
+    }
+}
@@ -0,0 +1,141 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, DecodeError};
+
+/// One filter per key prefix, such as a tenant ID, behind a single insert and query API.
+///
+/// Shards are created on first insert, each sized for `entries_per_shard` at the shared
+/// false positive rate, so a large tenant cannot use up a small tenant's FPR budget. Shards
+/// can be serialized and loaded individually.
+#[derive(Clone, Debug)]
+pub struct PrefixShardedFilter<P = String> {
+    shards: HashMap<P, BlockedBloomFilter>,
+    entries_per_shard: usize,
+    fpr: f64,
+    seed: u64,
+}
+
+impl<P: Hash + Eq> PrefixShardedFilter<P> {
+    /// Creates an empty family whose shards are sized for `entries_per_shard` at `fpr`.
+    pub fn new(entries_per_shard: usize, fpr: f64) -> Self {
+        Self::new_with_seed(entries_per_shard, fpr, 0)
+    }
+
+    /// Creates an empty family whose shards use a custom seed.
+    pub fn new_with_seed(entries_per_shard: usize, fpr: f64, seed: u64) -> Self {
+        Self {
+            shards: HashMap::new(),
+            entries_per_shard,
+            fpr,
+            seed,
+        }
+    }
+
+    /// Hashes the key and inserts it into the prefix's shard, creating the shard if needed.
+    pub fn insert_key<Q, T>(&mut self, prefix: &Q, key: &T)
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = P> + ?Sized,
+        T: Hash + ?Sized,
+    {
+        let shard = match self.shards.get_mut(prefix) {
+            Some(shard) => shard,
+            None => self.shards.entry(prefix.to_owned()).or_insert_with(|| {
+                BlockedBloomFilter::new_with_seed(self.entries_per_shard, self.fpr, self.seed)
+            }),
+        };
+        shard.insert_key(key);
+    }
+
+    /// Hashes the key and checks if the prefix's shard might contain it.
+    ///
+    /// Prefixes without a shard contain nothing.
+    pub fn may_match_key<Q, T>(&self, prefix: &Q, key: &T) -> bool
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        T: Hash + ?Sized,
+    {
+        self.shards
+            .get(prefix)
+            .is_some_and(|shard| shard.may_match_key(key))
+    }
+
+    /// Returns the shard for a prefix, if it exists.
+    pub fn shard<Q>(&self, prefix: &Q) -> Option<&BlockedBloomFilter>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.get(prefix)
+    }
+
+    /// Returns the number of shards.
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Iterates over the prefixes and their shards.
+    pub fn shards(&self) -> impl Iterator<Item = (&P, &BlockedBloomFilter)> {
+        self.shards.iter()
+    }
+
+    /// Removes a prefix's shard and returns it.
+    pub fn remove_shard<Q>(&mut self, prefix: &Q) -> Option<BlockedBloomFilter>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.remove(prefix)
+    }
+
+    /// Encodes a prefix's shard in the binary format.
+    pub fn shard_to_bytes<Q>(&self, prefix: &Q) -> Option<Vec<u8>>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.get(prefix).map(BlockedBloomFilter::to_bytes)
+    }
+
+    /// Decodes a shard produced by `shard_to_bytes` and installs it for `prefix`.
+    ///
+    /// Returns the shard it replaces, if any.
+    pub fn load_shard(
+        &mut self,
+        prefix: P,
+        bytes: &[u8],
+    ) -> Result<Option<BlockedBloomFilter>, DecodeError> {
+        let shard = BlockedBloomFilter::from_bytes(bytes)?;
+        Ok(self.shards.insert(prefix, shard))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shards_are_isolated_and_portable() {
+        let mut family: PrefixShardedFilter = PrefixShardedFilter::new(1000, 0.01);
+        family.insert_key("tenant-a", "Leopold Bloom");
+        family.insert_key("tenant-b", "Molly Bloom");
+        assert_eq!(family.num_shards(), 2);
+
+        assert!(family.may_match_key("tenant-a", "Leopold Bloom"));
+        assert!(!family.may_match_key("tenant-a", "Molly Bloom"));
+        assert!(!family.may_match_key("tenant-c", "Leopold Bloom"));
+
+        let bytes = family.shard_to_bytes("tenant-b").unwrap();
+        let mut other: PrefixShardedFilter = PrefixShardedFilter::new(1000, 0.01);
+        assert!(
+            other
+                .load_shard("tenant-b".to_owned(), &bytes)
+                .unwrap()
+                .is_none()
+        );
+        assert!(other.may_match_key("tenant-b", "Molly Bloom"));
+    }
+}
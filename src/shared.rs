@@ -0,0 +1,171 @@
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{BlockMapping, BlockedBloomFilter, CacheLineBlock, HashAlgorithm, math};
+
+#[repr(C, align(32))]
+#[derive(Debug, Default)]
+struct AtomicBlock {
+    words: [AtomicU32; 8],
+}
+
+/// A blocked Bloom filter that accepts inserts through `&self`.
+///
+/// Inserts set bits with atomic ORs, so the filter can live in an `Arc` shared by writers and
+/// readers without a lock. It is bit-compatible with `BlockedBloomFilter` and converts to and
+/// from it. A query racing with an insert of the same hash may miss it; once the insert has
+/// returned, every later query sees it.
+#[derive(Debug)]
+pub struct SharedBloomFilter {
+    blocks: Vec<AtomicBlock>,
+    num_blocks: u32,
+    seed: u64,
+    mapping: BlockMapping,
+    hash: HashAlgorithm,
+}
+
+impl SharedBloomFilter {
+    /// Creates a new filter with the given entries and false positive rate.
+    pub fn new(entries: usize, fpr: f64) -> Self {
+        Self::new_with_seed(entries, fpr, 0)
+    }
+
+    /// Creates a new filter with a custom seed.
+    pub fn new_with_seed(entries: usize, fpr: f64, seed: u64) -> Self {
+        let num_blocks = math::num_blocks_for(entries, fpr);
+        Self {
+            blocks: (0..num_blocks).map(|_| AtomicBlock::default()).collect(),
+            num_blocks,
+            seed,
+            mapping: BlockMapping::Direct,
+            hash: HashAlgorithm::Xxh64,
+        }
+    }
+
+    #[inline(always)]
+    fn block(&self, h: u64) -> &AtomicBlock {
+        let block_idx = self.mapping.block_index(h, self.num_blocks);
+        &self.blocks[block_idx]
+    }
+
+    /// Inserts a hash into the filter.
+    #[inline]
+    pub fn insert_hash(&self, h: u64) {
+        let block = self.block(h);
+        for (w, &salt) in block.words.iter().zip(BlockedBloomFilter::SALT.iter()) {
+            let idx = (h as u32).wrapping_mul(salt) >> 27;
+            let bit = 1 << idx;
+            // Skip the read-modify-write when the bit is already set to keep the line shared.
+            if w.load(Ordering::Relaxed) & bit == 0 {
+                w.fetch_or(bit, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Checks if the filter might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let block = self.block(h);
+        let check = block
+            .words
+            .iter()
+            .zip(BlockedBloomFilter::SALT.iter())
+            .fold(0u32, |acc, (w, &salt)| {
+                let idx = (h as u32).wrapping_mul(salt) >> 27;
+                acc | ((1 << idx) & !w.load(Ordering::Relaxed))
+            });
+        check == 0
+    }
+
+    /// Hashes the key and inserts it.
+    #[inline]
+    pub fn insert_key<T: Hash + ?Sized>(&self, key: &T) {
+        self.insert_hash(self.hash.hash_key(self.seed, key));
+    }
+
+    /// Hashes the key and checks if it might be present.
+    #[inline]
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.hash.hash_key(self.seed, key))
+    }
+
+    /// Zeroes every bit with atomic stores.
+    ///
+    /// Concurrent inserts may survive the clear; concurrent queries may see it half done.
+    pub fn clear(&self) {
+        for block in &self.blocks {
+            for w in &block.words {
+                w.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Copies the current bits into a `BlockedBloomFilter`.
+    pub fn snapshot(&self) -> BlockedBloomFilter {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| CacheLineBlock {
+                words: std::array::from_fn(|i| block.words[i].load(Ordering::Relaxed)),
+            })
+            .collect();
+        BlockedBloomFilter::from_blocks(blocks, self.seed)
+            .with_mapping(self.mapping)
+            .with_hash(self.hash)
+    }
+}
+
+impl From<BlockedBloomFilter> for SharedBloomFilter {
+    fn from(filter: BlockedBloomFilter) -> Self {
+        Self {
+            blocks: filter
+                .blocks
+                .iter()
+                .map(|block| AtomicBlock {
+                    words: block.words.map(AtomicU32::new),
+                })
+                .collect(),
+            num_blocks: filter.num_blocks,
+            seed: filter.seed,
+            mapping: filter.mapping,
+            hash: filter.hash,
+        }
+    }
+}
+
+impl From<SharedBloomFilter> for BlockedBloomFilter {
+    fn from(filter: SharedBloomFilter) -> Self {
+        filter.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_concurrent_inserts_match_sequential() {
+        let shared = Arc::new(SharedBloomFilter::new(10_000, 0.01));
+        std::thread::scope(|s| {
+            for t in 0..4u64 {
+                let shared = Arc::clone(&shared);
+                s.spawn(move || {
+                    for i in (t..10_000).step_by(4) {
+                        shared.insert_key(&i);
+                    }
+                });
+            }
+        });
+
+        let mut sequential = BlockedBloomFilter::new(10_000, 0.01);
+        for i in 0..10_000u64 {
+            assert!(shared.may_match_key(&i));
+            sequential.insert_key(&i);
+        }
+        assert_eq!(shared.snapshot().blocks[..], sequential.blocks[..]);
+
+        let round_trip = SharedBloomFilter::from(sequential);
+        assert!(round_trip.may_match_key(&42u64));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::FilterConfig;
+
+/// HyperLogLog precision: 2^14 registers, about 0.8% standard error.
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// Forwards to xxh64 while counting the bytes a key writes.
+struct CountingHasher {
+    inner: Xxh64,
+    bytes: usize,
+}
+
+impl Hasher for CountingHasher {
+    fn finish(&self) -> u64 {
+        self.inner.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes += bytes.len();
+        self.inner.write(bytes);
+    }
+}
+
+/// Statistics about the keys fed to a `SizingProbe`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyStats {
+    /// Number of keys observed, duplicates included.
+    pub observed: u64,
+    /// Estimated number of distinct keys.
+    pub estimated_distinct: u64,
+    /// Smallest hashed key size in bytes.
+    pub min_bytes: usize,
+    /// Largest hashed key size in bytes.
+    pub max_bytes: usize,
+    /// Mean hashed key size in bytes.
+    pub mean_bytes: f64,
+    /// Number of keys observed per Rust type name.
+    pub types: BTreeMap<&'static str, u64>,
+}
+
+/// Dry run that observes a sample of keys and recommends filter parameters.
+///
+/// Nothing is inserted anywhere: the probe estimates the number of distinct keys with a
+/// HyperLogLog sketch (16 KiB, about 0.8% standard error) and records key size and type
+/// statistics, so `entries` can be measured instead of guessed.
+#[derive(Clone, Debug)]
+pub struct SizingProbe {
+    registers: Box<[u8]>,
+    observed: u64,
+    total_bytes: u64,
+    min_bytes: usize,
+    max_bytes: usize,
+    types: BTreeMap<&'static str, u64>,
+}
+
+impl Default for SizingProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SizingProbe {
+    /// Creates an empty probe.
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; REGISTERS].into_boxed_slice(),
+            observed: 0,
+            total_bytes: 0,
+            min_bytes: usize::MAX,
+            max_bytes: 0,
+            types: BTreeMap::new(),
+        }
+    }
+
+    /// Observes a key.
+    pub fn observe<T: Hash + ?Sized>(&mut self, key: &T) {
+        let mut hasher = CountingHasher {
+            inner: Xxh64::new(0),
+            bytes: 0,
+        };
+        key.hash(&mut hasher);
+        self.observe_hash(hasher.finish());
+
+        self.total_bytes += hasher.bytes as u64;
+        self.min_bytes = self.min_bytes.min(hasher.bytes);
+        self.max_bytes = self.max_bytes.max(hasher.bytes);
+        *self.types.entry(std::any::type_name::<T>()).or_default() += 1;
+    }
+
+    /// Observes an already hashed key. Only the distinct count is updated.
+    pub fn observe_hash(&mut self, h: u64) {
+        let register = (h >> (64 - PRECISION)) as usize;
+        // Set a sentinel bit so the rank is bounded by the remaining hash bits.
+        let rest = (h << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.observed += 1;
+        let slot = &mut self.registers[register];
+        *slot = (*slot).max(rank);
+    }
+
+    /// Returns the estimated number of distinct keys observed.
+    pub fn estimated_distinct(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities.
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+        estimate.round() as u64
+    }
+
+    /// Returns the statistics gathered so far.
+    pub fn key_stats(&self) -> KeyStats {
+        let observed_keys: u64 = self.types.values().sum();
+        KeyStats {
+            observed: self.observed,
+            estimated_distinct: self.estimated_distinct(),
+            min_bytes: if observed_keys == 0 {
+                0
+            } else {
+                self.min_bytes
+            },
+            max_bytes: self.max_bytes,
+            mean_bytes: self.total_bytes as f64 / observed_keys.max(1) as f64,
+            types: self.types.clone(),
+        }
+    }
+
+    /// Recommends a filter for the observed keys at `target_fpr`.
+    ///
+    /// The distinct estimate is padded by three standard errors, so the filter is undersized
+    /// only with negligible probability.
+    pub fn recommend(&self, target_fpr: f64) -> FilterConfig {
+        let std_error = 1.04 / (REGISTERS as f64).sqrt();
+        let entries = (self.estimated_distinct() as f64 * (1.0 + 3.0 * std_error)).ceil();
+        FilterConfig::Blocked {
+            entries: (entries as usize).max(1),
+            fpr: target_fpr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_estimates_distinct_keys() {
+        let mut probe = SizingProbe::new();
+        for round in 0..3 {
+            for i in 0..200_000u64 {
+                probe.observe(&i);
+            }
+            if round == 0 {
+                probe.observe("Leopold Bloom");
+            }
+        }
+
+        let stats = probe.key_stats();
+        assert_eq!(stats.observed, 600_001);
+        let error = (stats.estimated_distinct as f64 - 200_001.0).abs() / 200_001.0;
+        assert!(error < 0.03, "{}", stats.estimated_distinct);
+        assert_eq!((stats.min_bytes, stats.max_bytes), (8, 14));
+        assert_eq!(stats.types["u64"], 600_000);
+        assert_eq!(stats.types["str"], 1);
+
+        let FilterConfig::Blocked { entries, fpr } = probe.recommend(0.01) else {
+            panic!("expected a blocked filter recommendation");
+        };
+        assert!(entries >= stats.estimated_distinct as usize);
+        assert_eq!(fpr, 0.01);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::{BlockMapping, BlockedBloomFilter, CacheLineBlock, FilterHeader, HashAlgorithm, math};
+
+/// Builds a filter from a run of sorted hashes while holding only one block in memory.
+///
+/// Blocks are chosen by the upper 32 bits of a hash, so ascending hashes visit blocks in
+/// order. The builder reads little-endian `u64` hashes (e.g. the output of an external sort)
+/// and writes the filter in the `to_bytes` format block by block, so filters far larger than
+/// the available memory can be built with sequential I/O only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortedRunBuilder {
+    num_blocks: u32,
+    seed: u64,
+}
+
+impl SortedRunBuilder {
+    /// Creates a builder for a filter sized like `BlockedBloomFilter::new_with_seed`.
+    pub fn new(entries: usize, fpr: f64, seed: u64) -> Self {
+        Self {
+            num_blocks: math::num_blocks_for(entries, fpr),
+            seed,
+        }
+    }
+
+    /// Streams the hashes from `hashes` into a filter written to `out`.
+    ///
+    /// Returns the number of hashes read. Fails with `InvalidData` if the hashes are not
+    /// sorted by block or the input ends in the middle of a hash.
+    pub fn build<R: Read, W: Write>(&self, hashes: R, out: W) -> io::Result<u64> {
+        let mut reader = BufReader::new(hashes);
+        let mut writer = BufWriter::new(out);
+
+        let header = FilterHeader {
+            version: BlockedBloomFilter::layout_version(),
+            num_blocks: self.num_blocks,
+            mapping: BlockMapping::Direct,
+            hash: HashAlgorithm::Xxh64,
+            seed: self.seed,
+        };
+        let mut bytes = Vec::with_capacity(FilterHeader::LEN);
+        header.write(&mut bytes);
+        writer.write_all(&bytes)?;
+
+        let mut block = CacheLineBlock::default();
+        let mut block_idx = 0;
+        let mut count = 0;
+        while let Some(h) = read_hash(&mut reader)? {
+            let idx = BlockMapping::Direct.block_index(h, self.num_blocks);
+            if idx < block_idx {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("hash {count} is out of order"),
+                ));
+            }
+            while block_idx < idx {
+                write_block(&mut writer, &block)?;
+                block = CacheLineBlock::default();
+                block_idx += 1;
+            }
+            for (w, &salt) in block.words.iter_mut().zip(BlockedBloomFilter::SALT.iter()) {
+                *w |= 1 << ((h as u32).wrapping_mul(salt) >> 27);
+            }
+            count += 1;
+        }
+        while block_idx < self.num_blocks as usize {
+            write_block(&mut writer, &block)?;
+            block = CacheLineBlock::default();
+            block_idx += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+}
+
+pub(crate) fn read_hash<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut buf = [0; size_of::<u64>()];
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "input ends in the middle of a hash",
+                ));
+            }
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(u64::from_le_bytes(buf)))
+}
+
+pub(crate) fn write_block<W: Write>(writer: &mut W, block: &CacheLineBlock) -> io::Result<()> {
+    for w in block.words {
+        writer.write_all(&w.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted_run_matches_in_memory_build() {
+        let mut expected = BlockedBloomFilter::new_with_seed(10_000, 0.01, 9);
+        let mut hashes: Vec<u64> = (0..10_000u64).map(|i| expected.hash_key(&i)).collect();
+        hashes.iter().for_each(|&h| expected.insert_hash(h));
+        hashes.sort_unstable();
+        let run: Vec<u8> = hashes.iter().flat_map(|h| h.to_le_bytes()).collect();
+
+        let builder = SortedRunBuilder::new(10_000, 0.01, 9);
+        let mut out = Vec::new();
+        assert_eq!(builder.build(&run[..], &mut out).unwrap(), 10_000);
+        assert_eq!(out, expected.to_bytes());
+
+        hashes.reverse();
+        let reversed: Vec<u8> = hashes.iter().flat_map(|h| h.to_le_bytes()).collect();
+        let err = builder.build(&reversed[..], io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = builder.build(&run[..7], io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::sorted::{read_hash, write_block};
+use crate::{
+    BlockMapping, BlockedBloomFilter, CacheLineBlock, FilterHeader, HashAlgorithm, key_hash, math,
+};
+
+/// Builds a filter over a key set whose size is not known up front, with bounded memory.
+///
+/// Pass one appends each hash to a spool file, so the filter can be sized for the real key
+/// count. Pass two, in `finish`, writes the filter in the `to_bytes` format one slice of
+/// blocks at a time, rereading the spool for each slice and keeping only that slice in memory.
+/// A filter over billions of keys can be built with a memory budget far below its size, at
+/// the cost of one sequential read of the spool per slice.
+#[derive(Debug)]
+pub struct SpooledBuilder {
+    path: PathBuf,
+    spool: BufWriter<File>,
+    seed: u64,
+    entries: u64,
+}
+
+impl SpooledBuilder {
+    /// Creates a builder spooling to a new file at `path`, which `finish` removes.
+    pub fn create(path: impl Into<PathBuf>, seed: u64) -> io::Result<Self> {
+        let path = path.into();
+        let spool = BufWriter::new(File::create_new(&path)?);
+        Ok(Self {
+            path,
+            spool,
+            seed,
+            entries: 0,
+        })
+    }
+
+    /// Returns the spool file's path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the number of hashes spooled so far.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Spools a hash computed with the builder's seed.
+    pub fn push_hash(&mut self, h: u64) -> io::Result<()> {
+        self.spool.write_all(&h.to_le_bytes())?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Hashes the key and spools it.
+    pub fn push_key<T: Hash + ?Sized>(&mut self, key: &T) -> io::Result<()> {
+        self.push_hash(key_hash(self.seed, key))
+    }
+
+    /// Writes a filter sized for the spooled hashes at `fpr` to `out`, holding at most
+    /// `memory_budget` bytes of blocks at once, then removes the spool.
+    ///
+    /// The filter matches one built by `BlockedBloomFilter::new_with_seed` and filled with the
+    /// same hashes.
+    pub fn finish<W: Write>(mut self, fpr: f64, memory_budget: usize, out: W) -> io::Result<()> {
+        let result = self
+            .spool
+            .flush()
+            .and_then(|()| self.write_filter(fpr, memory_budget, out));
+        let removed = fs::remove_file(&self.path);
+        result.and(removed)
+    }
+
+    fn write_filter<W: Write>(&self, fpr: f64, memory_budget: usize, out: W) -> io::Result<()> {
+        let mut spool = self.spool.get_ref();
+        let num_blocks = math::num_blocks_for(self.entries as usize, fpr);
+        let slice_blocks = (memory_budget / size_of::<CacheLineBlock>()).max(1);
+
+        let mut writer = BufWriter::new(out);
+        let header = FilterHeader {
+            version: BlockedBloomFilter::layout_version(),
+            num_blocks,
+            mapping: BlockMapping::Direct,
+            hash: HashAlgorithm::Xxh64,
+            seed: self.seed,
+        };
+        let mut bytes = Vec::with_capacity(FilterHeader::LEN);
+        header.write(&mut bytes);
+        writer.write_all(&bytes)?;
+
+        let mut slice = Vec::new();
+        for start in (0..num_blocks as usize).step_by(slice_blocks) {
+            let end = (start + slice_blocks).min(num_blocks as usize);
+            slice.clear();
+            slice.resize(end - start, CacheLineBlock::default());
+            spool.rewind()?;
+            let mut reader = BufReader::new(spool);
+            while let Some(h) = read_hash(&mut reader)? {
+                let idx = BlockMapping::Direct.block_index(h, num_blocks);
+                if (start..end).contains(&idx) {
+                    let block = &mut slice[idx - start];
+                    for (w, &salt) in block.words.iter_mut().zip(BlockedBloomFilter::SALT.iter()) {
+                        *w |= 1 << ((h as u32).wrapping_mul(salt) >> 27);
+                    }
+                }
+            }
+            for block in &slice {
+                write_block(&mut writer, block)?;
+            }
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spooled_build_matches_in_memory_build() {
+        let path = std::env::temp_dir().join(format!("bloomsday-spool-{}", std::process::id()));
+        let mut builder = SpooledBuilder::create(&path, 5).unwrap();
+        let mut expected = BlockedBloomFilter::new_with_seed(10_000, 0.01, 5);
+        for i in 0..10_000u64 {
+            builder.push_key(&i).unwrap();
+            expected.insert_key(&i);
+        }
+        assert_eq!(builder.entries(), 10_000);
+
+        let mut out = Vec::new();
+        builder.finish(0.01, 1000, &mut out).unwrap();
+        assert_eq!(out, expected.to_bytes());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,172 @@
+use rusqlite::functions::{Aggregate, Context, FunctionFlags};
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::{Connection, Error, Result};
+
+use crate::{ArchivedFilter, BlockedBloomFilter, HashAlgorithm};
+
+/// Registers the `bloom_*` SQL functions on a connection.
+///
+/// Filters are passed around as BLOBs in the `to_bytes` format, so they can be stored in
+/// tables and exchanged with Rust code:
+///
+/// - `bloom_create(entries, fpr [, seed])` returns an empty filter.
+/// - `bloom_insert(filter, key)` returns the filter with `key` inserted. The result is a copy
+///   of `filter` with only the key's block changed, as SQL functions cannot modify their
+///   arguments; the filter is not decoded.
+/// - `bloom_insert_all(filter, key)` is an aggregate returning `filter` with every row's
+///   `key` inserted. It decodes the first row's filter once and encodes the result once, so
+///   use it to load many keys, e.g. `SELECT bloom_insert_all(bloom_create(1000, 0.01), url)
+///   FROM visits`. Later rows' `filter` arguments are ignored.
+/// - `bloom_may_match(filter, key)` returns 1 if `key` might be present, 0 otherwise.
+///
+/// TEXT keys hash like `&str`, INTEGER keys like `i64` and BLOB keys like `&[u8]`, matching
+/// `insert_key` and `may_match_key`. NULL keys leave the filter unchanged and match as NULL.
+pub fn register_sqlite_functions(conn: &Connection) -> Result<()> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    for n_arg in [2, 3] {
+        conn.create_scalar_function("bloom_create", n_arg, flags, |ctx| {
+            let entries: i64 = ctx.get(0)?;
+            let fpr: f64 = ctx.get(1)?;
+            let seed: i64 = if ctx.len() > 2 { ctx.get(2)? } else { 0 };
+            let entries =
+                usize::try_from(entries).map_err(|e| Error::UserFunctionError(Box::new(e)))?;
+            Ok(BlockedBloomFilter::new_with_seed(entries, fpr, seed as u64).to_bytes())
+        })?;
+    }
+    conn.create_scalar_function("bloom_insert", 2, flags, |ctx| {
+        let filter = filter_arg(ctx)?;
+        let mut bytes = filter.as_bytes().to_vec();
+        if let Some(h) = key_hash_arg(ctx, filter.hash_algorithm(), filter.seed())? {
+            filter.insert_hash_into(&mut bytes, h);
+        }
+        Ok(bytes)
+    })?;
+    conn.create_aggregate_function("bloom_insert_all", 2, flags, InsertAll)?;
+    conn.create_scalar_function("bloom_may_match", 2, flags, |ctx| {
+        let filter = filter_arg(ctx)?;
+        Ok(key_hash_arg(ctx, filter.hash_algorithm(), filter.seed())?
+            .map(|h| filter.may_match_hash(h)))
+    })?;
+    Ok(())
+}
+
+/// The `bloom_insert_all` aggregate, holding the decoded filter between rows.
+struct InsertAll;
+
+impl Aggregate<BlockedBloomFilter, Option<Vec<u8>>> for InsertAll {
+    fn init(&self, ctx: &mut Context<'_>) -> Result<BlockedBloomFilter> {
+        Ok(filter_arg(ctx)?.to_filter())
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, filter: &mut BlockedBloomFilter) -> Result<()> {
+        if let Some(h) = key_hash_arg(ctx, filter.hash, filter.seed)? {
+            filter.insert_hash(h);
+        }
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        _: &mut Context<'_>,
+        filter: Option<BlockedBloomFilter>,
+    ) -> Result<Option<Vec<u8>>> {
+        Ok(filter.map(|filter| filter.to_bytes()))
+    }
+}
+
+fn filter_arg<'a>(ctx: &'a Context<'_>) -> Result<ArchivedFilter<'a>> {
+    let bytes = ctx.get_raw(0).as_blob()?;
+    ArchivedFilter::from_bytes(bytes).map_err(|e| Error::UserFunctionError(Box::new(e)))
+}
+
+fn key_hash_arg(ctx: &Context<'_>, hash: HashAlgorithm, seed: u64) -> Result<Option<u64>> {
+    Ok(match ctx.get_raw(1) {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(hash.hash_key(seed, &i)),
+        ValueRef::Text(bytes) => Some(hash.hash_str_bytes(seed, bytes)),
+        ValueRef::Blob(bytes) => Some(hash.hash_key(seed, bytes)),
+        ValueRef::Real(_) => return Err(Error::InvalidFunctionParameterType(1, Type::Real)),
+    })
+}
+
+/// Entry point used by SQLite's `load_extension` for a cdylib named `bloomsday`.
+///
+/// # Safety
+///
+/// Must only be called by SQLite while loading the extension.
+#[cfg(feature = "sqlite-extension")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sqlite3_bloomsday_init(
+    db: *mut rusqlite::ffi::sqlite3,
+    pz_err_msg: *mut *mut std::os::raw::c_char,
+    p_api: *mut rusqlite::ffi::sqlite3_api_routines,
+) -> std::os::raw::c_int {
+    // SAFETY: the arguments come straight from SQLite's extension loader.
+    unsafe {
+        Connection::extension_init2(db, pz_err_msg, p_api, |conn| {
+            register_sqlite_functions(&conn)?;
+            Ok(false)
+        })
+    }
+}
+
+// Connections cannot be opened directly once SQLite calls are routed through an extension's
+// API table.
+#[cfg(all(test, not(feature = "sqlite-extension")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sql_functions_match_rust_filter() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_sqlite_functions(&conn).unwrap();
+
+        let bytes: Vec<u8> = conn
+            .query_row(
+                "SELECT bloom_insert(bloom_insert(bloom_create(1000, 0.01, 7), 'Molly'), 1904)",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        let bf = BlockedBloomFilter::from_bytes(&bytes).unwrap();
+        assert!(bf.may_match_key("Molly"));
+        assert!(bf.may_match_key(&1904i64));
+
+        let mut expected = BlockedBloomFilter::new_with_seed(1000, 0.01, 7);
+        expected.insert_key("Molly");
+        expected.insert_key(&1904i64);
+        assert_eq!(bytes, expected.to_bytes());
+
+        conn.execute_batch(
+            "CREATE TABLE people (name TEXT); \
+             INSERT INTO people VALUES ('Molly'), (NULL), ('Leopold');",
+        )
+        .unwrap();
+        let all: Vec<u8> = conn
+            .query_row(
+                "SELECT bloom_insert_all(bloom_create(1000, 0.01, 7), name) FROM people",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        let mut molly_and_leopold = BlockedBloomFilter::new_with_seed(1000, 0.01, 7);
+        molly_and_leopold.insert_key("Molly");
+        molly_and_leopold.insert_key("Leopold");
+        assert_eq!(all, molly_and_leopold.to_bytes());
+
+        let matches: (bool, bool, Option<bool>) = conn
+            .query_row(
+                "SELECT bloom_may_match(?1, 'Molly'), bloom_may_match(?1, 'Blazes'), \
+                 bloom_may_match(?1, NULL)",
+                [&bytes],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(matches, (true, false, None));
+        assert!(
+            conn.query_row("SELECT bloom_may_match(x'00', 'Molly')", [], |r| r
+                .get::<_, bool>(0))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{BlockedBloomFilter, CacheLineBlock};
+
+/// A filter over a fixed set of strings, built at compile time and stored in the binary.
+///
+/// Meant for small embedded sets such as reserved words or blocklists, where building a
+/// filter at startup is wasted work. Construct one with `bloom_static!`, which evaluates
+/// `build` in a constant so the blocks land in read-only data with no startup cost:
+///
+/// ```
+/// use bloomsday::{StaticBloom, bloom_static};
+///
+/// static RESERVED: StaticBloom = bloom_static!["fn", "let", "match"];
+/// assert!(RESERVED.may_match_str("let"));
+/// ```
+///
+/// Keys are hashed as raw bytes with `hash_compat::xxh64_bytes`, like
+/// `BlockedBloomFilter::insert_bytes`, and sized at `BITS_PER_KEY`, about a 1% false positive
+/// rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaticBloom {
+    blocks: &'static [[u32; 8]],
+    seed: u64,
+}
+
+impl StaticBloom {
+    /// Bits allocated per key, `math::bits_per_key(0.01)`.
+    pub const BITS_PER_KEY: usize = 10;
+
+    /// Returns the number of blocks `build` needs for `keys` keys.
+    pub const fn blocks_for(keys: usize) -> usize {
+        let blocks = (keys * Self::BITS_PER_KEY).div_ceil(CacheLineBlock::BITS);
+        if blocks == 0 { 1 } else { blocks }
+    }
+
+    /// Builds the blocks of a filter over `keys`, usually in a constant. `N` must be
+    /// `blocks_for(keys.len())` for the intended false positive rate, though any positive `N`
+    /// gives a working filter.
+    pub const fn build<const N: usize>(keys: &[&str], seed: u64) -> [[u32; 8]; N] {
+        let mut blocks = [[0; 8]; N];
+        let mut k = 0;
+        while k < keys.len() {
+            let h = xxhash_rust::const_xxh64::xxh64(keys[k].as_bytes(), seed);
+            let block = (((h >> 32) * N as u64) >> 32) as usize;
+            let mut word = 0;
+            while word < 8 {
+                let bit = (h as u32).wrapping_mul(BlockedBloomFilter::SALT[word]) >> 27;
+                blocks[block][word] |= 1 << bit;
+                word += 1;
+            }
+            k += 1;
+        }
+        blocks
+    }
+
+    /// Wraps blocks made by `build` with the same seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocks` is empty.
+    pub const fn from_blocks(blocks: &'static [[u32; 8]], seed: u64) -> Self {
+        assert!(!blocks.is_empty(), "a filter needs at least one block");
+        Self { blocks, seed }
+    }
+
+    /// Checks if the filter might contain the hash, computed with `xxh64_bytes` and the seed.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let block = &self.blocks[(((h >> 32) * self.blocks.len() as u64) >> 32) as usize];
+        block
+            .iter()
+            .zip(BlockedBloomFilter::SALT.iter())
+            .all(|(&w, &salt)| w & (1 << ((h as u32).wrapping_mul(salt) >> 27)) != 0)
+    }
+
+    /// Hashes raw bytes and checks if they might be present.
+    #[inline]
+    pub fn may_match_bytes(&self, bytes: &[u8]) -> bool {
+        self.may_match_hash(crate::hash_compat::xxh64_bytes(self.seed, bytes))
+    }
+
+    /// Checks if the string might be present.
+    #[inline]
+    pub fn may_match_str(&self, key: &str) -> bool {
+        self.may_match_bytes(key.as_bytes())
+    }
+
+    /// Copies the filter into an owned, mutable `BlockedBloomFilter`.
+    pub fn to_filter(&self) -> BlockedBloomFilter {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|&words| CacheLineBlock { words })
+            .collect();
+        BlockedBloomFilter::from_blocks(blocks, self.seed)
+    }
+}
+
+/// Builds a `StaticBloom` over string literals at compile time, hashed with seed 0.
+///
+/// Usable as the initializer of a `static`.
+#[macro_export]
+macro_rules! bloom_static {
+    ($($key:expr),* $(,)?) => {{
+        const KEYS: &[&str] = &[$($key),*];
+        const BLOCKS: [[u32; 8]; $crate::StaticBloom::blocks_for(KEYS.len())] =
+            $crate::StaticBloom::build(KEYS, 0);
+        $crate::StaticBloom::from_blocks(&BLOCKS, 0)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SURNAMES: StaticBloom = bloom_static!["Bloom", "Dedalus", "Mulligan", "Boylan"];
+
+    #[test]
+    fn test_static_filter_matches_runtime_build() {
+        let names = ["Bloom", "Dedalus", "Mulligan", "Boylan"];
+        assert!(names.iter().all(|name| SURNAMES.may_match_str(name)));
+        assert!(!SURNAMES.may_match_str("Earwicker"));
+
+        let mut runtime = BlockedBloomFilter::new(names.len(), 0.01);
+        names
+            .iter()
+            .for_each(|name| runtime.insert_bytes(name.as_bytes()));
+        assert_eq!(SURNAMES.to_filter().to_bytes(), runtime.to_bytes());
+        assert!(!bloom_static![].may_match_str("Bloom"));
+    }
+}
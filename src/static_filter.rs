@@ -0,0 +1,241 @@
+use std::convert::Infallible;
+use std::future::{Future, ready};
+use std::hash::Hash;
+
+use crate::fuse::geometry_for;
+use crate::ribbon::num_slots_for;
+use crate::xor::block_length_for;
+use crate::{
+    ApproxMembership, BinaryFuseFilter, BlockedBloomFilter, CacheLineBlock, ClassicBloomFilter,
+    HashAlgorithm, RibbonFilter, XorFilter8, XorFilter16, key_hash, math,
+};
+
+/// What `StaticFilterBuilder` optimizes for besides the requested false positive rate.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Prefer one cache miss per query, accepting some extra memory.
+    #[default]
+    Speed,
+    /// Prefer the smallest filter, accepting several cache misses per query.
+    Space,
+}
+
+/// A filter built by `StaticFilterBuilder`, whichever structure it chose.
+#[derive(Clone, Debug)]
+pub enum StaticFilter {
+    /// A blocked filter sized exactly for the requested rate.
+    Blocked(BlockedBloomFilter),
+    /// A classic partitioned filter.
+    Classic(ClassicBloomFilter),
+    /// An xor filter with 8-bit fingerprints, for rates of at least 1/256.
+    Xor8(XorFilter8),
+    /// An xor filter with 16-bit fingerprints, for rates of at least 1/65536.
+    Xor16(XorFilter16),
+    /// A binary fuse filter with 8-bit fingerprints, for rates of at least 1/256.
+    Fuse8(BinaryFuseFilter<u8>),
+    /// A binary fuse filter with 16-bit fingerprints, for rates of at least 1/65536.
+    Fuse16(BinaryFuseFilter<u16>),
+    /// A ribbon filter with 8-bit fingerprints, for rates of at least 1/256.
+    Ribbon8(RibbonFilter<u8>),
+    /// A ribbon filter with 16-bit fingerprints, for rates of at least 1/65536.
+    Ribbon16(RibbonFilter<u16>),
+}
+
+impl StaticFilter {
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        match self {
+            Self::Blocked(filter) => filter.may_match_hash(h),
+            Self::Classic(filter) => filter.may_match_hash(h),
+            Self::Xor8(filter) => filter.contains_hash(h),
+            Self::Xor16(filter) => filter.contains_hash(h),
+            Self::Fuse8(filter) => filter.may_match_hash(h),
+            Self::Fuse16(filter) => filter.may_match_hash(h),
+            Self::Ribbon8(filter) => filter.may_match_hash(h),
+            Self::Ribbon16(filter) => filter.may_match_hash(h),
+        }
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(key_hash(ApproxMembership::seed(self), key))
+    }
+}
+
+impl ApproxMembership for StaticFilter {
+    type Error = Infallible;
+
+    fn seed(&self) -> u64 {
+        match self {
+            Self::Blocked(filter) => filter.seed,
+            Self::Classic(filter) => filter.seed(),
+            Self::Xor8(filter) => filter.seed(),
+            Self::Xor16(filter) => filter.seed(),
+            Self::Fuse8(filter) => filter.seed(),
+            Self::Fuse16(filter) => filter.seed(),
+            Self::Ribbon8(filter) => filter.seed(),
+            Self::Ribbon16(filter) => filter.seed(),
+        }
+    }
+
+    /// Always `Xxh64`: `StaticFilterBuilder` builds from hashes, and `may_match_key` hashes
+    /// keys with xxHash64 whatever the structure.
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Xxh64
+    }
+
+    fn may_match_hashes(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Result<Vec<bool>, Self::Error>> + Send {
+        ready(Ok(hashes.iter().map(|&h| self.may_match_hash(h)).collect()))
+    }
+}
+
+/// The structures `StaticFilterBuilder` chooses between.
+#[derive(Clone, Copy)]
+enum Structure {
+    Blocked,
+    Classic,
+    Xor8,
+    Xor16,
+    Fuse8,
+    Fuse16,
+    Ribbon8,
+    Ribbon16,
+}
+
+/// Builds a read-only filter over a known set of hashes, choosing the structure from the
+/// requested false positive rate and priority so application code need not.
+///
+/// `Space` picks the smallest of the classic filter and, when their fixed rate of 1/256 or
+/// 1/65536 meets `fpr`, the 8- and 16-bit xor, binary fuse and ribbon filters. Ribbon filters
+/// are the smallest up to several million keys, where binary fuse filters take over. `Speed`
+/// picks a blocked filter sized with `math::blocked_bits_for`, unless that needs more than
+/// twice the classic filter's bits, which happens at very low rates where a single block per
+/// key stops paying for itself; it then falls back to the `Space` choice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaticFilterBuilder;
+
+impl StaticFilterBuilder {
+    /// How many times the classic filter's size a blocked filter may take under `Speed`.
+    pub const MAX_BLOCKED_OVERHEAD: usize = 2;
+
+    /// Picks the structure for `entries` hashes and returns it with its size in bits.
+    fn choose(entries: usize, fpr: f64, priority: Priority) -> (Structure, usize) {
+        let blocked_bits = math::blocked_bits_for(entries, fpr);
+        let classic_bits = math::bits_for(entries, fpr);
+        if priority == Priority::Speed && blocked_bits <= classic_bits * Self::MAX_BLOCKED_OVERHEAD
+        {
+            return (Structure::Blocked, blocked_bits);
+        }
+
+        // Duplicates only shrink the xor and fuse filters, so sizing them by `entries` is
+        // conservative.
+        let xor_len = 3 * block_length_for(entries) as usize;
+        let (segment_length, segment_count) = geometry_for(entries);
+        let fuse_len = (segment_count + 2) * segment_length as usize;
+        let ribbon_len = num_slots_for(entries);
+        [
+            (Structure::Xor8, xor_len * 8, 1.0 / 256.0),
+            (Structure::Xor16, xor_len * 16, 1.0 / 65536.0),
+            (Structure::Fuse8, fuse_len * 8, 1.0 / 256.0),
+            (Structure::Fuse16, fuse_len * 16, 1.0 / 65536.0),
+            (Structure::Ribbon8, ribbon_len * 8, 1.0 / 256.0),
+            (Structure::Ribbon16, ribbon_len * 16, 1.0 / 65536.0),
+        ]
+        .into_iter()
+        .filter(|&(_, _, rate)| rate <= fpr)
+        .map(|(structure, bits, _)| (structure, bits))
+        .chain([(Structure::Classic, classic_bits)])
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+    }
+
+    /// Returns the size in bytes of the filter `from_hashes` would build over `entries`
+    /// distinct hashes.
+    pub fn expected_bytes(entries: usize, fpr: f64, priority: Priority) -> usize {
+        Self::choose(entries, fpr, priority).1.div_ceil(8)
+    }
+
+    /// Builds a filter over `hashes` at `fpr` for keys hashed with seed 0.
+    pub fn from_hashes(hashes: &[u64], fpr: f64, priority: Priority) -> StaticFilter {
+        Self::from_hashes_with_seed(hashes, fpr, priority, 0)
+    }
+
+    /// Builds a filter over `hashes`, computed with `seed`, at `fpr`.
+    pub fn from_hashes_with_seed(
+        hashes: &[u64],
+        fpr: f64,
+        priority: Priority,
+        seed: u64,
+    ) -> StaticFilter {
+        let entries = hashes.len();
+        let (structure, bits) = Self::choose(entries, fpr, priority);
+        let hashes_iter = hashes.iter().copied();
+        match structure {
+            Structure::Blocked => {
+                let blocks = vec![CacheLineBlock::default(); bits / CacheLineBlock::BITS];
+                let mut filter = BlockedBloomFilter::from_blocks(blocks, seed);
+                for &h in hashes {
+                    filter.insert_hash(h);
+                }
+                StaticFilter::Blocked(filter)
+            }
+            Structure::Xor8 => StaticFilter::Xor8(XorFilter8::from_hashes(hashes_iter, seed)),
+            Structure::Xor16 => StaticFilter::Xor16(XorFilter16::from_hashes(hashes_iter, seed)),
+            Structure::Fuse8 => {
+                StaticFilter::Fuse8(BinaryFuseFilter::from_hashes(hashes_iter, seed))
+            }
+            Structure::Fuse16 => {
+                StaticFilter::Fuse16(BinaryFuseFilter::from_hashes(hashes_iter, seed))
+            }
+            Structure::Ribbon8 => {
+                StaticFilter::Ribbon8(RibbonFilter::from_hashes(hashes_iter, seed))
+            }
+            Structure::Ribbon16 => {
+                StaticFilter::Ribbon16(RibbonFilter::from_hashes(hashes_iter, seed))
+            }
+            Structure::Classic => {
+                let mut filter = ClassicBloomFilter::new_with_seed(entries, fpr, seed);
+                for &h in hashes {
+                    filter.insert_hash(h);
+                }
+                StaticFilter::Classic(filter)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_picks_structure() {
+        let hashes: Vec<u64> = (0..10_000u64).map(|i| key_hash(0, &i)).collect();
+        let fast = StaticFilterBuilder::from_hashes(&hashes, 0.01, Priority::Speed);
+        assert!(matches!(fast, StaticFilter::Blocked(_)));
+        let small = StaticFilterBuilder::from_hashes(&hashes, 0.01, Priority::Space);
+        assert!(matches!(small, StaticFilter::Ribbon8(_)));
+        let ribbon16 = StaticFilterBuilder::from_hashes(&hashes, 1e-4, Priority::Space);
+        assert!(matches!(ribbon16, StaticFilter::Ribbon16(_)));
+        // Binary fuse filters overtake ribbon filters on very large sets.
+        let (large, _) = StaticFilterBuilder::choose(20_000_000, 0.01, Priority::Space);
+        assert!(matches!(large, Structure::Fuse8));
+        // Below 1/65536 only the classic filter meets the rate.
+        let classic = StaticFilterBuilder::from_hashes(&hashes, 1e-6, Priority::Space);
+        assert!(matches!(classic, StaticFilter::Classic(_)));
+        let strict = StaticFilterBuilder::from_hashes(&hashes, 1e-9, Priority::Speed);
+        assert!(matches!(strict, StaticFilter::Classic(_)));
+
+        for filter in [fast, small, ribbon16, classic, strict] {
+            assert!((0..10_000u64).all(|i| filter.may_match_key(&i)));
+            let fp = (10_000..110_000u64)
+                .filter(|i| filter.may_match_key(i))
+                .count();
+            assert!(fp < 1300, "{fp}");
+        }
+    }
+}
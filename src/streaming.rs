@@ -0,0 +1,155 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, DecodeError, ExpiringBloomFilter, FilterHeader};
+
+const CHECKPOINT_MAGIC: [u8; 4] = *b"BLDP";
+const CHECKPOINT_VERSION: u32 = 1;
+const CHECKPOINT_HEADER_LEN: usize = 24;
+
+/// Skips messages whose key was possibly processed already, e.g. in a Kafka consumer.
+///
+/// The processor wraps either a stable filter that remembers keys forever or an
+/// `ExpiringBloomFilter` that the caller rotates to forget old keys. False positives make a
+/// new key look processed, so size the filter for the keys it must remember.
+///
+/// Its state can be checkpointed together with the consumer offset and restored after a
+/// restart or rebalance, so redelivered messages are still recognized.
+#[derive(Clone, Debug)]
+pub struct DedupProcessor {
+    filter: ExpiringBloomFilter,
+}
+
+impl DedupProcessor {
+    /// Creates a processor that remembers keys for as long as it lives.
+    pub fn new(filter: BlockedBloomFilter) -> Self {
+        Self {
+            filter: ExpiringBloomFilter::from_segments(vec![filter]),
+        }
+    }
+
+    /// Creates a processor that forgets keys as `rotate` expires the filter's segments.
+    pub fn rotating(filter: ExpiringBloomFilter) -> Self {
+        Self { filter }
+    }
+
+    /// Returns `true` and records the key if it was not seen before.
+    pub fn should_process<K: Hash + ?Sized>(&mut self, message_key: &K) -> bool {
+        if self.filter.may_match_key(message_key) {
+            return false;
+        }
+        self.filter.insert_key(message_key);
+        true
+    }
+
+    /// Like `should_process`, for keys that are already hashed.
+    pub fn should_process_hash(&mut self, h: u64) -> bool {
+        if self.filter.may_match_hash(h) {
+            return false;
+        }
+        self.filter.insert_hash(h);
+        true
+    }
+
+    /// Expires the oldest segment. A stable processor forgets every key.
+    pub fn rotate(&mut self) {
+        self.filter.rotate();
+    }
+
+    /// Encodes the processor's state together with the consumer `offset` it reflects.
+    ///
+    /// The checkpoint is a 24-byte little-endian header (magic, version, offset, segment
+    /// count, reserved) followed by each segment in the `to_bytes` format, oldest first.
+    pub fn checkpoint(&self, offset: u64) -> Vec<u8> {
+        let mut out = Vec::with_capacity(CHECKPOINT_HEADER_LEN);
+        out.extend_from_slice(&CHECKPOINT_MAGIC);
+        out.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(self.filter.num_segments() as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        for segment in self.filter.segments_oldest_first() {
+            out.extend_from_slice(&segment.to_bytes());
+        }
+        out
+    }
+
+    /// Decodes a checkpoint, returning the processor and the offset it was taken at.
+    pub fn restore(bytes: &[u8]) -> Result<(Self, u64), DecodeError> {
+        if bytes.len() < CHECKPOINT_HEADER_LEN {
+            return Err(DecodeError::LengthMismatch {
+                expected: CHECKPOINT_HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[0..4] != CHECKPOINT_MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let version = u32_at(4);
+        if version != CHECKPOINT_VERSION {
+            return Err(DecodeError::UnsupportedVersion {
+                found: version,
+                current: CHECKPOINT_VERSION,
+            });
+        }
+        let offset = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let count = u32_at(16);
+        if count == 0 {
+            return Err(DecodeError::NoBlocks);
+        }
+
+        let mut rest = &bytes[CHECKPOINT_HEADER_LEN..];
+        let mut segments = Vec::with_capacity(count.min(1024) as usize);
+        for _ in 0..count {
+            let header = FilterHeader::parse(rest)?;
+            let len = FilterHeader::LEN + header.payload_len();
+            if rest.len() < len {
+                return Err(DecodeError::LengthMismatch {
+                    expected: len,
+                    actual: rest.len(),
+                });
+            }
+            segments.push(BlockedBloomFilter::from_bytes(&rest[..len])?);
+            rest = &rest[len..];
+        }
+        if !rest.is_empty() {
+            return Err(DecodeError::LengthMismatch {
+                expected: bytes.len() - rest.len(),
+                actual: bytes.len(),
+            });
+        }
+
+        let filter = ExpiringBloomFilter::from_segments(segments);
+        Ok((Self { filter }, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_restores_seen_keys() {
+        let mut processor = DedupProcessor::rotating(ExpiringBloomFilter::new(1000, 0.01, 3));
+        assert!(processor.should_process("offset-1"));
+        assert!(!processor.should_process("offset-1"));
+        processor.rotate();
+        assert!(processor.should_process("offset-2"));
+
+        let bytes = processor.checkpoint(42);
+        let (mut restored, offset) = DedupProcessor::restore(&bytes).unwrap();
+        assert_eq!(offset, 42);
+        assert!(!restored.should_process("offset-1"));
+        assert!(!restored.should_process("offset-2"));
+
+        // Segment order survives the round trip: "offset-1" expires first.
+        restored.rotate();
+        restored.rotate();
+        assert!(restored.should_process("offset-1"));
+        assert!(!restored.should_process("offset-2"));
+
+        assert!(matches!(
+            DedupProcessor::restore(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::LengthMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore, ObjectStoreExt};
+
+use crate::{BlockedBloomFilter, LoadError};
+
+/// Callbacks invoked by `FilterSubscriber`, e.g. to feed metrics.
+///
+/// All methods default to doing nothing.
+pub trait SubscriberHooks: Send + Sync {
+    /// A new filter generation was downloaded and swapped in.
+    fn on_update(&self, _filter: &BlockedBloomFilter, _meta: &ObjectMeta) {}
+    /// The published filter has not changed since the last poll.
+    fn on_not_modified(&self) {}
+    /// Polling or downloading failed; the active filter is left in place.
+    fn on_error(&self, _error: &LoadError) {}
+}
+
+/// Keeps a local copy of a filter published to an object store up to date.
+///
+/// Each `refresh` issues a `HEAD` request and only downloads the filter when its ETag (or
+/// version, or modification time) changed. New generations are swapped in atomically: readers
+/// holding the previous `Arc` keep using it until they drop it.
+pub struct FilterSubscriber {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+    active: RwLock<Arc<BlockedBloomFilter>>,
+    generation: Mutex<String>,
+    hooks: Option<Box<dyn SubscriberHooks>>,
+}
+
+impl FilterSubscriber {
+    /// Downloads the current filter at `path` and starts tracking it.
+    pub async fn connect(store: Arc<dyn ObjectStore>, path: Path) -> Result<Self, LoadError> {
+        let meta = store.head(&path).await?;
+        let filter = BlockedBloomFilter::load_object(store.as_ref(), &meta).await?;
+        Ok(Self {
+            store,
+            path,
+            active: RwLock::new(Arc::new(filter)),
+            generation: Mutex::new(generation(&meta)),
+            hooks: None,
+        })
+    }
+
+    /// Installs callbacks for updates, unchanged polls and errors.
+    pub fn with_hooks(mut self, hooks: impl SubscriberHooks + 'static) -> Self {
+        self.hooks = Some(Box::new(hooks));
+        self
+    }
+
+    /// Returns the active filter generation.
+    pub fn current(&self) -> Arc<BlockedBloomFilter> {
+        Arc::clone(&self.active.read().unwrap())
+    }
+
+    /// Polls the store once, returning whether a new filter generation was swapped in.
+    pub async fn refresh(&self) -> Result<bool, LoadError> {
+        let result = self.try_refresh().await;
+        if let (Some(hooks), Err(e)) = (&self.hooks, &result) {
+            hooks.on_error(e);
+        }
+        result
+    }
+
+    async fn try_refresh(&self) -> Result<bool, LoadError> {
+        let meta = self.store.head(&self.path).await?;
+        let next = generation(&meta);
+        if *self.generation.lock().unwrap() == next {
+            if let Some(hooks) = &self.hooks {
+                hooks.on_not_modified();
+            }
+            return Ok(false);
+        }
+
+        let filter = BlockedBloomFilter::load_object(self.store.as_ref(), &meta).await?;
+        if let Some(hooks) = &self.hooks {
+            hooks.on_update(&filter, &meta);
+        }
+        *self.active.write().unwrap() = Arc::new(filter);
+        *self.generation.lock().unwrap() = next;
+        Ok(true)
+    }
+
+    /// Spawns a task on the current Tokio runtime that calls `refresh` every `period`.
+    ///
+    /// Errors are reported through the hooks and polling continues. Abort the returned handle
+    /// to stop polling.
+    #[cfg(feature = "tokio")]
+    pub fn spawn(self: Arc<Self>, period: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let _ = self.refresh().await;
+            }
+        })
+    }
+}
+
+/// Identifies an object generation, preferring the ETag over weaker indicators.
+fn generation(meta: &ObjectMeta) -> String {
+    meta.e_tag
+        .clone()
+        .or_else(|| meta.version.clone())
+        .unwrap_or_else(|| format!("{}:{}", meta.last_modified, meta.size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use object_store::memory::InMemory;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, Default)]
+    struct Counts(Arc<AtomicUsize>, Arc<AtomicUsize>);
+
+    impl SubscriberHooks for Counts {
+        fn on_update(&self, _: &BlockedBloomFilter, _: &ObjectMeta) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_not_modified(&self) {
+            self.1.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_subscriber_swaps_new_generation() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let path = Path::from("bloom.bin");
+
+        let mut bf = BlockedBloomFilter::new(100, 0.01);
+        bf.insert_key("Leopold Bloom");
+        block_on(store.put(&path, bf.to_bytes().into())).unwrap();
+
+        let counts = Counts::default();
+        let subscriber = block_on(FilterSubscriber::connect(Arc::clone(&store), path.clone()))
+            .unwrap()
+            .with_hooks(counts.clone());
+        let first = subscriber.current();
+        assert!(!block_on(subscriber.refresh()).unwrap());
+
+        bf.insert_key("Molly Bloom");
+        block_on(store.put(&path, bf.to_bytes().into())).unwrap();
+        assert!(block_on(subscriber.refresh()).unwrap());
+
+        assert!(!first.may_match_key("Molly Bloom"));
+        assert!(subscriber.current().may_match_key("Molly Bloom"));
+        assert_eq!(counts.0.load(Ordering::Relaxed), 1);
+        assert_eq!(counts.1.load(Ordering::Relaxed), 1);
+    }
+}
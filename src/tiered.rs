@@ -0,0 +1,173 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, math};
+
+/// A small cache-resident filter for recent keys in front of a large filter for the long tail.
+///
+/// Inserts land in the hot tier, whose blocks stay in cache, and are remembered as pending
+/// hashes. Queries check the hot tier first, so skewed workloads that mostly ask about recent
+/// or frequently touched keys rarely miss the cache on the large cold tier. Once the hot tier
+/// holds `hot_entries` keys, its pending hashes are merged into the cold tier and it is
+/// cleared; a maintenance task can also call `merge` off the request path.
+#[derive(Clone, Debug)]
+pub struct TieredFilter {
+    hot: BlockedBloomFilter,
+    cold: BlockedBloomFilter,
+    pending: Vec<u64>,
+    hot_count: usize,
+    hot_entries: usize,
+    entries: usize,
+}
+
+impl TieredFilter {
+    /// Creates a filter for `entries` keys overall with a hot tier for `hot_entries` keys.
+    pub fn new(entries: usize, hot_entries: usize, fpr: f64) -> Self {
+        Self::new_with_seed(entries, hot_entries, fpr, 0)
+    }
+
+    /// Creates a tiered filter with a custom seed shared by both tiers.
+    pub fn new_with_seed(entries: usize, hot_entries: usize, fpr: f64, seed: u64) -> Self {
+        let hot_entries = hot_entries.max(1);
+        Self {
+            hot: BlockedBloomFilter::new_with_seed(hot_entries, fpr, seed),
+            cold: BlockedBloomFilter::new_with_seed(entries, fpr, seed),
+            pending: Vec::with_capacity(hot_entries),
+            hot_count: 0,
+            hot_entries,
+            entries,
+        }
+    }
+
+    /// Creates a tiered filter whose tiers share a global false positive rate budget.
+    ///
+    /// Every query may check both tiers, so their rates compound. The budget is split with
+    /// `math::split_fpr` in proportion to each tier's entries, and each tier is sized exactly
+    /// for its share under the blocked model.
+    pub fn with_fpr_budget(entries: usize, hot_entries: usize, target_fpr: f64, seed: u64) -> Self {
+        let hot_entries = hot_entries.max(1);
+        let rates = math::split_fpr(target_fpr, &[hot_entries as f64, entries.max(1) as f64]);
+        let tier = |entries: usize, fpr: f64| {
+            let num_blocks = math::blocked_num_blocks_for(entries, fpr);
+            BlockedBloomFilter::from_blocks(
+                vec![CacheLineBlock::default(); num_blocks as usize],
+                seed,
+            )
+        };
+        Self {
+            hot: tier(hot_entries, rates[0]),
+            cold: tier(entries, rates[1]),
+            pending: Vec::with_capacity(hot_entries),
+            hot_count: 0,
+            hot_entries,
+            entries,
+        }
+    }
+
+    /// Returns the expected false positive rate of a query with both tiers at capacity.
+    pub fn expected_fpr(&self) -> f64 {
+        math::composite_fpr(&[
+            math::blocked_fpr_for(self.hot.num_blocks, self.hot_entries),
+            math::blocked_fpr_for(self.cold.num_blocks, self.entries),
+        ])
+    }
+
+    /// Inserts a hash into the hot tier, merging first if the hot tier is full.
+    pub fn insert_hash(&mut self, h: u64) {
+        if self.hot_count >= self.hot_entries {
+            self.merge();
+        }
+        self.hot.insert_hash(h);
+        self.pending.push(h);
+        self.hot_count += 1;
+    }
+
+    /// Checks the hot tier, then the cold tier.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.hot.may_match_hash(h) || self.cold.may_match_hash(h)
+    }
+
+    /// Like `may_match_hash`, but promotes hashes found only in the cold tier to the hot one.
+    ///
+    /// Promoted hashes are already in the cold tier, so they are not merged again.
+    pub fn touch_hash(&mut self, h: u64) -> bool {
+        if self.hot.may_match_hash(h) {
+            return true;
+        }
+        if !self.cold.may_match_hash(h) {
+            return false;
+        }
+        if self.hot_count >= self.hot_entries {
+            self.merge();
+        }
+        self.hot.insert_hash(h);
+        self.hot_count += 1;
+        true
+    }
+
+    /// Hashes the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.insert_hash(self.hot.hash_key(key));
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(self.hot.hash_key(key))
+    }
+
+    /// Hashes the key and checks it like `touch_hash`.
+    pub fn touch_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        self.touch_hash(self.hot.hash_key(key))
+    }
+
+    /// Moves the pending hashes into the cold tier and clears the hot tier.
+    pub fn merge(&mut self) {
+        for h in self.pending.drain(..) {
+            self.cold.insert_hash(h);
+        }
+        self.hot.clear();
+        self.hot_count = 0;
+    }
+
+    /// Returns the number of hashes waiting to be merged into the cold tier.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the cold tier, which holds every key once pending hashes are merged.
+    pub fn cold(&self) -> &BlockedBloomFilter {
+        &self.cold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_tier_merges_into_cold() {
+        let mut bf = TieredFilter::new(100_000, 100, 0.01);
+        for i in 0..250u64 {
+            bf.insert_key(&i);
+        }
+        assert_eq!(bf.pending(), 50);
+        assert!((0..250u64).all(|i| bf.may_match_key(&i)));
+        assert!(!bf.cold().may_match_key(&249u64));
+
+        bf.merge();
+        assert_eq!(bf.pending(), 0);
+        assert!((0..250u64).all(|i| bf.cold().may_match_key(&i)));
+
+        // Touching a cold key promotes it without queueing it for another merge.
+        assert!(bf.touch_key(&7u64));
+        assert!(bf.hot.may_match_key(&7u64));
+        assert_eq!(bf.pending(), 0);
+    }
+
+    #[test]
+    fn test_fpr_budget_is_met_across_tiers() {
+        let bf = TieredFilter::with_fpr_budget(100_000, 1000, 0.01, 0);
+        assert!(bf.expected_fpr() <= 0.01);
+        assert!(bf.expected_fpr() > 0.009);
+        assert!(TieredFilter::new(100_000, 1000, 0.01).expected_fpr() > 0.01);
+    }
+}
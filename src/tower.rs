@@ -0,0 +1,275 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use http::{HeaderName, Request, Response, StatusCode};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{Clock, ExpiringBloomFilter};
+
+/// What `DedupService` does with a request whose idempotency key was possibly seen before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupAction {
+    /// Answers `409 Conflict` without calling the inner service.
+    #[default]
+    Reject,
+    /// Forwards the request with a `PossibleDuplicate` extension.
+    Flag,
+}
+
+/// Request extension added by `DedupAction::Flag` to requests carrying an idempotency key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PossibleDuplicate(pub bool);
+
+#[derive(Debug)]
+struct Window {
+    filter: ExpiringBloomFilter,
+    period: Duration,
+    rotated_at: SystemTime,
+}
+
+impl Window {
+    /// Rotates once per elapsed period, then records the key and reports if it was seen.
+    ///
+    /// A clock that moves backwards counts as no time elapsed.
+    fn check_and_insert(&mut self, key: &[u8]) -> bool {
+        let now = self.filter.clock().now();
+        let elapsed = |since: SystemTime| now.duration_since(since).unwrap_or_default();
+        let mut rotations = 0;
+        while elapsed(self.rotated_at) >= self.period && rotations < self.filter.num_segments() {
+            self.filter.rotate();
+            self.rotated_at += self.period;
+            rotations += 1;
+        }
+        if elapsed(self.rotated_at) >= self.period {
+            // Every segment has been cleared, so skip the remaining periods.
+            self.rotated_at = now;
+        }
+
+        let seen = self.filter.may_match_key(key);
+        self.filter.insert_key(key);
+        seen
+    }
+}
+
+/// A layer deduplicating requests by an idempotency key header.
+///
+/// Keys are remembered in an `ExpiringBloomFilter` whose segments rotate lazily every
+/// `window / segments`, so a key stays visible for roughly `window`. Requests without the
+/// header pass through untouched. False positives make a fresh key look like a duplicate,
+/// so size the filter for the expected number of keys per segment.
+#[derive(Clone, Debug)]
+pub struct DedupLayer {
+    header: HeaderName,
+    action: DedupAction,
+    window: Arc<Mutex<Window>>,
+}
+
+impl DedupLayer {
+    /// The header read by default.
+    pub const DEFAULT_HEADER: &'static str = "idempotency-key";
+
+    /// Creates a layer remembering keys for `window`, sized for `entries_per_segment` keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segments` is zero or does not fit in a `u32`.
+    pub fn new(window: Duration, segments: usize, entries_per_segment: usize, fpr: f64) -> Self {
+        assert!(segments > 0, "a dedup window needs at least one segment");
+        let period = window / u32::try_from(segments).expect("too many segments");
+        let filter = ExpiringBloomFilter::new(entries_per_segment, fpr, segments);
+        let rotated_at = filter.clock().now();
+        Self {
+            header: HeaderName::from_static(Self::DEFAULT_HEADER),
+            action: DedupAction::default(),
+            window: Arc::new(Mutex::new(Window {
+                filter,
+                period,
+                rotated_at,
+            })),
+        }
+    }
+
+    /// Reads the idempotency key from `header` instead of `idempotency-key`.
+    pub fn with_header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        {
+            let mut window = self.window.lock().unwrap();
+            window.rotated_at = clock.now();
+            window.filter.set_clock(clock);
+        }
+        self
+    }
+
+    /// Sets what happens to possible duplicates.
+    pub fn with_action(mut self, action: DedupAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+impl<S> Layer<S> for DedupLayer {
+    type Service = DedupService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DedupService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The service produced by `DedupLayer`.
+#[derive(Clone, Debug)]
+pub struct DedupService<S> {
+    inner: S,
+    layer: DedupLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for DedupService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = DedupFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let Some(key) = req.headers().get(&self.layer.header) else {
+            return DedupFuture::Inner {
+                future: self.inner.call(req),
+            };
+        };
+        let seen = {
+            let mut window = self.layer.window.lock().unwrap_or_else(|e| e.into_inner());
+            window.check_and_insert(key.as_bytes())
+        };
+
+        match self.layer.action {
+            DedupAction::Reject if seen => {
+                let mut response = Response::new(ResBody::default());
+                *response.status_mut() = StatusCode::CONFLICT;
+                DedupFuture::Rejected {
+                    response: Some(response),
+                }
+            }
+            DedupAction::Reject => DedupFuture::Inner {
+                future: self.inner.call(req),
+            },
+            DedupAction::Flag => {
+                req.extensions_mut().insert(PossibleDuplicate(seen));
+                DedupFuture::Inner {
+                    future: self.inner.call(req),
+                }
+            }
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response future of `DedupService`.
+    #[project = DedupFutureProj]
+    pub enum DedupFuture<F, B> {
+        /// Waiting on the inner service.
+        Inner { #[pin] future: F },
+        /// Answering a rejected duplicate.
+        Rejected { response: Option<Response<B>> },
+    }
+}
+
+impl<F, B, E> Future for DedupFuture<F, B>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            DedupFutureProj::Inner { future } => future.poll(cx),
+            DedupFutureProj::Rejected { response } => Poll::Ready(Ok(response
+                .take()
+                .expect("DedupFuture polled after completion"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::future::{Ready, ready};
+
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<Option<bool>>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<()>) -> Self::Future {
+            let flag = req.extensions().get::<PossibleDuplicate>().map(|d| d.0);
+            ready(Ok(Response::new(flag)))
+        }
+    }
+
+    fn send(service: &mut DedupService<Echo>, key: Option<&str>) -> Response<Option<bool>> {
+        let mut req = Request::builder();
+        if let Some(key) = key {
+            req = req.header(DedupLayer::DEFAULT_HEADER, key);
+        }
+        futures::executor::block_on(service.call(req.body(()).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_duplicates_are_rejected_or_flagged() {
+        let layer = DedupLayer::new(Duration::from_secs(60), 4, 1000, 0.001);
+        let mut reject = layer.layer(Echo);
+        assert_eq!(send(&mut reject, Some("order-1")).status(), StatusCode::OK);
+        assert_eq!(
+            send(&mut reject, Some("order-1")).status(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(send(&mut reject, None).status(), StatusCode::OK);
+        assert_eq!(send(&mut reject, None).status(), StatusCode::OK);
+
+        let mut flag = DedupLayer::new(Duration::from_secs(60), 4, 1000, 0.001)
+            .with_action(DedupAction::Flag)
+            .layer(Echo);
+        assert_eq!(*send(&mut flag, Some("order-2")).body(), Some(false));
+        assert_eq!(*send(&mut flag, Some("order-2")).body(), Some(true));
+        assert_eq!(*send(&mut flag, None).body(), None);
+    }
+
+    #[test]
+    fn test_keys_expire_with_the_clock() {
+        let clock = Arc::new(crate::ManualClock::default());
+        let mut service = DedupLayer::new(Duration::from_secs(60), 4, 1000, 0.001)
+            .with_clock(clock.clone())
+            .layer(Echo);
+        assert_eq!(send(&mut service, Some("order-1")).status(), StatusCode::OK);
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(
+            send(&mut service, Some("order-1")).status(),
+            StatusCode::CONFLICT
+        );
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(send(&mut service, Some("order-1")).status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,258 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, math};
+
+/// Probe counts a `ReducedProbeFilter` supports, each giving whole lanes of the block.
+pub const PROBE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+/// What a `ProbeTuner` learned about a filter's probes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeTuningReport {
+    /// Queries observed.
+    pub queries: u64,
+    /// Queries answered "no".
+    pub negatives: u64,
+    /// For each salt position, the negatives where it was the only word rejecting the hash.
+    /// Without that probe those queries would have been false positives.
+    pub sole_rejections: [u64; 8],
+    /// Distinct keys the filter is estimated to hold.
+    pub estimated_entries: f64,
+    /// Predicted false positive rate of a rebuild with each of `PROBE_COUNTS` probes at the
+    /// same size and estimated entries.
+    pub predicted_fpr: [f64; 4],
+    /// The fewest probes whose predicted rate meets the target, or 8 if none does.
+    pub recommended_probes: u32,
+}
+
+impl ProbeTuningReport {
+    /// Returns, per salt position, the share of queries that would turn into false positives
+    /// if only that probe were dropped.
+    pub fn marginal_fpr(&self) -> [f64; 8] {
+        self.sole_rejections
+            .map(|sole| sole as f64 / self.queries.max(1) as f64)
+    }
+}
+
+/// Experimental: watches queries to measure what each of the 8 probes contributes, and
+/// recommends a probe count for a rebuild.
+///
+/// Probes beyond what the fill needs cost memory traffic and hashing without rejecting much.
+/// For every negative query the tuner notes which words rejected the hash; a word that is
+/// often the sole rejector earns its keep, one that never is does not. `report` combines
+/// these counts with the blocked model's prediction for fewer probes, and
+/// `ReducedProbeFilter` rebuilds the filter with the recommended count.
+#[derive(Clone, Debug)]
+pub struct ProbeTuner {
+    filter: BlockedBloomFilter,
+    queries: u64,
+    negatives: u64,
+    sole_rejections: [u64; 8],
+}
+
+impl ProbeTuner {
+    /// Wraps `filter`, recording the queries made through the tuner.
+    pub fn new(filter: BlockedBloomFilter) -> Self {
+        Self {
+            filter,
+            queries: 0,
+            negatives: 0,
+            sole_rejections: [0; 8],
+        }
+    }
+
+    /// Checks if the filter might contain the hash, recording which probes rejected it.
+    pub fn may_match_hash(&mut self, h: u64) -> bool {
+        let block = &self.filter.blocks[self.filter.fast_map(h)];
+        let rejecting = block
+            .words
+            .iter()
+            .zip(BlockedBloomFilter::SALT.iter())
+            .enumerate()
+            .fold(0u8, |acc, (i, (&w, &salt))| {
+                let bit = (h as u32).wrapping_mul(salt) >> 27;
+                acc | (((w >> bit) & 1 == 0) as u8) << i
+            });
+        self.queries += 1;
+        if rejecting != 0 {
+            self.negatives += 1;
+        }
+        if rejecting.is_power_of_two() {
+            self.sole_rejections[rejecting.trailing_zeros() as usize] += 1;
+        }
+        rejecting == 0
+    }
+
+    /// Hashes the key and checks it like `may_match_hash`.
+    pub fn may_match_key<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        self.may_match_hash(self.filter.hash_key(key))
+    }
+
+    /// Reports the observations and the fewest probes predicted to meet `target_fpr`.
+    pub fn report(&self, target_fpr: f64) -> ProbeTuningReport {
+        let estimated_entries = self.filter.export_diagnostics().estimated_entries;
+        let predicted_fpr = PROBE_COUNTS.map(|probes| {
+            math::blocked_fpr_with_probes(
+                self.filter.num_blocks,
+                estimated_entries.round() as usize,
+                probes,
+            )
+        });
+        let recommended_probes = PROBE_COUNTS
+            .into_iter()
+            .zip(predicted_fpr)
+            .find(|&(_, fpr)| fpr <= target_fpr)
+            .map_or(BlockedBloomFilter::PROBES, |(probes, _)| probes);
+        ProbeTuningReport {
+            queries: self.queries,
+            negatives: self.negatives,
+            sole_rejections: self.sole_rejections,
+            estimated_entries,
+            predicted_fpr,
+            recommended_probes,
+        }
+    }
+
+    /// Returns the wrapped filter.
+    pub fn filter(&self) -> &BlockedBloomFilter {
+        &self.filter
+    }
+
+    /// Returns the filter, dropping the observations.
+    pub fn into_inner(self) -> BlockedBloomFilter {
+        self.filter
+    }
+}
+
+/// Experimental: a blocked filter that sets fewer than 8 bits per key.
+///
+/// The block is split into `probes` lanes of `256 / probes` bits and each probe sets one bit
+/// in its lane, so with 8 probes the layout matches `BlockedBloomFilter` exactly. Fewer
+/// probes make queries cheaper and suit lightly loaded filters, where
+/// `math::blocked_fpr_with_probes` shows the extra probes buy little. The binary format
+/// only describes 8-probe filters, so these are not serializable.
+#[derive(Clone, Debug)]
+pub struct ReducedProbeFilter {
+    blocks: Vec<CacheLineBlock>,
+    seed: u64,
+    probes: u32,
+}
+
+impl ReducedProbeFilter {
+    /// Creates an empty filter of `num_blocks` blocks with `probes` probes per key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `probes` is not one of `PROBE_COUNTS` or `num_blocks` is zero.
+    pub fn new(num_blocks: u32, seed: u64, probes: u32) -> Self {
+        assert!(
+            PROBE_COUNTS.contains(&probes),
+            "probes must be 1, 2, 4 or 8"
+        );
+        assert!(num_blocks > 0, "a filter needs at least one block");
+        Self {
+            blocks: vec![CacheLineBlock::default(); num_blocks as usize],
+            seed,
+            probes,
+        }
+    }
+
+    /// Rebuilds `filter`'s keys, given as their hashes, at the same size with `probes`
+    /// probes, as a `ProbeTuningReport` recommends.
+    pub fn rebuild(filter: &BlockedBloomFilter, hashes: &[u64], probes: u32) -> Self {
+        let mut rebuilt = Self::new(filter.num_blocks, filter.seed, probes);
+        for &h in hashes {
+            rebuilt.insert_hash(h);
+        }
+        rebuilt
+    }
+
+    /// Returns the number of bits set per key.
+    pub fn probes(&self) -> u32 {
+        self.probes
+    }
+
+    /// Returns the (word, bit) pairs a hash sets.
+    #[inline]
+    fn bits(&self, h: u64) -> impl Iterator<Item = (usize, u32)> + use<> {
+        let lane_bits = CacheLineBlock::BITS as u32 / self.probes;
+        let shift = 32 - lane_bits.trailing_zeros();
+        BlockedBloomFilter::SALT[..self.probes as usize]
+            .iter()
+            .enumerate()
+            .map(move |(lane, &salt)| {
+                let bit = lane as u32 * lane_bits + ((h as u32).wrapping_mul(salt) >> shift);
+                ((bit / 32) as usize, bit % 32)
+            })
+    }
+
+    #[inline]
+    fn block_index(&self, h: u64) -> usize {
+        (((h >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+
+    /// Inserts a hash.
+    pub fn insert_hash(&mut self, h: u64) {
+        let block = self.block_index(h);
+        for (word, bit) in self.bits(h) {
+            self.blocks[block].words[word] |= 1 << bit;
+        }
+    }
+
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        let block = &self.blocks[self.block_index(h)];
+        self.bits(h)
+            .all(|(word, bit)| block.words[word] & (1 << bit) != 0)
+    }
+
+    /// Hashes the key and inserts it.
+    pub fn insert_key<T: Hash + ?Sized>(&mut self, key: &T) {
+        self.insert_hash(crate::key_hash(self.seed, key));
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.may_match_hash(crate::key_hash(self.seed, key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuner_recommends_fewer_probes_for_light_filters() {
+        // Sized for 100x the keys it holds, so a couple of probes suffice.
+        let mut bf = BlockedBloomFilter::new(100_000, 0.01);
+        let hashes: Vec<u64> = (0..1000u64).map(|i| bf.hash_key(&i)).collect();
+        hashes.iter().for_each(|&h| bf.insert_hash(h));
+
+        let mut tuner = ProbeTuner::new(bf);
+        let hits = (1000..101_000u64)
+            .filter(|i| tuner.may_match_key(i))
+            .count();
+        let report = tuner.report(0.0005);
+        assert_eq!(report.queries, 100_000);
+        assert_eq!(report.negatives, 100_000 - hits as u64);
+        assert!(report.marginal_fpr().iter().all(|&m| m < 0.001));
+        assert_eq!(report.recommended_probes, 2);
+
+        let rebuilt = ReducedProbeFilter::rebuild(tuner.filter(), &hashes, 2);
+        assert!((0..1000u64).all(|i| rebuilt.may_match_key(&i)));
+        let fp = (1000..101_000u64)
+            .filter(|i| rebuilt.may_match_key(i))
+            .count();
+        assert!(fp < 20, "{fp}");
+    }
+
+    #[test]
+    fn test_eight_probes_match_blocked_layout() {
+        let mut bf = BlockedBloomFilter::new(1000, 0.01);
+        let mut reduced = ReducedProbeFilter::new(bf.num_blocks, 0, 8);
+        for i in 0..1000u64 {
+            bf.insert_key(&i);
+            reduced.insert_key(&i);
+        }
+        assert_eq!(reduced.blocks[..], bf.blocks[..]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::{
+    ArchivedFilter, BlockedBloomFilter, MaskCache, ReducedProbeFilter, SharedBloomFilter, key_hash,
+};
+
+/// An implementation that disagreed with the reference path in `verify_implementations`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerificationError {
+    /// The disagreeing implementation.
+    pub implementation: &'static str,
+    /// The hash it disagreed on.
+    pub hash: u64,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} disagrees with the reference path on hash {:#018x}",
+            self.implementation, self.hash
+        )
+    }
+}
+
+impl Error for VerificationError {}
+
+/// Checks that every implementation of the blocked layout sets and tests exactly the same
+/// bits, for running once at startup before serving traffic.
+///
+/// Builds a filter from `samples / 2` pseudo-random hashes derived from `seed` with
+/// `BlockedBloomFilter::insert_hash`, the reference path, and checks all `samples` hashes
+/// against it. The same hashes then go through the other paths: the atomic
+/// `SharedBloomFilter`, the branchless `select_hashes`, `may_match_hash_cached`,
+/// `insert_split`, a zero-copy `ArchivedFilter` of the encoded filter, and an 8-probe
+/// `ReducedProbeFilter`. Paths that insert must produce bit-identical blocks, and every path
+/// must give the reference answer for every hash. This guards against a miscompiled or
+/// wrongly specialized path silently diverging on a particular target.
+///
+/// Returns the first disagreement found.
+pub fn verify_implementations(seed: u64, samples: usize) -> Result<(), VerificationError> {
+    let hashes: Vec<u64> = (0..samples as u64).map(|i| key_hash(seed, &i)).collect();
+    let (inserted, _) = hashes.split_at(samples / 2);
+    let disagrees = |implementation, hash| {
+        Err(VerificationError {
+            implementation,
+            hash,
+        })
+    };
+
+    let mut reference = BlockedBloomFilter::new_with_seed(inserted.len(), 0.01, seed);
+    inserted.iter().for_each(|&h| reference.insert_hash(h));
+    let expected: Vec<bool> = hashes
+        .iter()
+        .map(|&h| reference.may_match_hash(h))
+        .collect();
+    if let Some(&h) = inserted.iter().find(|&&h| !reference.may_match_hash(h)) {
+        return disagrees("may_match_hash", h);
+    }
+
+    let empty = BlockedBloomFilter::new_with_seed(inserted.len(), 0.01, seed);
+    let shared = SharedBloomFilter::from(empty.clone());
+    let mut split = empty;
+    for &h in inserted {
+        shared.insert_hash(h);
+        split.insert_split((h >> 32) as u32, h as u32);
+    }
+    let reduced = ReducedProbeFilter::rebuild(&reference, inserted, 8);
+    let bytes = reference.to_bytes();
+    let archived = ArchivedFilter::from_bytes(&bytes).expect("a filter decodes its own bytes");
+    let mut selection = Vec::new();
+    reference.select_hashes(&hashes, &mut selection);
+    let mut selected = selection.iter().map(|&i| i as usize).peekable();
+    let mut cache = MaskCache::new(64);
+
+    let shared_blocks = shared.snapshot();
+    for (i, (&h, &expected)) in hashes.iter().zip(&expected).enumerate() {
+        let block = reference.fast_map(h);
+        if shared_blocks.blocks[block] != reference.blocks[block] {
+            return disagrees("SharedBloomFilter::insert_hash", h);
+        }
+        if split.blocks[block] != reference.blocks[block] {
+            return disagrees("insert_split", h);
+        }
+        let answers = [
+            (
+                "SharedBloomFilter::may_match_hash",
+                shared.may_match_hash(h),
+            ),
+            (
+                "may_match_split",
+                split.may_match_split((h >> 32) as u32, h as u32),
+            ),
+            (
+                "may_match_hash_cached",
+                reference.may_match_hash_cached(h, &mut cache),
+            ),
+            ("ArchivedFilter::may_match_hash", archived.may_match_hash(h)),
+            (
+                "ReducedProbeFilter::may_match_hash",
+                reduced.may_match_hash(h),
+            ),
+            ("select_hashes", selected.next_if_eq(&i).is_some()),
+        ];
+        if let Some(&(implementation, _)) = answers.iter().find(|&&(_, a)| a != expected) {
+            return disagrees(implementation, h);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_implementations_agree() {
+        assert_eq!(verify_implementations(0, 10_000), Ok(()));
+        assert_eq!(verify_implementations(0xb1005, 3), Ok(()));
+        let err = VerificationError {
+            implementation: "select_hashes",
+            hash: 1,
+        };
+        assert_eq!(
+            err.to_string(),
+            "select_hashes disagrees with the reference path on hash 0x0000000000000001"
+        );
+    }
+}
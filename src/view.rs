@@ -0,0 +1,93 @@
+use std::hash::Hash;
+use std::ops::Range;
+
+use crate::{BlockMapping, BlockedBloomFilter, CacheLineBlock, HashAlgorithm};
+
+/// A read-only view of a contiguous range of a filter's blocks.
+///
+/// Hashes still map to blocks of the whole filter; the view answers only for hashes whose
+/// block falls in its range and returns `None` for the others, so a distributed deployment
+/// can route each query to the node serving its block.
+#[derive(Clone, Copy, Debug)]
+pub struct FilterView<'a> {
+    blocks: &'a [CacheLineBlock],
+    start: usize,
+    num_blocks: u32,
+    seed: u64,
+    mapping: BlockMapping,
+    hash: HashAlgorithm,
+}
+
+impl BlockedBloomFilter {
+    /// Returns a read-only view of the blocks in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds, like slicing.
+    pub fn view(&self, range: Range<usize>) -> FilterView<'_> {
+        FilterView {
+            start: range.start,
+            blocks: &self.blocks[range],
+            num_blocks: self.num_blocks,
+            seed: self.seed,
+            mapping: self.mapping,
+            hash: self.hash,
+        }
+    }
+}
+
+impl FilterView<'_> {
+    /// Returns the range of block indices the view covers.
+    pub fn block_range(&self) -> Range<usize> {
+        self.start..self.start + self.blocks.len()
+    }
+
+    /// Returns the index of the block a hash maps to in the whole filter.
+    pub fn block_index(&self, h: u64) -> usize {
+        self.mapping.block_index(h, self.num_blocks)
+    }
+
+    /// Checks if the filter might contain the hash, or `None` if its block is not in the view.
+    pub fn may_match_hash(&self, h: u64) -> Option<bool> {
+        let block = self
+            .blocks
+            .get(self.block_index(h).checked_sub(self.start)?)?;
+        let check = block
+            .words
+            .iter()
+            .zip(BlockedBloomFilter::SALT.iter())
+            .fold(0u32, |acc, (&w, &salt)| {
+                let idx = (h as u32).wrapping_mul(salt) >> 27;
+                acc | ((1 << idx) & !w)
+            });
+        Some(check == 0)
+    }
+
+    /// Hashes the key and checks if it might be present, or `None` if not served here.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> Option<bool> {
+        self.may_match_hash(self.hash.hash_key(self.seed, key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_views_partition_queries() {
+        let mut bf = BlockedBloomFilter::new(10_000, 0.01);
+        for i in 0..10_000u64 {
+            bf.insert_key(&i);
+        }
+        let mid = bf.num_blocks as usize / 2;
+        let low = bf.view(0..mid);
+        let high = bf.view(mid..bf.num_blocks as usize);
+        assert_eq!(high.block_range(), mid..bf.num_blocks as usize);
+
+        for i in 0..20_000u64 {
+            let answers = [low.may_match_key(&i), high.may_match_key(&i)];
+            let served: Vec<bool> = answers.into_iter().flatten().collect();
+            assert_eq!(served, [bf.may_match_key(&i)]);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, key_hash};
+
+impl BlockedBloomFilter {
+    /// Builds a replacement filter pre-populated with a sample of the keys it will hold, such
+    /// as the current contents of a cache.
+    ///
+    /// A fresh filter after a deploy treats every key as unseen, so a dedup stage lets
+    /// duplicates through until the filter fills up again. Warming it with a sample of recent
+    /// keys avoids much of that. `scale_factor` is how many live keys each sampled key stands
+    /// for, so a 1% sample passes 100; the filter is sized for the sample's size times that at
+    /// `fpr`, leaving room for the keys still to come.
+    pub fn warm_from_sample<I>(keys: I, scale_factor: f64, fpr: f64, seed: u64) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        let hashes: Vec<u64> = keys.into_iter().map(|key| key_hash(seed, &key)).collect();
+        let entries = (hashes.len() as f64 * scale_factor.max(1.0)).ceil() as usize;
+        let mut filter = Self::new_with_seed(entries, fpr, seed);
+        hashes.iter().for_each(|&h| filter.insert_hash(h));
+        filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_sizes_for_scaled_sample() {
+        let warm = BlockedBloomFilter::warm_from_sample(0..1000u64, 10.0, 0.01, 4);
+        assert_eq!(
+            warm.num_blocks,
+            BlockedBloomFilter::new_with_seed(10_000, 0.01, 4).num_blocks
+        );
+        assert!((0..1000u64).all(|i| warm.may_match_key(&i)));
+    }
+}
@@ -0,0 +1,678 @@
+use std::convert::Infallible;
+use std::f64::consts::LN_2;
+use std::fmt;
+use std::future::{Future, ready};
+use std::hash::Hash;
+
+use crate::{
+    ApproxMembership, BlockedBloomFilter, CacheLineBlock, CounterWidth, CountingBloomFilter,
+    CuckooFilter, ErrorKind, HashAlgorithm, Priority, StaticFilter, StaticFilterBuilder, math,
+};
+
+/// Describes how a filter will be used, for `choose_filter`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkloadSpec {
+    /// Expected number of distinct keys.
+    pub entries: usize,
+    /// Desired false positive rate.
+    pub target_fpr: f64,
+    /// Whether every key is known before the first query, so the filter never changes
+    /// after it is built.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub static_set: bool,
+    /// Whether keys must be removable after insertion.
+    pub deletions: bool,
+    /// Upper bound on the filter size in bytes.
+    pub memory_budget: Option<usize>,
+    /// Expected queries per second, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub queries_per_sec: Option<f64>,
+}
+
+impl WorkloadSpec {
+    /// The query rate from which `choose_filter` keeps static sets in a blocked filter, one
+    /// cache miss per query, rather than a smaller filter that takes up to three.
+    pub const HIGH_QUERY_RATE: f64 = 1_000_000.0;
+
+    /// Describes a dynamic, insert-only workload without a memory budget or known query rate.
+    pub fn new(entries: usize, target_fpr: f64) -> Self {
+        Self {
+            entries,
+            target_fpr,
+            static_set: false,
+            deletions: false,
+            memory_budget: None,
+            queries_per_sec: None,
+        }
+    }
+}
+
+/// A recommended filter variant and its sizing.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FilterConfig {
+    /// A `BlockedBloomFilter` built with `BlockedBloomFilter::new(entries, fpr)`.
+    Blocked { entries: usize, fpr: f64 },
+    /// A `BlockedBloomFilter` of exactly `num_blocks` blocks, expected to hold `entries` keys.
+    BlockedSized { entries: usize, num_blocks: u32 },
+    /// A `CuckooFilter` built with `CuckooFilter::new(capacity)`, for removable keys at rates
+    /// of at least `CuckooFilter::MAX_FPR`.
+    Cuckoo { capacity: usize },
+    /// A `CountingBloomFilter` with four-bit counters built with
+    /// `CountingBloomFilter::new(entries, fpr, CounterWidth::Four)`, for removable keys at
+    /// rates below `CuckooFilter::MAX_FPR`.
+    Counting { entries: usize, fpr: f64 },
+    /// A `StaticFilter` built by `StaticFilterBuilder` from the full set of `entries` keys.
+    Static {
+        entries: usize,
+        fpr: f64,
+        priority: Priority,
+    },
+}
+
+impl FilterConfig {
+    /// Returns the size of the filter's block, bucket or counter array in bytes.
+    pub fn expected_bytes(&self) -> usize {
+        match *self {
+            Self::Blocked { entries, fpr } => {
+                let blocks = math::num_blocks_for(entries, fpr);
+                blocks as usize * size_of::<CacheLineBlock>()
+            }
+            Self::BlockedSized { num_blocks, .. } => {
+                num_blocks as usize * size_of::<CacheLineBlock>()
+            }
+            Self::Cuckoo { capacity } => CuckooFilter::bytes_for(capacity),
+            Self::Counting { entries, fpr } => {
+                let counters = math::num_blocks_for(entries, fpr) as usize * CacheLineBlock::BITS;
+                counters * CounterWidth::Four.bits() as usize / 8
+            }
+            Self::Static {
+                entries,
+                fpr,
+                priority,
+            } => StaticFilterBuilder::expected_bytes(entries, fpr, priority),
+        }
+    }
+
+    /// Builds the recommended filter, empty, if it is a `BlockedBloomFilter`.
+    ///
+    /// Returns `None` for the other variants; build any variant with `build_from_hashes`.
+    pub fn build(&self, seed: u64) -> Option<BlockedBloomFilter> {
+        match *self {
+            Self::Blocked { entries, fpr } => {
+                Some(BlockedBloomFilter::new_with_seed(entries, fpr, seed))
+            }
+            Self::BlockedSized { num_blocks, .. } => Some(BlockedBloomFilter::from_blocks(
+                vec![CacheLineBlock::default(); num_blocks as usize],
+                seed,
+            )),
+            Self::Cuckoo { .. } | Self::Counting { .. } | Self::Static { .. } => None,
+        }
+    }
+
+    /// Builds the recommended filter holding `hashes`, computed with `seed` and `hash`, which
+    /// its `*_key` methods then hash keys with.
+    ///
+    /// Static filters are built over exactly these hashes; the others are sized from the
+    /// config, with a cuckoo filter's capacity raised to fit all of them, and take further
+    /// inserts through the matching `ConfiguredFilter` variant. Static filters only hash keys
+    /// with `Xxh64`, so other algorithms fail with `WorkloadError::UnsupportedHash` for them.
+    pub fn build_from_hashes(
+        &self,
+        hashes: &[u64],
+        seed: u64,
+        hash: HashAlgorithm,
+    ) -> Result<ConfiguredFilter, WorkloadError> {
+        Ok(match *self {
+            Self::Blocked { .. } | Self::BlockedSized { .. } => {
+                let mut filter = self
+                    .build(seed)
+                    .expect("blocked configs build")
+                    .with_hash(hash);
+                hashes.iter().for_each(|&h| filter.insert_hash(h));
+                ConfiguredFilter::Blocked(filter)
+            }
+            Self::Cuckoo { capacity } => {
+                let mut filter =
+                    CuckooFilter::new_with_hash(capacity.max(hashes.len()), seed, hash);
+                hashes.iter().for_each(|&h| {
+                    filter.insert_hash(h);
+                });
+                ConfiguredFilter::Cuckoo(filter)
+            }
+            Self::Counting { entries, fpr } => {
+                let mut filter = CountingBloomFilter::new_with_hash(
+                    entries,
+                    fpr,
+                    CounterWidth::Four,
+                    seed,
+                    hash,
+                );
+                hashes.iter().for_each(|&h| filter.insert_hash(h));
+                ConfiguredFilter::Counting(filter)
+            }
+            Self::Static { .. } if hash != HashAlgorithm::Xxh64 => {
+                return Err(WorkloadError::UnsupportedHash(hash));
+            }
+            Self::Static { fpr, priority, .. } => ConfiguredFilter::Static(
+                StaticFilterBuilder::from_hashes_with_seed(hashes, fpr, priority, seed),
+            ),
+        })
+    }
+}
+
+/// A filter built by `FilterConfig::build_from_hashes`, whichever variant was recommended.
+///
+/// Queries work the same on every variant, directly or through `ApproxMembership`; inserts
+/// and removals go through the variant's own filter.
+#[derive(Clone, Debug)]
+pub enum ConfiguredFilter {
+    /// A blocked filter, from `FilterConfig::Blocked` or `FilterConfig::BlockedSized`.
+    Blocked(BlockedBloomFilter),
+    /// A cuckoo filter, from `FilterConfig::Cuckoo`.
+    Cuckoo(CuckooFilter),
+    /// A counting filter, from `FilterConfig::Counting`.
+    Counting(CountingBloomFilter),
+    /// A read-only filter, from `FilterConfig::Static`.
+    Static(StaticFilter),
+}
+
+impl ConfiguredFilter {
+    /// Checks if the filter might contain the hash.
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        match self {
+            Self::Blocked(filter) => filter.may_match_hash(h),
+            Self::Cuckoo(filter) => filter.may_match_hash(h),
+            Self::Counting(filter) => filter.may_match_hash(h),
+            Self::Static(filter) => filter.may_match_hash(h),
+        }
+    }
+
+    /// Hashes the key and checks if it might be present.
+    pub fn may_match_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        match self {
+            Self::Blocked(filter) => filter.may_match_key(key),
+            _ => self.may_match_hash(
+                self.hash_algorithm()
+                    .hash_key(ApproxMembership::seed(self), key),
+            ),
+        }
+    }
+}
+
+impl ApproxMembership for ConfiguredFilter {
+    type Error = Infallible;
+
+    fn seed(&self) -> u64 {
+        match self {
+            Self::Blocked(filter) => filter.seed,
+            Self::Cuckoo(filter) => filter.seed(),
+            Self::Counting(filter) => filter.seed(),
+            Self::Static(filter) => ApproxMembership::seed(filter),
+        }
+    }
+
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        match self {
+            Self::Blocked(filter) => filter.hash_algorithm(),
+            Self::Cuckoo(filter) => filter.hash_algorithm(),
+            Self::Counting(filter) => filter.hash_algorithm(),
+            Self::Static(filter) => filter.hash_algorithm(),
+        }
+    }
+
+    fn may_match_hashes(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Result<Vec<bool>, Self::Error>> + Send {
+        ready(Ok(hashes.iter().map(|&h| self.may_match_hash(h)).collect()))
+    }
+}
+
+/// Errors produced when no filter in this crate fits a workload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorkloadError {
+    /// The memory budget is below the smallest filter, or set of filters, that can serve the
+    /// workload.
+    BudgetTooSmall { budget: usize, minimum: usize },
+    /// The recommended filter cannot hash keys with this algorithm.
+    UnsupportedHash(HashAlgorithm),
+}
+
+impl fmt::Display for WorkloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BudgetTooSmall { budget, minimum } => write!(
+                f,
+                "memory budget of {budget} bytes is below the minimum of {minimum} bytes"
+            ),
+            Self::UnsupportedHash(hash) => {
+                write!(f, "the recommended filter cannot hash keys with {hash:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkloadError {}
+
+impl WorkloadError {
+    /// Returns the category of the error, always `InvalidParameters`.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidParameters
+    }
+}
+
+/// Recommends a filter variant and sizing for a workload.
+///
+/// Static sets without deletions get a `StaticFilter`: `Priority::Space`, the smallest
+/// structure, unless `queries_per_sec` reaches `WorkloadSpec::HIGH_QUERY_RATE`, where
+/// `Priority::Speed` keeps queries to one cache miss. If the speed choice is over budget the
+/// space one is tried, and if that is too, the set is treated as dynamic. Dynamic sets get a
+/// `BlockedBloomFilter`, whatever the query rate, since it is already the fastest structure
+/// that takes inserts.
+///
+/// Workloads with deletions get a `CuckooFilter`, which at rates it can reach is smaller than
+/// a counting filter, or a `CountingBloomFilter` below `CuckooFilter::MAX_FPR`; neither can
+/// trade its rate for space, so they fail if over budget. Otherwise, when the blocked filter
+/// for `target_fpr` does not fit the memory budget, the recommendation is shrunk to the
+/// largest filter that fits, at a correspondingly higher false positive rate.
+pub fn choose_filter(workload: &WorkloadSpec) -> Result<FilterConfig, WorkloadError> {
+    if workload.deletions {
+        let config = if workload.target_fpr >= CuckooFilter::MAX_FPR {
+            FilterConfig::Cuckoo {
+                capacity: workload.entries,
+            }
+        } else {
+            FilterConfig::Counting {
+                entries: workload.entries,
+                fpr: workload.target_fpr,
+            }
+        };
+        return match workload.memory_budget {
+            Some(budget) if config.expected_bytes() > budget => {
+                Err(WorkloadError::BudgetTooSmall {
+                    budget,
+                    minimum: config.expected_bytes(),
+                })
+            }
+            _ => Ok(config),
+        };
+    }
+
+    if workload.static_set {
+        let fast = workload
+            .queries_per_sec
+            .is_some_and(|qps| qps >= WorkloadSpec::HIGH_QUERY_RATE);
+        let priorities: &[Priority] = if fast {
+            &[Priority::Speed, Priority::Space]
+        } else {
+            &[Priority::Space]
+        };
+        for &priority in priorities {
+            let config = FilterConfig::Static {
+                entries: workload.entries,
+                fpr: workload.target_fpr,
+                priority,
+            };
+            if workload
+                .memory_budget
+                .is_none_or(|budget| config.expected_bytes() <= budget)
+            {
+                return Ok(config);
+            }
+        }
+    }
+
+    let config = FilterConfig::Blocked {
+        entries: workload.entries,
+        fpr: workload.target_fpr,
+    };
+    let Some(budget) = workload.memory_budget else {
+        return Ok(config);
+    };
+    if config.expected_bytes() <= budget {
+        return Ok(config);
+    }
+
+    let block_bytes = size_of::<CacheLineBlock>();
+    let usable_bits = budget / block_bytes * CacheLineBlock::BITS;
+    let bits_per_key = usable_bits / workload.entries.max(1);
+    if bits_per_key == 0 {
+        return Err(WorkloadError::BudgetTooSmall {
+            budget,
+            minimum: workload.entries.div_ceil(CacheLineBlock::BITS).max(1) * block_bytes,
+        });
+    }
+    // Pick the rate whose rounded-up bits per key is exactly `bits_per_key`.
+    let fpr = (-(bits_per_key as f64 - 0.5) * LN_2 * LN_2).exp();
+    Ok(FilterConfig::Blocked {
+        entries: workload.entries,
+        fpr,
+    })
+}
+
+/// One of the datasets `pack_filters` allocates memory across.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetSpec {
+    /// Expected number of distinct keys.
+    pub entries: usize,
+    /// The highest false positive rate the dataset's filter may have.
+    pub max_fpr: f64,
+    /// How many queries for absent keys the filter sees, relative to the others. Its false
+    /// positive rate counts this many times toward the total.
+    pub query_weight: f64,
+}
+
+impl DatasetSpec {
+    /// Describes a dataset queried as often as the others.
+    pub fn new(entries: usize, max_fpr: f64) -> Self {
+        Self {
+            entries,
+            max_fpr,
+            query_weight: 1.0,
+        }
+    }
+
+    fn fpr(&self, num_blocks: u32) -> f64 {
+        math::blocked_fpr_for(num_blocks, self.entries)
+    }
+
+    /// The weighted drop in false positives from adding one block to `num_blocks`.
+    fn gain(&self, num_blocks: u32) -> f64 {
+        self.query_weight * (self.fpr(num_blocks) - self.fpr(num_blocks.saturating_add(1)))
+    }
+
+    /// Returns the smallest block count at or above `min` whose next block gains less than
+    /// `lambda`, which is where a filter stops being worth growing at that price.
+    fn blocks_at(&self, min: u32, lambda: f64) -> u32 {
+        let (mut lo, mut hi) = (min, min);
+        while self.gain(hi) >= lambda && hi < u32::MAX {
+            lo = hi;
+            hi = hi.saturating_mul(2);
+        }
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.gain(mid) >= lambda {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        hi
+    }
+}
+
+/// How `pack_filters` divided a memory budget, one entry per dataset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackingPlan {
+    /// The filter for each dataset, in input order.
+    pub configs: Vec<FilterConfig>,
+    /// Each filter's expected false positive rate.
+    pub expected_fprs: Vec<f64>,
+    /// The sum of the false positive rates weighted by `query_weight`, the quantity the plan
+    /// minimizes.
+    pub weighted_fpr: f64,
+    /// The bytes allocated in total.
+    pub total_bytes: usize,
+}
+
+impl PackingPlan {
+    /// Builds the planned filters, all with the same seed.
+    pub fn build(&self, seed: u64) -> Vec<BlockedBloomFilter> {
+        self.configs
+            .iter()
+            .map(|config| {
+                config
+                    .build(seed)
+                    .expect("pack_filters plans blocked filters")
+            })
+            .collect()
+    }
+}
+
+/// Divides `budget` bytes across one blocked filter per dataset, minimizing the total false
+/// positives while keeping every filter at or under its `max_fpr`.
+///
+/// Each filter first gets the fewest blocks that meet its `max_fpr`. The rest of the budget
+/// goes to whichever filters cut weighted false positives the most per block: since a filter's
+/// rate falls ever more slowly as it grows, this is found by searching for the price per
+/// block at which the filters' demands just fill the budget, then spending what rounding
+/// leaves one block at a time.
+pub fn pack_filters(datasets: &[DatasetSpec], budget: usize) -> Result<PackingPlan, WorkloadError> {
+    let block_bytes = size_of::<CacheLineBlock>();
+    let budget_blocks = (budget / block_bytes) as u64;
+    let min_blocks: Vec<u32> = datasets
+        .iter()
+        .map(|d| math::blocked_num_blocks_for(d.entries, d.max_fpr))
+        .collect();
+    let minimum: u64 = min_blocks.iter().map(|&b| b as u64).sum();
+    if minimum > budget_blocks {
+        return Err(WorkloadError::BudgetTooSmall {
+            budget,
+            minimum: minimum as usize * block_bytes,
+        });
+    }
+
+    let demand = |lambda: f64| -> Vec<u32> {
+        datasets
+            .iter()
+            .zip(&min_blocks)
+            .map(|(d, &min)| d.blocks_at(min, lambda))
+            .collect()
+    };
+    let total = |blocks: &[u32]| blocks.iter().map(|&b| b as u64).sum::<u64>();
+
+    // Search the price in log space for the lowest one whose demand fits.
+    let (mut lo, mut hi) = (f64::MIN_POSITIVE.ln(), 0f64);
+    for _ in 0..100 {
+        let mid = (lo + hi) / 2.0;
+        if total(&demand(mid.exp())) > budget_blocks {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let mut blocks = demand(hi.exp());
+    for _ in total(&blocks)..budget_blocks {
+        let best = (0..datasets.len())
+            .filter(|&i| blocks[i] < u32::MAX)
+            .max_by(|&i, &j| {
+                let (gi, gj) = (datasets[i].gain(blocks[i]), datasets[j].gain(blocks[j]));
+                gi.total_cmp(&gj)
+            });
+        match best {
+            Some(i) if datasets[i].gain(blocks[i]) > 0.0 => blocks[i] += 1,
+            _ => break,
+        }
+    }
+
+    let expected_fprs: Vec<f64> = datasets
+        .iter()
+        .zip(&blocks)
+        .map(|(d, &b)| d.fpr(b))
+        .collect();
+    Ok(PackingPlan {
+        weighted_fpr: datasets
+            .iter()
+            .zip(&expected_fprs)
+            .map(|(d, fpr)| d.query_weight * fpr)
+            .sum(),
+        total_bytes: total(&blocks) as usize * block_bytes,
+        configs: datasets
+            .iter()
+            .zip(&blocks)
+            .map(|(d, &num_blocks)| FilterConfig::BlockedSized {
+                entries: d.entries,
+                num_blocks,
+            })
+            .collect(),
+        expected_fprs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_hash;
+
+    #[test]
+    fn test_choose_filter_respects_budget() {
+        let mut spec = WorkloadSpec::new(1_000_000, 0.001);
+        let unbounded = choose_filter(&spec).unwrap();
+        assert_eq!(
+            unbounded,
+            FilterConfig::Blocked {
+                entries: 1_000_000,
+                fpr: 0.001
+            }
+        );
+
+        spec.memory_budget = Some(1_000_000);
+        let bounded = choose_filter(&spec).unwrap();
+        assert!(bounded.expected_bytes() <= 1_000_000);
+        assert!(bounded.expected_bytes() < unbounded.expected_bytes());
+
+        spec.memory_budget = Some(10);
+        assert!(matches!(
+            choose_filter(&spec),
+            Err(WorkloadError::BudgetTooSmall { .. })
+        ));
+
+        spec.deletions = true;
+        spec.memory_budget = None;
+        spec.static_set = true;
+        let cuckoo = choose_filter(&spec).unwrap();
+        assert_eq!(
+            cuckoo,
+            FilterConfig::Cuckoo {
+                capacity: 1_000_000
+            }
+        );
+        assert_eq!(
+            cuckoo.expected_bytes(),
+            CuckooFilter::new(1_000_000).slots() * 2
+        );
+        spec.target_fpr = 1e-6;
+        assert!(matches!(
+            choose_filter(&spec),
+            Ok(FilterConfig::Counting { .. })
+        ));
+        spec.memory_budget = Some(1_000_000);
+        assert!(matches!(
+            choose_filter(&spec),
+            Err(WorkloadError::BudgetTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn test_choose_filter_for_static_sets() {
+        let mut spec = WorkloadSpec {
+            static_set: true,
+            ..WorkloadSpec::new(200_000, 0.01)
+        };
+        let small = choose_filter(&spec).unwrap();
+        assert!(matches!(
+            small,
+            FilterConfig::Static {
+                priority: Priority::Space,
+                ..
+            }
+        ));
+        spec.queries_per_sec = Some(5e6);
+        let fast = choose_filter(&spec).unwrap();
+        assert!(matches!(
+            fast,
+            FilterConfig::Static {
+                priority: Priority::Speed,
+                ..
+            }
+        ));
+        assert!(small.expected_bytes() < fast.expected_bytes());
+        spec.memory_budget = Some(small.expected_bytes());
+        assert_eq!(choose_filter(&spec).unwrap(), small);
+
+        let hashes: Vec<u64> = (0..200_000u64).map(|i| key_hash(3, &i)).collect();
+        let filter = small
+            .build_from_hashes(&hashes, 3, HashAlgorithm::Xxh64)
+            .unwrap();
+        assert!(matches!(
+            filter,
+            ConfiguredFilter::Static(StaticFilter::Ribbon8(_))
+        ));
+        assert!((0..200_000u64).all(|i| filter.may_match_key(&i)));
+        assert_eq!(
+            small
+                .build_from_hashes(&hashes, 3, HashAlgorithm::Xxh3)
+                .unwrap_err(),
+            WorkloadError::UnsupportedHash(HashAlgorithm::Xxh3)
+        );
+
+        let xxh3: Vec<u64> = (0..1000u64)
+            .map(|i| HashAlgorithm::Xxh3.hash_key(3, &i))
+            .collect();
+        let mut cuckoo = FilterConfig::Cuckoo { capacity: 100 }
+            .build_from_hashes(&xxh3, 3, HashAlgorithm::Xxh3)
+            .unwrap();
+        assert!((0..1000u64).all(|i| cuckoo.may_match_key(&i)));
+        let ConfiguredFilter::Cuckoo(inner) = &mut cuckoo else {
+            panic!("{cuckoo:?}");
+        };
+        assert!(inner.remove_key(&0u64));
+        let counting = FilterConfig::Counting {
+            entries: 1000,
+            fpr: 1e-5,
+        }
+        .build_from_hashes(&xxh3, 3, HashAlgorithm::Xxh3)
+        .unwrap();
+        assert!((0..1000u64).all(|i| counting.may_match_key(&i)));
+    }
+
+    #[test]
+    fn test_pack_filters_spends_budget_where_it_helps() {
+        let datasets = [
+            DatasetSpec::new(1_000_000, 0.05),
+            DatasetSpec {
+                query_weight: 10.0,
+                ..DatasetSpec::new(100_000, 0.01)
+            },
+            DatasetSpec::new(0, 0.01),
+        ];
+        let plan = pack_filters(&datasets, 2_000_000).unwrap();
+        assert!(plan.total_bytes <= 2_000_000);
+        assert!(plan.total_bytes > 2_000_000 - 32 * 3, "{plan:?}");
+        assert!(
+            plan.expected_fprs
+                .iter()
+                .zip(&datasets)
+                .all(|(f, d)| *f <= d.max_fpr)
+        );
+        // The heavily queried dataset gets more bits per key.
+        assert!(
+            plan.expected_fprs[1] < plan.expected_fprs[0] / 10.0,
+            "{plan:?}"
+        );
+
+        // Moving a block between the first two filters cannot lower the weighted total.
+        let blocks: Vec<u32> = plan
+            .configs
+            .iter()
+            .map(|c| (c.expected_bytes() / 32) as u32)
+            .collect();
+        let shifted = datasets[0].query_weight * datasets[0].fpr(blocks[0] - 1)
+            + datasets[1].query_weight * datasets[1].fpr(blocks[1] + 1);
+        let unshifted = datasets[0].query_weight * plan.expected_fprs[0]
+            + datasets[1].query_weight * plan.expected_fprs[1];
+        assert!(shifted >= unshifted * (1.0 - 1e-9));
+
+        let filters = plan.build(3);
+        assert_eq!(filters[1].num_blocks, blocks[1]);
+        assert!(FilterConfig::Cuckoo { capacity: 100 }.build(3).is_none());
+        assert!(matches!(
+            pack_filters(&datasets, 100_000),
+            Err(WorkloadError::BudgetTooSmall { .. })
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use std::hash::Hash;
+use std::ops::BitXor;
+
+use crate::key_hash;
+
+/// A fingerprint type for `XorFilter`, implemented for `u8` and `u16`.
+pub trait XorFingerprint: Copy + Default + Eq + BitXor<Output = Self> {
+    /// Derives the fingerprint from a mixed hash.
+    fn from_hash(h: u64) -> Self;
+}
+
+impl XorFingerprint for u8 {
+    #[inline]
+    fn from_hash(h: u64) -> Self {
+        (h ^ (h >> 32)) as u8
+    }
+}
+
+impl XorFingerprint for u16 {
+    #[inline]
+    fn from_hash(h: u64) -> Self {
+        (h ^ (h >> 32)) as u16
+    }
+}
+
+/// An xor filter with 8-bit fingerprints: a false positive rate of about 0.39% at 9.84 bits
+/// per key.
+pub type XorFilter8 = XorFilter<u8>;
+
+/// An xor filter with 16-bit fingerprints: a false positive rate of about 0.0015% at 19.7 bits
+/// per key.
+pub type XorFilter16 = XorFilter<u16>;
+
+/// An immutable xor filter over a set fixed at construction.
+///
+/// Each hash maps to one slot in each third of a fingerprint array, and construction solves for
+/// fingerprints such that the three slots of every key XOR to the key's own fingerprint. A
+/// query reads the three slots and compares. The array holds `1.23 * n + 32` fingerprints, so
+/// at a given rate it takes about 30% less memory than a blocked filter, at the cost of three
+/// cache misses per query and no inserts after construction. Keys are hashed with xxh64 like
+/// `BlockedBloomFilter`, so the same seed gives the same hashes in both.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XorFilter<F> {
+    fingerprints: Vec<F>,
+    block_length: u32,
+    len: usize,
+    seed: u64,
+    mix_seed: u64,
+}
+
+/// The `fmix64` finalizer from MurmurHash3, to rehash with a new seed on each attempt.
+#[inline]
+pub(crate) fn mix(h: u64, seed: u64) -> u64 {
+    let mut h = h.wrapping_add(seed);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// Maps a 32-bit value into `0..n` without division.
+#[inline]
+fn reduce(x: u32, n: u32) -> usize {
+    ((x as u64 * n as u64) >> 32) as usize
+}
+
+/// Returns the length of each third of the fingerprint array for `size` distinct hashes.
+pub(crate) fn block_length_for(size: usize) -> u32 {
+    let capacity = 32 + (1.23 * size as f64).ceil() as usize;
+    (capacity / 3) as u32
+}
+
+#[inline]
+fn slots(hm: u64, block_length: u32) -> [usize; 3] {
+    let bl = block_length as usize;
+    [
+        reduce(hm as u32, block_length),
+        reduce(hm.rotate_left(21) as u32, block_length) + bl,
+        reduce(hm.rotate_left(42) as u32, block_length) + 2 * bl,
+    ]
+}
+
+impl<F: XorFingerprint> XorFilter<F> {
+    /// Builds a filter from key hashes made with `seed`, the seed `contains_key` hashes with.
+    ///
+    /// Duplicate hashes are ignored. Construction rehashes and retries in the rare case the
+    /// slots cannot be solved, so it takes a few passes over the hashes in the worst case.
+    pub fn from_hashes<I: IntoIterator<Item = u64>>(hashes: I, seed: u64) -> Self {
+        let mut hashes: Vec<u64> = hashes.into_iter().collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let block_length = block_length_for(hashes.len());
+        let array_len = 3 * block_length as usize;
+        let mut counts = vec![0u32; array_len];
+        let mut xors = vec![0u64; array_len];
+        let mut queue = Vec::new();
+        let mut stack = Vec::with_capacity(hashes.len());
+        let mut mix_seed = seed;
+
+        loop {
+            counts.fill(0);
+            xors.fill(0);
+            for &h in &hashes {
+                let hm = mix(h, mix_seed);
+                for slot in slots(hm, block_length) {
+                    counts[slot] += 1;
+                    xors[slot] ^= hm;
+                }
+            }
+
+            // Peel slots used by a single key until none are left.
+            queue.clear();
+            queue.extend((0..array_len).filter(|&slot| counts[slot] == 1));
+            stack.clear();
+            while let Some(slot) = queue.pop() {
+                if counts[slot] != 1 {
+                    continue;
+                }
+                let hm = xors[slot];
+                stack.push((hm, slot));
+                for other in slots(hm, block_length) {
+                    xors[other] ^= hm;
+                    counts[other] -= 1;
+                    if counts[other] == 1 {
+                        queue.push(other);
+                    }
+                }
+            }
+            if stack.len() == hashes.len() {
+                break;
+            }
+            mix_seed = mix(mix_seed, 0x9e37_79b9_7f4a_7c15);
+        }
+
+        // Assign in reverse peeling order, so each key's slot is the last of its three set.
+        let mut fingerprints = vec![F::default(); array_len];
+        for &(hm, slot) in stack.iter().rev() {
+            let [a, b, c] = slots(hm, block_length);
+            fingerprints[slot] =
+                F::from_hash(hm) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+        }
+        Self {
+            fingerprints,
+            block_length,
+            len: hashes.len(),
+            seed,
+            mix_seed,
+        }
+    }
+
+    /// Hashes the keys with `seed` and builds a filter from them.
+    pub fn from_keys<I>(keys: I, seed: u64) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        Self::from_hashes(keys.into_iter().map(|key| key_hash(seed, &key)), seed)
+    }
+
+    /// Checks if the set might contain the hash.
+    #[inline]
+    pub fn contains_hash(&self, h: u64) -> bool {
+        let hm = mix(h, self.mix_seed);
+        let [a, b, c] = slots(hm, self.block_length);
+        F::from_hash(hm) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+
+    /// Hashes the key and checks if the set might contain it.
+    #[inline]
+    pub fn contains_key<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.contains_hash(key_hash(self.seed, key))
+    }
+
+    /// Returns the number of distinct hashes the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the filter was built from no hashes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the seed keys are hashed with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the size of the fingerprint array in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.fingerprints.len() * size_of::<F>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xor_filters() {
+        let keys = (0..100_000u64).chain(0..1000);
+        let xor8 = XorFilter8::from_keys(keys.clone(), 5);
+        let xor16 = XorFilter16::from_keys(keys, 5);
+        assert_eq!(xor8.len(), 100_000);
+        assert!((0..100_000u64).all(|i| xor8.contains_key(&i) && xor16.contains_key(&i)));
+
+        let fp8 = (100_000..1_100_000u64)
+            .filter(|i| xor8.contains_key(i))
+            .count();
+        let fp16 = (100_000..1_100_000u64)
+            .filter(|i| xor16.contains_key(i))
+            .count();
+        assert!((3400..4400).contains(&fp8), "{fp8}");
+        assert!(fp16 < 40, "{fp16}");
+        assert!(xor8.size_in_bytes() < 123_100, "{}", xor8.size_in_bytes());
+
+        let empty = XorFilter8::from_hashes([], 0);
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use std::hash::Hash;
+
+use crate::{BlockedBloomFilter, CacheLineBlock, DecodeError, key_hash};
+
+/// A single-block filter, small enough to embed per row group or zone in zone-map metadata.
+///
+/// It holds one 256-bit block and sets the same 8 bits for a hash as `BlockedBloomFilter`
+/// does within a block. At 32 bytes it suits zones with a few dozen distinct values: about 25
+/// keep the false positive rate near 1%, and `estimated_fpr` reports how a zone turned out.
+/// A `Vec<MicroBloom>` is a contiguous array of blocks, so a column's filters can be stored
+/// and probed as one buffer.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MicroBloom {
+    block: CacheLineBlock,
+}
+
+impl MicroBloom {
+    /// Encoded size in bytes.
+    pub const BYTES: usize = size_of::<CacheLineBlock>();
+
+    /// Builds a filter over `values`, hashed with `seed`.
+    pub fn from_values<T: Hash>(values: impl IntoIterator<Item = T>, seed: u64) -> Self {
+        let mut filter = Self::default();
+        for value in values {
+            filter.insert_hash(key_hash(seed, &value));
+        }
+        filter
+    }
+
+    /// Inserts a hash.
+    #[inline]
+    pub fn insert_hash(&mut self, h: u64) {
+        for (w, &salt) in self
+            .block
+            .words
+            .iter_mut()
+            .zip(BlockedBloomFilter::SALT.iter())
+        {
+            *w |= 1 << ((h as u32).wrapping_mul(salt) >> 27);
+        }
+    }
+
+    /// Checks if the filter might contain the hash.
+    #[inline]
+    pub fn may_match_hash(&self, h: u64) -> bool {
+        self.block
+            .words
+            .iter()
+            .zip(BlockedBloomFilter::SALT.iter())
+            .all(|(&w, &salt)| w & (1 << ((h as u32).wrapping_mul(salt) >> 27)) != 0)
+    }
+
+    /// Returns the false positive rate implied by the bits set, the product of each word's
+    /// fill.
+    pub fn estimated_fpr(&self) -> f64 {
+        self.block
+            .words
+            .iter()
+            .map(|w| w.count_ones() as f64 / 32.0)
+            .product()
+    }
+
+    /// Encodes the filter as eight little-endian words.
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0; Self::BYTES];
+        for (chunk, w) in bytes.chunks_exact_mut(4).zip(self.block.words) {
+            chunk.copy_from_slice(&w.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a filter written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Self {
+        Self {
+            block: CacheLineBlock {
+                words: std::array::from_fn(|i| {
+                    u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap())
+                }),
+            },
+        }
+    }
+}
+
+/// Builds one `MicroBloom` per zone, in order, from each zone's values.
+pub fn build_zone_filters<Z, T>(zones: impl IntoIterator<Item = Z>, seed: u64) -> Vec<MicroBloom>
+where
+    Z: IntoIterator<Item = T>,
+    T: Hash,
+{
+    zones
+        .into_iter()
+        .map(|values| MicroBloom::from_values(values, seed))
+        .collect()
+}
+
+/// Encodes zone filters back to back, `MicroBloom::BYTES` each.
+pub fn zone_filters_to_bytes(filters: &[MicroBloom]) -> Vec<u8> {
+    filters.iter().flat_map(MicroBloom::to_bytes).collect()
+}
+
+/// Decodes zone filters written by `zone_filters_to_bytes`.
+pub fn zone_filters_from_bytes(bytes: &[u8]) -> Result<Vec<MicroBloom>, DecodeError> {
+    if !bytes.len().is_multiple_of(MicroBloom::BYTES) {
+        return Err(DecodeError::LengthMismatch {
+            expected: bytes.len().next_multiple_of(MicroBloom::BYTES),
+            actual: bytes.len(),
+        });
+    }
+    Ok(bytes
+        .chunks_exact(MicroBloom::BYTES)
+        .map(|chunk| MicroBloom::from_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+/// Probes one hash against many zone filters, writing the indices of the zones that might
+/// contain it to `selection` and returning how many there are.
+pub fn select_zones(filters: &[MicroBloom], h: u64, selection: &mut Vec<u32>) -> usize {
+    selection.clear();
+    selection.resize(filters.len(), 0);
+    // Same branch-free compaction as `BlockedBloomFilter::select_hashes`.
+    let mut selected = 0;
+    for (i, filter) in filters.iter().enumerate() {
+        selection[selected] = i as u32;
+        selected += filter.may_match_hash(h) as usize;
+    }
+    selection.truncate(selected);
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_filters_select_matching_zones() {
+        let zones: Vec<Vec<u64>> = (0..100u64)
+            .map(|z| (z * 20..z * 20 + 20).collect())
+            .collect();
+        let filters = build_zone_filters(&zones, 3);
+        assert!(filters.iter().all(|f| f.estimated_fpr() < 0.02));
+
+        let bytes = zone_filters_to_bytes(&filters);
+        assert_eq!(bytes.len(), 100 * MicroBloom::BYTES);
+        assert_eq!(zone_filters_from_bytes(&bytes).unwrap(), filters);
+        assert!(zone_filters_from_bytes(&bytes[1..]).is_err());
+
+        let mut selection = Vec::new();
+        for value in [0u64, 555, 1999] {
+            select_zones(&filters, key_hash(3, &value), &mut selection);
+            assert!(selection.contains(&((value / 20) as u32)));
+            assert!(selection.len() <= 4, "{selection:?}");
+        }
+    }
+}